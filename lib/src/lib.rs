@@ -41,5 +41,25 @@ pub const DEFAULT_NUM_CONSECUTIVE_LEADER_SLOTS: u64 = 4;
 pub const DEFAULT_CHANNEL_CAPACITY: usize = 1_000;
 // Batch size for sending PoH records.
 pub const DEFAULT_BATCH_SIZE: usize = 64;
+// Ring buffer capacity between the hashing loop and record delivery (rounded up to a power of two).
+pub const DEFAULT_RING_CAPACITY: usize = 4_096;
 // Use spinlock for precise timing under threshold.
 pub const DEFAULT_SPINLOCK_THRESHOLD_US: u64 = 250;
+// A tick finishing this far behind its scheduled deadline counts as slipping (one full tick).
+pub const DEFAULT_SLIP_THRESHOLD_US: u64 = DEFAULT_US_PER_TICK;
+// Consecutive slipping ticks before the slip policy is applied (one slot).
+pub const DEFAULT_SLIP_TOLERANCE_TICKS: u64 = DEFAULT_TICKS_PER_SLOT;
+// Duration of the startup hash-rate self-test in milliseconds.
+pub const DEFAULT_SELF_TEST_MS: u64 = 200;
+// Minimum spare hash rate over DEFAULT_HASHES_PER_SECOND required to start (10%).
+pub const DEFAULT_MIN_HASH_HEADROOM: f64 = 0.10;
+// Time a slot waits for witness responses before it is delivered and silent witnesses are dropped.
+pub const DEFAULT_WITNESS_TIMEOUT_MS: u64 = 5_000;
+// Slot hashes events can reference, an event may be tied to a hash up to this many slots back (about a minute).
+pub const DEFAULT_RECENT_SLOT_HASHES: usize = 150;
+// Size after which the on-disk ledger starts a new segment file (64 MiB).
+pub const DEFAULT_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+// zstd level for compressed ledger segments, favours speed since segments are compressed while generating.
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+// Records inserted per SQLite transaction (one slot).
+pub const DEFAULT_SQLITE_BATCH_SIZE: usize = DEFAULT_TICKS_PER_SLOT as usize;
@@ -1,22 +1,164 @@
-pub enum OutputType {
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// Where records are delivered, any number can be active in one run.
+pub enum Output {
     Terminal,
     JsonFile(String),
+    JsonLinesFile(String),
+    BinaryFile(String),
+    CsvFile(String),
+    Socket(String),
+    Sqlite(String),
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        return match self {
+            Output::Terminal => write!(f, "terminal"),
+            Output::JsonFile(filename) => write!(f, "file {}", filename),
+            Output::JsonLinesFile(path) if path == "-" => write!(f, "stdout"),
+            Output::JsonLinesFile(path) | Output::BinaryFile(path) | Output::CsvFile(path) => write!(f, "file {}", path),
+            Output::Socket(path) => write!(f, "socket {}", path),
+            Output::Sqlite(path) => write!(f, "database {}", path),
+        };
+    }
+}
+
+/// Receipt commands run instead of generating, given as the first argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Record a digest in the ledger and save a receipt for it.
+    Stamp,
+    VerifyReceipt,
+    /// Extend a receipt to the newest signed slot hash of the ledger.
+    UpgradeReceipt,
+}
+
+pub struct Args {
+    pub command: Option<Command>,
+    pub outputs: Vec<Output>,
+    pub allow_slow_hardware: bool,
+    pub slip_policy: Option<String>,
+    pub slip_threshold_us: Option<u64>,
+    pub slip_tolerance_ticks: Option<u64>,
+    pub poh_core: Option<usize>,
+    pub poh_priority: u8,
+    pub delivery_core: Option<usize>,
+    pub ingest_core: Option<usize>,
+    pub sparse: bool,
+    pub commit_events: Option<String>,
+    pub identity: Option<String>,
+    pub witness: Option<String>,
+    pub witness_state: Option<String>,
+    pub witnesses: Vec<String>,
+    pub witness_threshold: Option<usize>,
+    pub ledger: Option<String>,
+    pub fsync: Option<String>,
+    pub compress: bool,
+    pub retain: Option<String>,
+    pub digest: Option<String>,
+    pub receipt: Option<String>,
+    pub public_key: Option<String>,
+    pub trust_receipt_key: bool,
 }
 
 pub fn print_usage() {
     println!("Proof of History (PoH) with output options");
     println!("Usage:");
-    println!("  Run this program [--json=FILENAME]");
+    println!("  Run this program [--json=FILENAME] [--jsonl=PATH] [--bin=FILENAME] [--csv=PATH] [--socket=PATH] [--sqlite=PATH]");
+    println!("                   [--no-terminal] [--allow-slow-hardware] [--slip-policy=warn|marker|abort] [--slip-threshold-us=N]");
+    println!("                   [--slip-tolerance=N] [--poh-core=N] [--poh-priority=N] [--delivery-core=N] [--ingest-core=N] [--sparse]");
+    println!("                   [--commit-events=FILE] [--identity=FILE] [--witnesses=SOCKET,...] [--witness-threshold=N]");
+    println!("                   [--ledger=DIR] [--fsync=never|slot|record] [--compress] [--retain=epochs:N|bytes:N|after:MS]");
+    println!("  Run as a witness  --witness=SOCKET --identity=FILE --witness-state=FILE");
+    println!("  Stamp a digest    stamp --ledger=DIR --identity=FILE --digest=HEX --receipt=FILE");
+    println!("  Check a receipt   verify-receipt --receipt=FILE --public-key=HEX|--trust-receipt-key");
+    println!("  Upgrade a receipt upgrade-receipt --receipt=FILE --ledger=DIR");
     println!();
     println!("Options:");
-    println!("--json=FILENAME     Save output in JSON format to FILENAME");
-    println!("Without options     Print output to terminal (default)");
+    println!("--json=FILENAME         Save output in JSON format to FILENAME");
+    println!("--jsonl=PATH            Stream output as JSON Lines to PATH, or to stdout for -");
+    println!("--bin=FILENAME          Stream output in the binary ledger format to FILENAME");
+    println!("--csv=PATH              Stream output as CSV rows to PATH");
+    println!("--socket=PATH           Stream output as JSON Lines to the Unix socket listening at PATH");
+    println!("--sqlite=PATH           Store output in the SQLite database at PATH, created if missing");
+    println!("--no-terminal           Don't show the live record view");
+    println!("--allow-slow-hardware   Start even if the hash-rate self-test shows too little headroom");
+    println!("--slip-policy=POLICY    What to do on a sustained schedule slip: warn (default), marker to record it in the chain, or abort");
+    println!("--slip-threshold-us=N   Lateness in microseconds from which a tick counts as late");
+    println!("--slip-tolerance=N      Consecutive late ticks before the slip policy applies");
+    println!("--poh-core=N            Pin the hashing thread to core N");
+    println!("--poh-priority=N        Raise the hashing thread priority to N (1-99)");
+    println!("--delivery-core=N       Pin the record delivery thread to core N");
+    println!("--ingest-core=N         Pin the event ingestion thread to core N");
+    println!("--sparse                Only emit records at events and slot boundaries");
+    println!("--commit-events=FILE    Mix only event hashes into the chain, the payloads are kept in FILE and left out of every output");
+    println!("--identity=FILE         Sign slot hashes with the keypair in FILE, generated if missing");
+    println!("--witnesses=SOCKET,...  Have the witnesses listening on these Unix sockets co-sign every slot");
+    println!("--witness-threshold=N   Witness signatures a slot needs to be finalised (default: majority)");
+    println!("--witness=SOCKET        Serve as a witness on SOCKET instead of generating");
+    println!("--witness-state=FILE    Keep the last co-signed slot of every generator in FILE across restarts");
+    println!("--ledger=DIR            Append records to the on-disk ledger in DIR, continuing from its last record");
+    println!("--fsync=POLICY          When the ledger syncs to disk: never, slot (default) or record");
+    println!("--compress              Compress closed ledger segments with zstd, slots stay readable without decompressing the whole file");
+    println!("--retain=POLICY         Delete old ledger segments, keeping the last N epochs, the last N bytes or records after MS ms of chain time");
+    println!("--digest=HEX            32-byte digest to stamp, e.g. the SHA-256 of a document");
+    println!("--receipt=FILE          Timestamp receipt, JSON when FILE ends in .json and binary otherwise");
+    println!("--public-key=HEX        Generator key to check a receipt against");
+    println!("--trust-receipt-key     Check a receipt against the generator key it names itself, which proves nothing about who signed it");
+    println!("Output options can be combined, the live view is shown in the terminal unless --no-terminal is given.");
+}
+
+fn parse_value<T: FromStr>(arg: &str, prefix: &str) -> Result<T, String> {
+    let value: &str = arg.strip_prefix(prefix).unwrap_or_default();
+    return value
+        .parse::<T>()
+        .map_err(|_| format!("Error: Invalid value for {}: {}", prefix.trim_end_matches('='), value));
 }
 
-pub fn parse_args(args: &[String]) -> Result<OutputType, String> {
-    let mut output_type: OutputType = OutputType::Terminal;
+// Check that `value` is 32 hex-encoded bytes, as digests and keys are.
+fn parse_hash(value: &str, option: &str) -> Result<String, String> {
+    if value.len() != 64 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Error: {} must be 64 hex characters: {}", option, value));
+    }
+    return Ok(value.to_string());
+}
+
+pub fn parse_args(args: &[String]) -> Result<Args, String> {
+    let command: Option<Command> = match args.get(1).map(String::as_str) {
+        Some("stamp") => Some(Command::Stamp),
+        Some("verify-receipt") => Some(Command::VerifyReceipt),
+        Some("upgrade-receipt") => Some(Command::UpgradeReceipt),
+        _ => None,
+    };
+    let mut outputs: Vec<Output> = Vec::new();
+    let mut terminal: bool = true;
+    let mut allow_slow_hardware: bool = false;
+    let mut slip_policy: Option<String> = None;
+    let mut slip_threshold_us: Option<u64> = None;
+    let mut slip_tolerance_ticks: Option<u64> = None;
+    let mut poh_core: Option<usize> = None;
+    let mut poh_priority: u8 = 0;
+    let mut delivery_core: Option<usize> = None;
+    let mut ingest_core: Option<usize> = None;
+    let mut sparse: bool = false;
+    let mut commit_events: Option<String> = None;
+    let mut identity: Option<String> = None;
+    let mut witness: Option<String> = None;
+    let mut witness_state: Option<String> = None;
+    let mut witnesses: Vec<String> = Vec::new();
+    let mut witness_threshold: Option<usize> = None;
+    let mut ledger: Option<String> = None;
+    let mut fsync: Option<String> = None;
+    let mut compress: bool = false;
+    let mut retain: Option<String> = None;
+    let mut digest: Option<String> = None;
+    let mut receipt: Option<String> = None;
+    let mut public_key: Option<String> = None;
+    let mut trust_receipt_key: bool = false;
     // Check arguments for output mode.
-    for arg in args.iter().skip(1) {
+    for arg in args.iter().skip(1usize.saturating_add(usize::from(command.is_some()))) {
         if arg == "--help" || arg == "-h" {
             return Err(String::from("Help requested"));
         } else if arg.starts_with("--json=") {
@@ -24,10 +166,176 @@ pub fn parse_args(args: &[String]) -> Result<OutputType, String> {
             if filename.is_empty() {
                 return Err(String::from("Error: The filename cannot be empty"));
             }
-            output_type = OutputType::JsonFile(filename);
+            outputs.push(Output::JsonFile(filename));
+        } else if arg.starts_with("--jsonl=") {
+            let path: String = arg.replace("--jsonl=", "");
+            if path.is_empty() {
+                return Err(String::from("Error: The path cannot be empty"));
+            }
+            outputs.push(Output::JsonLinesFile(path));
+        } else if arg.starts_with("--bin=") {
+            let filename: String = arg.replace("--bin=", "");
+            if filename.is_empty() {
+                return Err(String::from("Error: The filename cannot be empty"));
+            }
+            outputs.push(Output::BinaryFile(filename));
+        } else if arg.starts_with("--csv=") {
+            let path: String = arg.replace("--csv=", "");
+            if path.is_empty() {
+                return Err(String::from("Error: The path cannot be empty"));
+            }
+            outputs.push(Output::CsvFile(path));
+        } else if arg.starts_with("--socket=") {
+            let path: String = arg.replace("--socket=", "");
+            if path.is_empty() {
+                return Err(String::from("Error: The socket path cannot be empty"));
+            }
+            outputs.push(Output::Socket(path));
+        } else if arg.starts_with("--sqlite=") {
+            let path: String = arg.replace("--sqlite=", "");
+            if path.is_empty() {
+                return Err(String::from("Error: The database path cannot be empty"));
+            }
+            outputs.push(Output::Sqlite(path));
+        } else if arg == "--no-terminal" {
+            terminal = false;
+        } else if arg == "--trust-receipt-key" {
+            trust_receipt_key = true;
+        } else if arg == "--allow-slow-hardware" {
+            allow_slow_hardware = true;
+        } else if arg.starts_with("--slip-policy=") {
+            let policy: String = arg.replace("--slip-policy=", "");
+            if !["warn", "marker", "abort"].contains(&policy.as_str()) {
+                return Err(format!("Error: Invalid value for --slip-policy: {}", policy));
+            }
+            slip_policy = Some(policy);
+        } else if arg.starts_with("--slip-threshold-us=") {
+            slip_threshold_us = Some(parse_value(arg, "--slip-threshold-us=")?);
+        } else if arg.starts_with("--slip-tolerance=") {
+            let ticks: u64 = parse_value(arg, "--slip-tolerance=")?;
+            if ticks == 0 {
+                return Err(String::from("Error: --slip-tolerance must be greater than 0"));
+            }
+            slip_tolerance_ticks = Some(ticks);
+        } else if arg.starts_with("--poh-core=") {
+            poh_core = Some(parse_value(arg, "--poh-core=")?);
+        } else if arg.starts_with("--poh-priority=") {
+            poh_priority = parse_value(arg, "--poh-priority=")?;
+            // Real-time priorities run from 1 to 99 and 0 leaves it unset, the thread would only refuse anything else once it is already spawned.
+            if poh_priority > 99 {
+                return Err(format!("Error: --poh-priority must be between 1 and 99, or 0 to leave it unset: {}", poh_priority));
+            }
+        } else if arg.starts_with("--delivery-core=") {
+            delivery_core = Some(parse_value(arg, "--delivery-core=")?);
+        } else if arg.starts_with("--ingest-core=") {
+            ingest_core = Some(parse_value(arg, "--ingest-core=")?);
+        } else if arg == "--sparse" {
+            sparse = true;
+        } else if arg.starts_with("--commit-events=") {
+            let path: String = arg.replace("--commit-events=", "");
+            if path.is_empty() {
+                return Err(String::from("Error: The event store path cannot be empty"));
+            }
+            commit_events = Some(path);
+        } else if arg.starts_with("--identity=") {
+            let filename: String = arg.replace("--identity=", "");
+            if filename.is_empty() {
+                return Err(String::from("Error: The identity filename cannot be empty"));
+            }
+            identity = Some(filename);
+        } else if arg.starts_with("--witnesses=") {
+            witnesses = arg.replace("--witnesses=", "").split(',').filter(|s| !s.is_empty()).map(String::from).collect();
+        } else if arg.starts_with("--witness-threshold=") {
+            witness_threshold = Some(parse_value(arg, "--witness-threshold=")?);
+        } else if arg.starts_with("--witness-state=") {
+            let filename: String = arg.replace("--witness-state=", "");
+            if filename.is_empty() {
+                return Err(String::from("Error: The witness state filename cannot be empty"));
+            }
+            witness_state = Some(filename);
+        } else if arg.starts_with("--witness=") {
+            let socket: String = arg.replace("--witness=", "");
+            if socket.is_empty() {
+                return Err(String::from("Error: The witness socket cannot be empty"));
+            }
+            witness = Some(socket);
+        } else if arg.starts_with("--ledger=") {
+            let dir: String = arg.replace("--ledger=", "");
+            if dir.is_empty() {
+                return Err(String::from("Error: The ledger directory cannot be empty"));
+            }
+            ledger = Some(dir);
+        } else if arg.starts_with("--fsync=") {
+            let policy: String = arg.replace("--fsync=", "");
+            if !["never", "slot", "record"].contains(&policy.as_str()) {
+                return Err(format!("Error: Invalid value for --fsync: {}", policy));
+            }
+            fsync = Some(policy);
+        } else if arg == "--compress" {
+            compress = true;
+        } else if arg.starts_with("--retain=") {
+            let policy: String = arg.replace("--retain=", "");
+            let valid: bool = policy
+                .split_once(':')
+                .is_some_and(|(kind, value)| ["epochs", "bytes", "after"].contains(&kind) && value.parse::<u64>().is_ok());
+            if !valid {
+                return Err(format!("Error: Invalid value for --retain: {}", policy));
+            }
+            retain = Some(policy);
+        } else if arg.starts_with("--digest=") {
+            digest = Some(parse_hash(&arg.replace("--digest=", ""), "--digest")?);
+        } else if arg.starts_with("--receipt=") {
+            let path: String = arg.replace("--receipt=", "");
+            if path.is_empty() {
+                return Err(String::from("Error: The receipt path cannot be empty"));
+            }
+            receipt = Some(path);
+        } else if arg.starts_with("--public-key=") {
+            public_key = Some(parse_hash(&arg.replace("--public-key=", ""), "--public-key")?);
         } else {
             return Err(format!("Unrecognized argument: {}", arg));
         }
     }
-    return Ok(output_type);
+    let missing: Option<&str> = match command {
+        Some(Command::Stamp) if ledger.is_none() => Some("--ledger"),
+        Some(Command::Stamp) if identity.is_none() => Some("--identity"),
+        Some(Command::Stamp) if digest.is_none() => Some("--digest"),
+        Some(Command::UpgradeReceipt) if ledger.is_none() => Some("--ledger"),
+        Some(Command::VerifyReceipt) if public_key.is_none() && !trust_receipt_key => Some("--public-key (or --trust-receipt-key)"),
+        Some(_) if receipt.is_none() => Some("--receipt"),
+        _ => None,
+    };
+    if let Some(option) = missing {
+        return Err(format!("Error: {} is required for this command", option));
+    }
+    if terminal {
+        outputs.insert(0, Output::Terminal);
+    }
+    return Ok(Args {
+        command,
+        outputs,
+        allow_slow_hardware,
+        slip_policy,
+        slip_threshold_us,
+        slip_tolerance_ticks,
+        poh_core,
+        poh_priority,
+        delivery_core,
+        ingest_core,
+        sparse,
+        commit_events,
+        identity,
+        witness,
+        witness_state,
+        witnesses,
+        witness_threshold,
+        ledger,
+        fsync,
+        compress,
+        retain,
+        digest,
+        receipt,
+        public_key,
+        trust_receipt_key,
+    });
 }
@@ -1,15 +1,68 @@
 use std::fs::File;
-use std::io::{Result, Write};
+use std::io::{BufWriter, Result, Write, stdout};
 
 use serde::Serialize;
-use serde_json::to_string_pretty;
+use serde_json::to_writer_pretty;
 
-pub fn write<T: Serialize>(records: &[T], filename: &str) -> Result<()> {
-    let json: String = to_string_pretty(records)?;
-    let mut file: File = File::create(format!("target/{}", filename))?;
+use crate::DEFAULT_BATCH_SIZE;
 
-    file.write_all(json.as_bytes())?;
-    println!("Records saved to {}", filename);
+/// Write `records` as one pretty-printed JSON array to the file at `path`.
+pub fn write<T: Serialize>(records: &[T], path: &str) -> Result<()> {
+    let mut writer: BufWriter<File> = BufWriter::new(File::create(path)?);
+
+    to_writer_pretty(&mut writer, records)?;
+    writer.flush()?;
 
     return Ok(());
 }
+
+/// Streams records as JSON Lines, one compact document per line, so memory use stays flat however long the run.
+pub struct JsonLinesWriter<W: Write> {
+    pub writer: BufWriter<W>,
+    /// Records written since the last flush.
+    pub pending: usize,
+    /// Records per flush.
+    pub batch_size: usize,
+}
+
+impl JsonLinesWriter<Box<dyn Write>> {
+    /// Write to the file at `path`, or to stdout for `-`.
+    pub fn create(path: &str) -> Result<Self> {
+        let writer: Box<dyn Write> = match path {
+            "-" => Box::new(stdout()),
+            _ => Box::new(File::create(path)?),
+        };
+        return Ok(Self::new(writer));
+    }
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(writer: W) -> Self {
+        return Self {
+            writer: BufWriter::new(writer),
+            pending: 0,
+            batch_size: DEFAULT_BATCH_SIZE,
+        };
+    }
+
+    /// Append `record` as one line, flushing once a batch is complete.
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.pending = self.pending.saturating_add(1);
+        if self.pending >= self.batch_size {
+            self.flush()?;
+        }
+        return Ok(());
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.pending = 0;
+        return Ok(());
+    }
+
+    pub fn into_inner(self) -> Result<W> {
+        return self.writer.into_inner().map_err(|e| e.into_error());
+    }
+}
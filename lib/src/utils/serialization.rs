@@ -18,3 +18,78 @@ pub fn deserialize<'a, T: Deserializer<'a>>(deserializer: T) -> Result<[u8; 32],
 
     return Ok(arr);
 }
+
+/// Hex encoding for variable-length byte fields.
+pub mod bytes {
+    use hex::{decode, encode};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<T: Serializer>(bytes: &[u8], serializer: T) -> Result<T::Ok, T::Error> {
+        return serializer.serialize_str(&encode(bytes));
+    }
+
+    pub fn deserialize<'a, T: Deserializer<'a>>(deserializer: T) -> Result<Vec<u8>, T::Error> {
+        let str: String = String::deserialize(deserializer)?;
+        return decode(str).map_err(Error::custom);
+    }
+}
+
+/// Hex encoding for optional variable-length byte fields, pair with `#[serde(default)]`.
+pub mod optional_bytes {
+    use hex::{decode, encode};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<T: Serializer>(bytes: &Option<Vec<u8>>, serializer: T) -> Result<T::Ok, T::Error> {
+        return match bytes {
+            Some(bytes) => serializer.serialize_str(&encode(bytes)),
+            None => serializer.serialize_none(),
+        };
+    }
+
+    pub fn deserialize<'a, T: Deserializer<'a>>(deserializer: T) -> Result<Option<Vec<u8>>, T::Error> {
+        return Option::<String>::deserialize(deserializer)?.map(|str| decode(str).map_err(Error::custom)).transpose();
+    }
+}
+
+/// Hex encoding for optional fixed-size byte arrays such as hashes and signatures, pair with `#[serde(default)]`.
+pub mod optional {
+    use hex::{decode, encode};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<T: Serializer, const N: usize>(bytes: &Option<[u8; N]>, serializer: T) -> Result<T::Ok, T::Error> {
+        return match bytes {
+            Some(bytes) => serializer.serialize_str(&encode(bytes)),
+            None => serializer.serialize_none(),
+        };
+    }
+
+    pub fn deserialize<'a, T: Deserializer<'a>, const N: usize>(deserializer: T) -> Result<Option<[u8; N]>, T::Error> {
+        let Some(str) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        let bytes: Vec<u8> = decode(str).map_err(Error::custom)?;
+        let arr: [u8; N] = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| Error::custom(format!("Expected {} bytes, got {}", N, bytes.len())))?;
+
+        return Ok(Some(arr));
+    }
+}
+
+/// Hex encoding for fixed-size byte arrays other than hashes, such as signatures.
+pub mod array {
+    use hex::{decode, encode};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<T: Serializer, const N: usize>(bytes: &[u8; N], serializer: T) -> Result<T::Ok, T::Error> {
+        return serializer.serialize_str(&encode(bytes));
+    }
+
+    pub fn deserialize<'a, T: Deserializer<'a>, const N: usize>(deserializer: T) -> Result<[u8; N], T::Error> {
+        let str: String = String::deserialize(deserializer)?;
+        let bytes: Vec<u8> = decode(str).map_err(Error::custom)?;
+        return bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| Error::custom(format!("Expected {} bytes, got {}", N, bytes.len())));
+    }
+}
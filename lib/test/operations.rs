@@ -1,8 +1,13 @@
 #[cfg(test)]
 mod operations {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
     use std::time::{Duration, Instant};
 
+    use lib::utils::args::parse_args;
     use lib::utils::hash::extend_hash_chain;
+    use lib::utils::json::{JsonLinesWriter, write};
 
     use ring::digest::{Context, Digest, SHA256};
 
@@ -27,6 +32,44 @@ mod operations {
         println!("Optimized: {:?} for {} iterations.", optimized_duration, iterations);
     }
 
+    #[test]
+    fn test_poh_priority_range() {
+        let parse = |value: &str| parse_args(&["poh".to_string(), format!("--poh-priority={}", value)]).map(|args| args.poh_priority);
+        assert_eq!(parse("1"), Ok(1));
+        assert_eq!(parse("99"), Ok(99));
+        assert_eq!(parse("0"), Ok(0));
+        // Out of range values are refused up front rather than by the spawned hashing thread.
+        for value in ["100", "255"] {
+            assert!(parse(value).unwrap_err().contains("between 1 and 99"), "Accepted priority {}.", value);
+        }
+    }
+
+    #[test]
+    fn test_json_lines_writer() {
+        let mut writer: JsonLinesWriter<Vec<u8>> = JsonLinesWriter::new(Vec::new());
+        writer.batch_size = 2;
+        for i in 0..3u64 {
+            writer.write(&vec![i; 2]).unwrap();
+        }
+        // A full batch is flushed right away, the rest waits for the next one.
+        assert_eq!(writer.pending, 1);
+        assert_eq!(writer.writer.get_ref().len(), "[0,0]\n[1,1]\n".len());
+
+        let bytes: Vec<u8> = writer.into_inner().unwrap();
+        let lines: Vec<Vec<u64>> = String::from_utf8(bytes).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines, vec![vec![0, 0], vec![1, 1], vec![2, 2]]);
+    }
+
+    #[test]
+    fn test_json_write() {
+        let path: PathBuf = env::temp_dir().join(format!("lib-json-{}.json", std::process::id()));
+        write(&[vec![1u64, 2], vec![3]], path.to_str().unwrap()).unwrap();
+        // Written where asked, not under a fixed directory.
+        let records: Vec<Vec<u64>> = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(records, vec![vec![1, 2], vec![3]]);
+        fs::remove_file(&path).unwrap();
+    }
+
     // Reference implementation for testing.
     fn manual_hash_chain(prev_hash: &[u8; 32], iterations: u64) -> [u8; 32] {
         let mut current_hash: [u8; 32] = *prev_hash;
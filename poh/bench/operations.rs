@@ -1,9 +1,10 @@
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
 use std::time::{Duration, Instant};
 
-use poh::types::{PoH, PoHRecord};
+use poh::types::{Event, PoH, PoHEntry, PoHRecord, RingBuffer, RingConsumer, RingProducer};
 
 use lib::utils::hash;
-use lib::{DEFAULT_HASHES_PER_TICK, DEFAULT_US_PER_TICK};
+use lib::{DEFAULT_BATCH_SIZE, DEFAULT_CHANNEL_CAPACITY, DEFAULT_HASHES_PER_TICK, DEFAULT_RING_CAPACITY, DEFAULT_US_PER_TICK};
 
 use criterion::{BenchmarkGroup, BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
 
@@ -53,8 +54,10 @@ fn bench_poh_core(c: &mut Criterion) {
     group.bench_function("insert_event", |b| {
         let seed: [u8; 64] = [b'0'; 64];
         let mut poh: PoH = PoH::new(&seed);
-        let event_data: &'static [u8; 38] = b"This is an event for benchmark testing";
-        b.iter(|| poh.insert_event(black_box(event_data)))
+        let event: Event = Event::Data {
+            bytes: b"This is an event for benchmark testing".to_vec(),
+        };
+        b.iter(|| poh.insert_event(black_box(event.clone())))
     });
     group.finish();
 }
@@ -105,8 +108,10 @@ fn bench_poh_generation(c: &mut Criterion) {
                 for i in 0..tick_count {
                     let record: PoHRecord = if i % 10 == 0 {
                         // Every 10th tick, insert an event.
-                        let event_data = format!("Event at tick {}", i);
-                        poh.insert_event(event_data.as_bytes())
+                        let event: Event = Event::Data {
+                            bytes: format!("Event at tick {}", i).into_bytes(),
+                        };
+                        poh.insert_event(event)
                     } else {
                         poh.next_tick()
                     };
@@ -194,6 +199,85 @@ fn bench_realtime_performance(c: &mut Criterion) {
     group.finish();
 }
 
+// Benchmark the jitter the hand-off adds to every tick of the hashing loop.
+// Only the producer side is timed, draining happens outside the measurement so both paths see an idle consumer.
+// Each record is timed on its own and the reported time per iteration is the 99th percentile of those, not the mean,
+// so a path that is cheap on average but stalls on batch flushes or allocations shows up.
+fn bench_record_handoff(c: &mut Criterion) {
+    let mut group: BenchmarkGroup<'_, criterion::measurement::WallTime> = c.benchmark_group("Record Hand-off Jitter");
+    group.warm_up_time(Duration::from_millis(500));
+    group.measurement_time(Duration::from_secs(3));
+    // Previous path: build a full record, batch it, then send each one through a bounded channel.
+    group.bench_function("mpsc_batch_send_p99", |b| {
+        let (tx, rx): (SyncSender<PoHRecord>, Receiver<PoHRecord>) = sync_channel(DEFAULT_CHANNEL_CAPACITY);
+        let event_data: &'static [u8; 22] = b"Event at tick 1000000.";
+        let mut batch: Vec<PoHRecord> = Vec::with_capacity(DEFAULT_BATCH_SIZE);
+        let mut tick: u64 = 0;
+
+        b.iter_custom(|iters| {
+            let mut samples: Vec<Duration> = Vec::with_capacity(iters as usize);
+            for _ in 0..iters {
+                tick = tick.wrapping_add(1);
+                let start: Instant = Instant::now();
+                batch.push(PoHRecord {
+                    tick_index: tick,
+                    slot_index: 0,
+                    epoch_index: 0,
+                    hash: [5u8; 32],
+                    timestamp_ms: 0,
+                    num_hashes: DEFAULT_HASHES_PER_TICK,
+                    commitment: None,
+                    event: if tick % 10 == 0 { Some(Event::Data { bytes: event_data.to_vec() }) } else { None },
+                    signature: None,
+                    witnesses: Vec::new(),
+                });
+                if batch.len() >= DEFAULT_BATCH_SIZE {
+                    for record in batch.drain(..) {
+                        tx.send(black_box(record)).unwrap();
+                    }
+                }
+                samples.push(start.elapsed());
+                if batch.is_empty() {
+                    while rx.try_recv().is_ok() {}
+                }
+            }
+            tail_latency(&mut samples, iters)
+        });
+    });
+    // Ring path: copy a fixed-size entry into a pre-allocated slot.
+    group.bench_function("spsc_ring_push_p99", |b| {
+        let (mut producer, mut consumer): (RingProducer<PoHEntry>, RingConsumer<PoHEntry>) = RingBuffer::channel(DEFAULT_RING_CAPACITY);
+        let mut tick: u64 = 0;
+
+        b.iter_custom(|iters| {
+            let mut samples: Vec<Duration> = Vec::with_capacity(iters as usize);
+            for _ in 0..iters {
+                tick = tick.wrapping_add(1);
+                let start: Instant = Instant::now();
+                let entry: PoHEntry = PoHEntry {
+                    tick_index: tick,
+                    hash: [5u8; 32],
+                    has_event: tick % 10 == 0,
+                    ..Default::default()
+                };
+                producer.push(black_box(entry)).unwrap();
+                samples.push(start.elapsed());
+                while consumer.try_pop().is_some() {}
+            }
+            tail_latency(&mut samples, iters)
+        });
+    });
+    group.finish();
+}
+
+// 99th percentile of the per-record `samples`, scaled by `iters` so criterion reports it as the time per iteration.
+fn tail_latency(samples: &mut [Duration], iters: u64) -> Duration {
+    samples.sort_unstable();
+    let index: usize = samples.len().saturating_mul(99).checked_div(100).unwrap_or(0).min(samples.len().saturating_sub(1));
+    let p99: Duration = samples.get(index).copied().unwrap_or_default();
+    return p99.saturating_mul(u32::try_from(iters).unwrap_or(u32::MAX));
+}
+
 criterion_group!(
     benches,
     bench_hash_operations,
@@ -202,5 +286,6 @@ criterion_group!(
     bench_poh_generation,
     bench_hash_algorithms,
     bench_realtime_performance,
+    bench_record_handoff,
 );
 criterion_main!(benches);
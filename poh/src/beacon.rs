@@ -0,0 +1,88 @@
+use crate::types::{BeaconValue, LedgerEntry, LedgerReader, PoHRecord};
+
+use lib::DEFAULT_TICKS_PER_SLOT;
+use lib::utils::hash;
+
+use anyhow::{Result, anyhow, bail};
+use ring::signature::{ED25519, UnparsedPublicKey};
+
+// Prefix of every hashed beacon input, keeps beacon values apart from chain hashes.
+const BEACON_DOMAIN: &[u8] = b"poh-beacon-v1";
+
+/// Random value of `slot` in the ledger behind `reader`, which only exists once the slot's last hash has been computed.
+/// With a `public_key` the generator signature over the slot hash is checked and mixed in, so the value cannot be
+/// computed ahead by anyone without the generator key, even with faster hashing hardware.
+/// An honest generator's Ed25519 signatures are deterministic, a dishonest one could pick among valid signatures,
+/// so this is VRF-style rather than a true VRF.
+///
+/// Neither mode is unbiasable by the generator: it decides which events go into the slot and when, so it can grind
+/// through event sets in private and publish the slot whose value suits it. The value is only as fair as the generator
+/// is honest, use it where that is trusted or combine it with input the generator does not control.
+pub fn randomness_at(reader: &LedgerReader, slot: u64, public_key: Option<&[u8; 32]>) -> Result<BeaconValue> {
+    let mut last: Option<PoHRecord> = None;
+    for found in reader.slots(slot..slot.saturating_add(1))? {
+        let found: LedgerEntry = found?;
+        last = Some(found.record);
+    }
+    let record: PoHRecord = last.filter(PoHRecord::is_slot_end).ok_or_else(|| anyhow!("Slot {} is not closed yet.", slot))?;
+    return BeaconValue::from_slot_end(&record, public_key);
+}
+
+/// Check that `value` is derived from its slot hash, and that the generator with `public_key` signed that hash
+/// for values derived VRF-style.
+pub fn verify_randomness(value: &BeaconValue, public_key: Option<&[u8; 32]>) -> Result<()> {
+    if value.tick_index != value.slot_index.saturating_add(1).saturating_mul(DEFAULT_TICKS_PER_SLOT).saturating_sub(1) {
+        bail!("Tick {} does not close slot {}.", value.tick_index, value.slot_index);
+    }
+    match (&value.signature, public_key) {
+        (Some(signature), Some(public_key)) => {
+            if UnparsedPublicKey::new(&ED25519, public_key)
+                .verify(&PoHRecord::message_for_slot(value.slot_index, &value.slot_hash), signature)
+                .is_err()
+            {
+                bail!("Slot {} signature does not match the public key.", value.slot_index);
+            }
+        }
+        (Some(_), None) => bail!("Signed beacon value needs the generator public key to be checked."),
+        (None, Some(_)) => bail!("Beacon value of slot {} is not signed.", value.slot_index),
+        (None, None) => {}
+    }
+    if derive(value.slot_index, &value.slot_hash, value.signature.as_ref()) != value.randomness {
+        bail!("Randomness of slot {} is not derived from its slot hash.", value.slot_index);
+    }
+    return Ok(());
+}
+
+impl BeaconValue {
+    /// Value of the slot `record` closes, mixing in its signature when a `public_key` is given.
+    pub fn from_slot_end(record: &PoHRecord, public_key: Option<&[u8; 32]>) -> Result<Self> {
+        if !record.is_slot_end() {
+            bail!("Record at tick {} does not close its slot.", record.tick_index);
+        }
+        let signature: Option<[u8; 64]> = match public_key {
+            Some(_) => Some(record.signature.ok_or_else(|| anyhow!("Slot {} is not signed.", record.slot_index))?),
+            None => None,
+        };
+        let value: Self = Self {
+            slot_index: record.slot_index,
+            tick_index: record.tick_index,
+            slot_hash: record.hash,
+            signature,
+            randomness: derive(record.slot_index, &record.hash, signature.as_ref()),
+        };
+        verify_randomness(&value, public_key)?;
+        return Ok(value);
+    }
+}
+
+// Hash of the domain, slot index, slot hash and signature if any.
+fn derive(slot: u64, slot_hash: &[u8; 32], signature: Option<&[u8; 64]>) -> [u8; 32] {
+    let mut input: Vec<u8> = Vec::with_capacity(BEACON_DOMAIN.len().saturating_add(8 + 32 + 64));
+    input.extend_from_slice(BEACON_DOMAIN);
+    input.extend_from_slice(&slot.to_le_bytes());
+    input.extend_from_slice(slot_hash);
+    if let Some(signature) = signature {
+        input.extend_from_slice(signature);
+    }
+    return hash::hash(&input);
+}
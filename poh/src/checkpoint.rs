@@ -0,0 +1,100 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::types::{Checkpoint, Identity, PoHRecord};
+
+use lib::utils::hash;
+
+use anyhow::{Context, Result};
+use ring::signature::{ED25519, UnparsedPublicKey};
+
+// Checkpoint file kept next to the segments.
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+impl Checkpoint {
+    /// Checkpoint on `record`, the last record of the deleted `segment`, chained to the checkpoint it replaces.
+    /// Signed when an `identity` is given.
+    pub fn new(segment: u64, record: &PoHRecord, previous: Option<&Checkpoint>, identity: Option<&Identity>) -> Self {
+        let mut checkpoint: Self = Self {
+            segment,
+            tick_index: record.tick_index,
+            slot_index: record.slot_index,
+            epoch_index: record.epoch_index,
+            hash: record.hash,
+            timestamp_ms: record.timestamp_ms,
+            previous: previous.map_or([0u8; 32], Checkpoint::digest),
+            public_key: None,
+            signature: None,
+        };
+        if let Some(identity) = identity {
+            let mut signature: [u8; 64] = [0u8; 64];
+            signature.copy_from_slice(identity.keypair.sign(&checkpoint.digest()).as_ref());
+            checkpoint.public_key = Some(identity.public_key());
+            checkpoint.signature = Some(signature);
+        }
+        return checkpoint;
+    }
+
+    /// Hash over every field but the signature, this is what gets signed.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut message: Vec<u8> = Vec::with_capacity(8 * 5 + 32 * 2);
+        message.extend_from_slice(&self.segment.to_le_bytes());
+        message.extend_from_slice(&self.tick_index.to_le_bytes());
+        message.extend_from_slice(&self.slot_index.to_le_bytes());
+        message.extend_from_slice(&self.epoch_index.to_le_bytes());
+        message.extend_from_slice(&self.hash);
+        message.extend_from_slice(&self.timestamp_ms.to_le_bytes());
+        message.extend_from_slice(&self.previous);
+        return hash::hash(&message);
+    }
+
+    /// Check that the checkpoint is signed by `public_key`.
+    pub fn verify(&self, public_key: &[u8; 32]) -> bool {
+        let Some(signature) = &self.signature else {
+            return false;
+        };
+        return self.public_key.as_ref() == Some(public_key) && UnparsedPublicKey::new(&ED25519, public_key).verify(&self.digest(), signature).is_ok();
+    }
+
+    /// Stand-in for the deleted record, to verify the first kept record against.
+    pub fn anchor(&self) -> PoHRecord {
+        return PoHRecord {
+            tick_index: self.tick_index,
+            slot_index: self.slot_index,
+            epoch_index: self.epoch_index,
+            hash: self.hash,
+            timestamp_ms: self.timestamp_ms,
+            num_hashes: PoHRecord::default_num_hashes(),
+            commitment: None,
+            event: None,
+            signature: None,
+            witnesses: Vec::new(),
+        };
+    }
+
+    /// Checkpoint of the ledger in `dir`, `None` if it was never pruned.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path: PathBuf = dir.join(CHECKPOINT_FILE);
+        let bytes: Vec<u8> = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        return Ok(Some(
+            serde_json::from_slice(&bytes).with_context(|| format!("Corrupted checkpoint {}", path.display()))?,
+        ));
+    }
+
+    /// Durably replace the checkpoint of the ledger in `dir`, a crash leaves either the old or the new one.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path: PathBuf = dir.join(CHECKPOINT_FILE);
+        let temporary: PathBuf = path.with_extension("tmp");
+        let mut file: File = File::create(&temporary)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &path)?;
+        File::open(dir)?.sync_all()?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,540 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::types::{
+    Event, InclusionProof, LedgerHeader, PoHConfig, PoHRecord, ProofStep, Receipt, ReceiptSegment, RecordDecoder, RecordEncoder, RecordView, WitnessSignature,
+};
+
+use lib::utils::hash;
+use lib::{DEFAULT_HASHES_PER_TICK, DEFAULT_SLOTS_PER_EPOCH, DEFAULT_TICKS_PER_SLOT};
+
+use anyhow::{Result, anyhow, bail};
+
+// Every binary ledger starts with these bytes.
+const MAGIC: [u8; 4] = *b"POHL";
+// Bumped on any change to the layout below.
+const VERSION: u16 = 2;
+// Every binary receipt starts with these bytes.
+const RECEIPT_MAGIC: [u8; 4] = *b"POHR";
+// Bumped on any change to the receipt layout, see [`Receipt`].
+const RECEIPT_VERSION: u16 = 2;
+// Tick, slot and epoch index, hash, timestamp, hash count and flags.
+const FIXED_SIZE: usize = 8 + 8 + 8 + 32 + 8 + 8 + 1;
+// Public key and signature of one witness.
+const WITNESS_SIZE: usize = 32 + 64;
+
+// Optional parts following the fixed part, in this order.
+const FLAG_COMMITMENT: u8 = 1 << 0;
+const FLAG_SIGNATURE: u8 = 1 << 1;
+const FLAG_WITNESSES: u8 = 1 << 2;
+const FLAG_EVENT: u8 = 1 << 3;
+
+// Chain flags in the ledger header.
+const HEADER_SPARSE: u8 = 1 << 0;
+const HEADER_COMMIT_EVENTS: u8 = 1 << 1;
+
+impl LedgerHeader {
+    /// Header for a ledger generated by this build with `config`.
+    pub fn new(config: &PoHConfig) -> Self {
+        return Self {
+            version: VERSION,
+            algorithm: hash::get_current_algorithm(),
+            hashes_per_tick: DEFAULT_HASHES_PER_TICK,
+            ticks_per_slot: DEFAULT_TICKS_PER_SLOT,
+            slots_per_epoch: DEFAULT_SLOTS_PER_EPOCH,
+            sparse: config.sparse,
+            commit_events: config.commit_events,
+        };
+    }
+
+    /// Check that this build can verify the ledger, i.e. it uses the same algorithm and chain parameters.
+    pub fn validate(&self) -> Result<()> {
+        let current: LedgerHeader = LedgerHeader::new(&PoHConfig::default());
+        if self.algorithm != current.algorithm {
+            bail!("Ledger uses hash algorithm {}, this build uses {}.", self.algorithm, current.algorithm);
+        }
+        if (self.hashes_per_tick, self.ticks_per_slot, self.slots_per_epoch) != (current.hashes_per_tick, current.ticks_per_slot, current.slots_per_epoch) {
+            bail!(
+                "Ledger uses {} hashes per tick, {} ticks per slot and {} slots per epoch, which this build does not.",
+                self.hashes_per_tick,
+                self.ticks_per_slot,
+                self.slots_per_epoch
+            );
+        }
+        return Ok(());
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        let mut buffer: Vec<u8> = Vec::with_capacity(32);
+        let mut flags: u8 = 0;
+        flags |= if self.sparse { HEADER_SPARSE } else { 0 };
+        flags |= if self.commit_events { HEADER_COMMIT_EVENTS } else { 0 };
+
+        buffer.extend_from_slice(&MAGIC);
+        buffer.extend_from_slice(&self.version.to_le_bytes());
+        buffer.push(self.algorithm);
+        buffer.extend_from_slice(&self.hashes_per_tick.to_le_bytes());
+        buffer.extend_from_slice(&self.ticks_per_slot.to_le_bytes());
+        buffer.extend_from_slice(&self.slots_per_epoch.to_le_bytes());
+        buffer.push(flags);
+        writer.write_all(&buffer)?;
+        return Ok(());
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<Self> {
+        let mut magic: [u8; 4] = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            bail!("Not a binary PoH ledger.");
+        }
+        let version: u16 = u16::from_le_bytes(read_array(reader)?);
+        if version != VERSION {
+            bail!("Unsupported ledger version {}, expected {}.", version, VERSION);
+        }
+
+        let [algorithm] = read_array::<1>(reader)?;
+        let hashes_per_tick: u64 = u64::from_le_bytes(read_array(reader)?);
+        let ticks_per_slot: u64 = u64::from_le_bytes(read_array(reader)?);
+        let slots_per_epoch: u64 = u64::from_le_bytes(read_array(reader)?);
+        let [flags] = read_array::<1>(reader)?;
+
+        return Ok(Self {
+            version,
+            algorithm,
+            hashes_per_tick,
+            ticks_per_slot,
+            slots_per_epoch,
+            sparse: flags & HEADER_SPARSE != 0,
+            commit_events: flags & HEADER_COMMIT_EVENTS != 0,
+        });
+    }
+}
+
+impl PoHRecord {
+    /// Append the binary encoding of this record to `buffer`.
+    pub fn encode_into(&self, buffer: &mut Vec<u8>) -> Result<()> {
+        let mut flags: u8 = 0;
+        flags |= if self.commitment.is_some() { FLAG_COMMITMENT } else { 0 };
+        flags |= if self.signature.is_some() { FLAG_SIGNATURE } else { 0 };
+        flags |= if self.witnesses.is_empty() { 0 } else { FLAG_WITNESSES };
+        flags |= if self.event.is_some() { FLAG_EVENT } else { 0 };
+
+        buffer.extend_from_slice(&self.tick_index.to_le_bytes());
+        buffer.extend_from_slice(&self.slot_index.to_le_bytes());
+        buffer.extend_from_slice(&self.epoch_index.to_le_bytes());
+        buffer.extend_from_slice(&self.hash);
+        buffer.extend_from_slice(&self.timestamp_ms.to_le_bytes());
+        buffer.extend_from_slice(&self.num_hashes.to_le_bytes());
+        buffer.push(flags);
+
+        if let Some(commitment) = &self.commitment {
+            buffer.extend_from_slice(commitment);
+        }
+        if let Some(signature) = &self.signature {
+            buffer.extend_from_slice(signature);
+        }
+        if !self.witnesses.is_empty() {
+            let Ok(count) = u16::try_from(self.witnesses.len()) else {
+                bail!("Record at tick {} has too many witness signatures.", self.tick_index);
+            };
+            buffer.extend_from_slice(&count.to_le_bytes());
+            for witness in &self.witnesses {
+                buffer.extend_from_slice(&witness.public_key);
+                buffer.extend_from_slice(&witness.signature);
+            }
+        }
+        if let Some(event) = &self.event {
+            put_bytes(buffer, &event.encode())?;
+        }
+        return Ok(());
+    }
+
+    /// Read the next record.
+    ///
+    /// # Returns
+    /// `None` at a clean end of stream, an error if the stream ends inside a record.
+    pub fn read_from(reader: &mut impl Read) -> Result<Option<Self>> {
+        let mut fixed: [u8; FIXED_SIZE] = [0u8; FIXED_SIZE];
+        match read_full(reader, &mut fixed)? {
+            0 => return Ok(None),
+            FIXED_SIZE => {}
+            _ => return Err(std::io::Error::new(ErrorKind::UnexpectedEof, "Truncated record.").into()),
+        }
+
+        let mut rest: &[u8] = &fixed;
+        let tick_index: u64 = take_u64(&mut rest);
+        let slot_index: u64 = take_u64(&mut rest);
+        let epoch_index: u64 = take_u64(&mut rest);
+        let hash: [u8; 32] = take_array(&mut rest);
+        let timestamp_ms: u64 = take_u64(&mut rest);
+        let num_hashes: u64 = take_u64(&mut rest);
+        let [flags] = take_array::<1>(&mut rest);
+        if flags & !(FLAG_COMMITMENT | FLAG_SIGNATURE | FLAG_WITNESSES | FLAG_EVENT) != 0 {
+            bail!("Unknown record flags {:#04x} at tick {}.", flags, tick_index);
+        }
+
+        let commitment: Option<[u8; 32]> = if flags & FLAG_COMMITMENT != 0 { Some(read_array(reader)?) } else { None };
+        let signature: Option<[u8; 64]> = if flags & FLAG_SIGNATURE != 0 { Some(read_array(reader)?) } else { None };
+        let mut witnesses: Vec<WitnessSignature> = Vec::new();
+        if flags & FLAG_WITNESSES != 0 {
+            let count: u16 = u16::from_le_bytes(read_array(reader)?);
+            for _ in 0..count {
+                witnesses.push(WitnessSignature {
+                    public_key: read_array(reader)?,
+                    signature: read_array(reader)?,
+                });
+            }
+        }
+        let event: Option<Event> = if flags & FLAG_EVENT != 0 {
+            Some(Event::decode(&read_bytes(reader)?)?)
+        } else {
+            None
+        };
+
+        return Ok(Some(Self {
+            tick_index,
+            slot_index,
+            epoch_index,
+            hash,
+            timestamp_ms,
+            num_hashes,
+            commitment,
+            event,
+            signature,
+            witnesses,
+        }));
+    }
+}
+
+impl<'a> RecordView<'a> {
+    /// Parse the record at the start of `bytes` in place and advance past it.
+    ///
+    /// # Returns
+    /// `None` once `bytes` is empty, an error if it ends inside a record.
+    pub fn parse(bytes: &mut &'a [u8]) -> Result<Option<Self>> {
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        if bytes.len() < FIXED_SIZE {
+            bail!("Truncated record.");
+        }
+
+        let tick_index: u64 = u64::from_le_bytes(*take_ref(bytes)?);
+        let slot_index: u64 = u64::from_le_bytes(*take_ref(bytes)?);
+        let epoch_index: u64 = u64::from_le_bytes(*take_ref(bytes)?);
+        let hash: &[u8; 32] = take_ref(bytes)?;
+        let timestamp_ms: u64 = u64::from_le_bytes(*take_ref(bytes)?);
+        let num_hashes: u64 = u64::from_le_bytes(*take_ref(bytes)?);
+        let [flags] = *take_ref::<1>(bytes)?;
+        if flags & !(FLAG_COMMITMENT | FLAG_SIGNATURE | FLAG_WITNESSES | FLAG_EVENT) != 0 {
+            bail!("Unknown record flags {:#04x} at tick {}.", flags, tick_index);
+        }
+
+        let commitment: Option<&[u8; 32]> = if flags & FLAG_COMMITMENT != 0 { Some(take_ref(bytes)?) } else { None };
+        let signature: Option<&[u8; 64]> = if flags & FLAG_SIGNATURE != 0 { Some(take_ref(bytes)?) } else { None };
+        let witnesses: &[u8] = if flags & FLAG_WITNESSES != 0 {
+            let count: u16 = u16::from_le_bytes(*take_ref(bytes)?);
+            take_slice(bytes, (count as usize).saturating_mul(WITNESS_SIZE))?
+        } else {
+            &[]
+        };
+        let event: Option<&[u8]> = if flags & FLAG_EVENT != 0 {
+            let length: u32 = u32::from_le_bytes(*take_ref(bytes)?);
+            Some(take_slice(bytes, length as usize)?)
+        } else {
+            None
+        };
+
+        return Ok(Some(Self {
+            tick_index,
+            slot_index,
+            epoch_index,
+            hash,
+            timestamp_ms,
+            num_hashes,
+            commitment,
+            signature,
+            witnesses,
+            event,
+        }));
+    }
+
+    /// Copy the view into an owned record, decoding the event.
+    pub fn to_record(&self) -> Result<PoHRecord> {
+        let mut witnesses: Vec<WitnessSignature> = Vec::with_capacity(self.witnesses.len() / WITNESS_SIZE);
+        for mut witness in self.witnesses.chunks_exact(WITNESS_SIZE) {
+            witnesses.push(WitnessSignature {
+                public_key: *take_ref(&mut witness)?,
+                signature: *take_ref(&mut witness)?,
+            });
+        }
+        return Ok(PoHRecord {
+            tick_index: self.tick_index,
+            slot_index: self.slot_index,
+            epoch_index: self.epoch_index,
+            hash: *self.hash,
+            timestamp_ms: self.timestamp_ms,
+            num_hashes: self.num_hashes,
+            commitment: self.commitment.copied(),
+            event: self.event.map(Event::decode).transpose()?,
+            signature: self.signature.copied(),
+            witnesses,
+        });
+    }
+}
+
+impl Receipt {
+    /// Receipt in the current format for `digest`, included by `proof` in a chain signed by `public_key`.
+    pub fn new(digest: [u8; 32], header: LedgerHeader, public_key: [u8; 32], proof: InclusionProof) -> Self {
+        return Self {
+            version: RECEIPT_VERSION,
+            digest,
+            header,
+            public_key,
+            proof,
+            upgrades: Vec::new(),
+        };
+    }
+
+    /// Check that this build reads the receipt format and can verify its chain.
+    pub fn validate(&self) -> Result<()> {
+        if self.version != RECEIPT_VERSION {
+            bail!("Unsupported receipt version {}, expected {}.", self.version, RECEIPT_VERSION);
+        }
+        if self.proof.algorithm != self.header.algorithm {
+            bail!("Receipt proof uses hash algorithm {}, its header {}.", self.proof.algorithm, self.header.algorithm);
+        }
+        return self.header.validate();
+    }
+
+    /// Binary encoding of the receipt, see [`Receipt`] for the layout.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut buffer: Vec<u8> = Vec::new();
+        buffer.extend_from_slice(&RECEIPT_MAGIC);
+        buffer.extend_from_slice(&self.version.to_le_bytes());
+        self.header.write_to(&mut buffer)?;
+        buffer.extend_from_slice(&self.digest);
+        buffer.extend_from_slice(&self.public_key);
+
+        buffer.extend_from_slice(&self.proof.prev_tick.to_le_bytes());
+        buffer.extend_from_slice(&self.proof.prev_hash);
+        self.proof.record.encode_into(&mut buffer)?;
+        put_steps(&mut buffer, &self.proof.steps)?;
+        match &self.proof.slot_end {
+            Some(slot_end) => {
+                buffer.push(1);
+                slot_end.encode_into(&mut buffer)?;
+            }
+            None => buffer.push(0),
+        }
+
+        buffer.extend_from_slice(&u32::try_from(self.upgrades.len())?.to_le_bytes());
+        for upgrade in &self.upgrades {
+            put_steps(&mut buffer, &upgrade.steps)?;
+            upgrade.anchor.encode_into(&mut buffer)?;
+        }
+        return Ok(buffer);
+    }
+
+    /// Decode the binary layout, `reader` must hold nothing after the receipt.
+    pub fn decode(reader: &mut impl Read) -> Result<Self> {
+        if read_array(reader)? != RECEIPT_MAGIC {
+            bail!("Not a binary PoH receipt.");
+        }
+        let version: u16 = u16::from_le_bytes(read_array(reader)?);
+        if version != RECEIPT_VERSION {
+            bail!("Unsupported receipt version {}, expected {}.", version, RECEIPT_VERSION);
+        }
+        let header: LedgerHeader = LedgerHeader::read_from(reader)?;
+        let digest: [u8; 32] = read_array(reader)?;
+        let public_key: [u8; 32] = read_array(reader)?;
+
+        let prev_tick: u64 = u64::from_le_bytes(read_array(reader)?);
+        let prev_hash: [u8; 32] = read_array(reader)?;
+        let record: PoHRecord = read_record(reader)?;
+        let steps: Vec<ProofStep> = read_steps(reader)?;
+        let slot_end: Option<PoHRecord> = match read_array::<1>(reader)? {
+            [0] => None,
+            [1] => Some(read_record(reader)?),
+            [flag] => bail!("Invalid slot end flag {} in receipt.", flag),
+        };
+
+        let count: u32 = u32::from_le_bytes(read_array(reader)?);
+        let mut upgrades: Vec<ReceiptSegment> = Vec::new();
+        for _ in 0..count {
+            upgrades.push(ReceiptSegment {
+                steps: read_steps(reader)?,
+                anchor: read_record(reader)?,
+            });
+        }
+        // A receipt is the whole input, anything after the last upgrade means it was cut or concatenated.
+        let mut trailing: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut trailing)?;
+        if !trailing.is_empty() {
+            bail!("Receipt has {} trailing bytes.", trailing.len());
+        }
+
+        return Ok(Self {
+            version,
+            digest,
+            proof: InclusionProof {
+                algorithm: header.algorithm,
+                prev_tick,
+                prev_hash,
+                record,
+                steps,
+                slot_end,
+            },
+            header,
+            public_key,
+            upgrades,
+        });
+    }
+}
+
+impl<W: Write> RecordEncoder<W> {
+    /// Start a ledger stream by writing its header.
+    pub fn new(mut writer: W, config: &PoHConfig) -> Result<Self> {
+        LedgerHeader::new(config).write_to(&mut writer)?;
+        return Ok(Self { writer, buffer: Vec::new() });
+    }
+
+    pub fn write(&mut self, record: &PoHRecord) -> Result<()> {
+        self.buffer.clear();
+        record.encode_into(&mut self.buffer)?;
+        self.writer.write_all(&self.buffer)?;
+        return Ok(());
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        return Ok(());
+    }
+
+    pub fn into_inner(self) -> W {
+        return self.writer;
+    }
+}
+
+impl<R: Read> RecordDecoder<R> {
+    /// Open a ledger stream by reading its header.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let header: LedgerHeader = LedgerHeader::read_from(&mut reader)?;
+        return Ok(Self { reader, header });
+    }
+
+    pub fn read(&mut self) -> Result<Option<PoHRecord>> {
+        return PoHRecord::read_from(&mut self.reader);
+    }
+}
+
+impl<R: Read> Iterator for RecordDecoder<R> {
+    type Item = Result<PoHRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return self.read().transpose();
+    }
+}
+
+fn put_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) -> Result<()> {
+    let Ok(length) = u32::try_from(bytes.len()) else {
+        bail!("Field of {} bytes is too long to encode.", bytes.len());
+    };
+    buffer.extend_from_slice(&length.to_le_bytes());
+    buffer.extend_from_slice(bytes);
+    return Ok(());
+}
+
+fn put_steps(buffer: &mut Vec<u8>, steps: &[ProofStep]) -> Result<()> {
+    buffer.extend_from_slice(&u32::try_from(steps.len())?.to_le_bytes());
+    for step in steps {
+        buffer.extend_from_slice(&step.num_hashes.to_le_bytes());
+        match &step.data {
+            Some(data) => {
+                buffer.push(1);
+                put_bytes(buffer, data)?;
+            }
+            None => buffer.push(0),
+        }
+    }
+    return Ok(());
+}
+
+fn read_steps(reader: &mut impl Read) -> Result<Vec<ProofStep>> {
+    let count: u32 = u32::from_le_bytes(read_array(reader)?);
+    let mut steps: Vec<ProofStep> = Vec::new();
+    for _ in 0..count {
+        let num_hashes: u64 = u64::from_le_bytes(read_array(reader)?);
+        let data: Option<Vec<u8>> = match read_array::<1>(reader)? {
+            [0] => None,
+            [1] => Some(read_bytes(reader)?),
+            [flag] => bail!("Invalid step data flag {} in receipt.", flag),
+        };
+        steps.push(ProofStep { data, num_hashes });
+    }
+    return Ok(steps);
+}
+
+// A record that must be there, unlike at the end of a ledger stream.
+fn read_record(reader: &mut impl Read) -> Result<PoHRecord> {
+    return PoHRecord::read_from(reader)?.ok_or_else(|| anyhow!("Truncated receipt."));
+}
+
+// Fill `buffer` unless the stream ends first, returning how much was read.
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled: usize = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled = filled.saturating_add(n),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    return Ok(filled);
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes: [u8; N] = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    return Ok(bytes);
+}
+
+fn read_bytes(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let length: u32 = u32::from_le_bytes(read_array(reader)?);
+    // Grows with the bytes actually read, a corrupted length can't allocate gigabytes up front.
+    let mut bytes: Vec<u8> = Vec::new();
+    reader.by_ref().take(u64::from(length)).read_to_end(&mut bytes)?;
+    if bytes.len() != length as usize {
+        return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+    }
+    return Ok(bytes);
+}
+
+// The fixed part has already been read in full, so these cannot run short.
+fn take_array<const N: usize>(rest: &mut &[u8]) -> [u8; N] {
+    let (head, tail) = rest.split_at(N);
+    *rest = tail;
+    let mut bytes: [u8; N] = [0u8; N];
+    bytes.copy_from_slice(head);
+    return bytes;
+}
+
+fn take_u64(rest: &mut &[u8]) -> u64 {
+    return u64::from_le_bytes(take_array(rest));
+}
+
+fn take_ref<'a, const N: usize>(bytes: &mut &'a [u8]) -> Result<&'a [u8; N]> {
+    let Some((head, tail)) = bytes.split_first_chunk::<N>() else {
+        bail!("Truncated record.");
+    };
+    *bytes = tail;
+    return Ok(head);
+}
+
+fn take_slice<'a>(bytes: &mut &'a [u8], length: usize) -> Result<&'a [u8]> {
+    let Some((head, tail)) = bytes.split_at_checked(length) else {
+        bail!("Truncated record.");
+    };
+    *bytes = tail;
+    return Ok(head);
+}
@@ -0,0 +1,177 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::types::{CompressionStats, Ledger, LedgerHeader, PoHRecord, SeekEntry};
+
+use anyhow::{Result, anyhow, bail};
+
+// Magic of a zstd skippable frame, decoders pass over its content.
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+// Closes the seek table, after its entry count.
+const SEEK_MAGIC: [u8; 4] = *b"POHS";
+// Slot, offset and size of one seek table entry.
+const SEEK_ENTRY_SIZE: usize = 8 + 8 + 4;
+// Entry count and magic at the very end of the file.
+const SEEK_FOOTER_SIZE: usize = 4 + 4;
+// Magic and content size opening the skippable frame.
+const SKIPPABLE_HEADER_SIZE: usize = 4 + 4;
+
+impl Ledger {
+    /// Compress the raw segment at `raw` into `compressed`: the header, then one zstd frame per slot and a seek table in a skippable frame.
+    /// Decompressing the whole file with any zstd tool gives back the raw segment.
+    pub fn compress_segment(raw: &Path, compressed: &Path, level: i32) -> Result<CompressionStats> {
+        let start: Instant = Instant::now();
+        let bytes: Vec<u8> = fs::read(raw)?;
+        let mut rest: &[u8] = &bytes;
+        LedgerHeader::read_from(&mut rest)?;
+
+        // Where each slot starts, segments hold whole slots apart from a resumed first one.
+        let mut slots: Vec<(u64, usize)> = Vec::new();
+        let mut offset: usize = bytes.len().saturating_sub(rest.len());
+        let header_size: usize = offset;
+        while let Some(record) = PoHRecord::read_from(&mut rest)? {
+            if slots.last().is_none_or(|(slot, _)| *slot != record.slot_index) {
+                slots.push((record.slot_index, offset));
+            }
+            offset = bytes.len().saturating_sub(rest.len());
+        }
+
+        let mut output: Vec<u8> = zstd::bulk::compress(&bytes[..header_size], level)?;
+        let mut table: Vec<u8> = Vec::with_capacity(slots.len().saturating_mul(SEEK_ENTRY_SIZE).saturating_add(SEEK_FOOTER_SIZE));
+        for (i, (slot, start)) in slots.iter().enumerate() {
+            let end: usize = slots.get(i.saturating_add(1)).map_or(bytes.len(), |(_, next)| *next);
+            let frame: Vec<u8> = zstd::bulk::compress(&bytes[*start..end], level)?;
+            table.extend_from_slice(&slot.to_le_bytes());
+            table.extend_from_slice(&(output.len() as u64).to_le_bytes());
+            table.extend_from_slice(&u32::try_from(frame.len())?.to_le_bytes());
+            output.extend_from_slice(&frame);
+        }
+        table.extend_from_slice(&u32::try_from(slots.len())?.to_le_bytes());
+        table.extend_from_slice(&SEEK_MAGIC);
+        output.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        output.extend_from_slice(&u32::try_from(table.len())?.to_le_bytes());
+        output.extend_from_slice(&table);
+
+        // Written aside first so a crash never leaves a partial file under the final name.
+        let temporary: PathBuf = compressed.with_extension("tmp");
+        let mut file: File = File::create(&temporary)?;
+        file.write_all(&output)?;
+        file.sync_all()?;
+        fs::rename(&temporary, compressed)?;
+
+        return Ok(CompressionStats {
+            segments: 1,
+            raw_bytes: bytes.len() as u64,
+            compressed_bytes: output.len() as u64,
+            elapsed: start.elapsed(),
+        });
+    }
+
+    /// Records of `slot` in a compressed segment, only the frame holding them is read.
+    pub fn read_compressed_slot(path: &Path, slot: u64) -> Result<Vec<PoHRecord>> {
+        let mut file: File = File::open(path)?;
+        let table: Vec<SeekEntry> = SeekEntry::read_table(&mut file)?;
+        return match table.binary_search_by_key(&slot, |entry| entry.slot) {
+            Ok(position) => table[position].read(&mut file),
+            Err(_) => Ok(Vec::new()),
+        };
+    }
+
+    /// Last record of a compressed segment.
+    pub fn last_compressed_record(path: &Path) -> Result<Option<PoHRecord>> {
+        let mut file: File = File::open(path)?;
+        let table: Vec<SeekEntry> = SeekEntry::read_table(&mut file)?;
+        return match table.last() {
+            Some(entry) => Ok(entry.read(&mut file)?.pop()),
+            None => Ok(None),
+        };
+    }
+}
+
+impl SeekEntry {
+    /// Read the seek table at the end of a compressed segment.
+    pub fn read_table(file: &mut File) -> Result<Vec<Self>> {
+        let length: u64 = file.metadata()?.len();
+        let mut footer: [u8; SEEK_FOOTER_SIZE] = [0u8; SEEK_FOOTER_SIZE];
+        file.seek(SeekFrom::Start(length.saturating_sub(SEEK_FOOTER_SIZE as u64)))?;
+        file.read_exact(&mut footer)?;
+        if footer[4..] != SEEK_MAGIC {
+            bail!("Compressed segment has no seek table.");
+        }
+
+        let count: usize = u32::from_le_bytes(footer[..4].try_into()?) as usize;
+        let table_size: usize = count
+            .checked_mul(SEEK_ENTRY_SIZE)
+            .and_then(|size| size.checked_add(SEEK_FOOTER_SIZE))
+            .ok_or_else(|| anyhow!("Seek table of {} entries is too large.", count))?;
+        let frame_size: u64 = table_size.saturating_add(SKIPPABLE_HEADER_SIZE) as u64;
+        let frame_start: u64 = length
+            .checked_sub(frame_size)
+            .ok_or_else(|| anyhow!("Seek table runs past the start of the segment."))?;
+        let mut frame: Vec<u8> = vec![0u8; frame_size as usize];
+        file.seek(SeekFrom::Start(frame_start))?;
+        file.read_exact(&mut frame)?;
+        if frame[..4] != SKIPPABLE_MAGIC.to_le_bytes() || frame[4..8] != u32::try_from(table_size)?.to_le_bytes() {
+            bail!("Corrupted seek table.");
+        }
+
+        return Ok(frame[SKIPPABLE_HEADER_SIZE..]
+            .chunks_exact(SEEK_ENTRY_SIZE)
+            .map(|entry| Self {
+                slot: u64::from_le_bytes(entry[..8].try_into().unwrap_or_default()),
+                offset: u64::from_le_bytes(entry[8..16].try_into().unwrap_or_default()),
+                size: u32::from_le_bytes(entry[16..].try_into().unwrap_or_default()),
+            })
+            .collect());
+    }
+
+    /// Decompress the frame and decode its records.
+    pub fn read(&self, file: &mut File) -> Result<Vec<PoHRecord>> {
+        let mut frame: Vec<u8> = vec![0u8; self.size as usize];
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.read_exact(&mut frame)?;
+
+        let bytes: Vec<u8> = zstd::decode_all(&frame[..])?;
+        let mut rest: &[u8] = &bytes;
+        let mut records: Vec<PoHRecord> = Vec::new();
+        while let Some(record) = PoHRecord::read_from(&mut rest)? {
+            records.push(record);
+        }
+        return Ok(records);
+    }
+}
+
+impl CompressionStats {
+    pub fn add(&mut self, other: &CompressionStats) {
+        self.segments = self.segments.saturating_add(other.segments);
+        self.raw_bytes = self.raw_bytes.saturating_add(other.raw_bytes);
+        self.compressed_bytes = self.compressed_bytes.saturating_add(other.compressed_bytes);
+        self.elapsed = self.elapsed.saturating_add(other.elapsed);
+    }
+
+    pub fn ratio(&self) -> f64 {
+        return self.raw_bytes as f64 / self.compressed_bytes.max(1) as f64;
+    }
+
+    /// Raw bytes compressed per second.
+    pub fn throughput_mb_s(&self) -> f64 {
+        return self.raw_bytes as f64 / 1_000_000.0 / self.elapsed.as_secs_f64().max(f64::EPSILON);
+    }
+}
+
+impl Display for CompressionStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        return write!(
+            f,
+            "{} segments compressed from {:.2} MB to {:.2} MB ({:.2}x) at {:.1} MB/s",
+            self.segments,
+            self.raw_bytes as f64 / 1_000_000.0,
+            self.compressed_bytes as f64 / 1_000_000.0,
+            self.ratio(),
+            self.throughput_mb_s()
+        );
+    }
+}
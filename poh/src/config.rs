@@ -0,0 +1,101 @@
+use crate::types::{FsyncPolicy, Identity, LedgerConfig, PoHConfig, RetentionPolicy, SlipPolicy};
+
+use lib::{
+    DEFAULT_COMPRESSION_LEVEL, DEFAULT_MIN_HASH_HEADROOM, DEFAULT_RECENT_SLOT_HASHES, DEFAULT_SEGMENT_BYTES, DEFAULT_SELF_TEST_MS, DEFAULT_SLIP_THRESHOLD_US,
+    DEFAULT_SLIP_TOLERANCE_TICKS, DEFAULT_WITNESS_TIMEOUT_MS,
+};
+use thread::native::types::Config;
+
+use anyhow::{Result, bail};
+
+impl Default for SlipPolicy {
+    fn default() -> Self {
+        return Self::Warn;
+    }
+}
+
+impl Default for FsyncPolicy {
+    fn default() -> Self {
+        return Self::Slot;
+    }
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        return Self::KeepAll;
+    }
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        return Self {
+            segment_bytes: DEFAULT_SEGMENT_BYTES,
+            fsync: FsyncPolicy::default(),
+            compress: false,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            retention: RetentionPolicy::default(),
+        };
+    }
+}
+
+impl Default for PoHConfig {
+    fn default() -> Self {
+        return Self {
+            slip_policy: SlipPolicy::default(),
+            slip_threshold_us: DEFAULT_SLIP_THRESHOLD_US,
+            slip_tolerance_ticks: DEFAULT_SLIP_TOLERANCE_TICKS,
+            self_test_ms: DEFAULT_SELF_TEST_MS,
+            min_headroom: DEFAULT_MIN_HASH_HEADROOM,
+            enforce_self_test: false,
+            commit_events: false,
+            event_store: None,
+            sparse: false,
+            identity: None,
+            identity_path: None,
+            witnesses: Vec::new(),
+            witness_threshold: 0,
+            witness_timeout_ms: DEFAULT_WITNESS_TIMEOUT_MS,
+            recent_slot_hashes: DEFAULT_RECENT_SLOT_HASHES,
+            poh_thread: Config::default(),
+            delivery_thread: Config::default(),
+            ingest_thread: Config::default(),
+        };
+    }
+}
+
+impl PoHConfig {
+    /// Validates the PoH service configuration.
+    pub fn validate(&self) -> Result<()> {
+        if self.slip_tolerance_ticks == 0 {
+            bail!("slip_tolerance_ticks must be greater than 0.");
+        }
+        if !self.min_headroom.is_finite() || self.min_headroom <= -1.0 {
+            bail!("min_headroom must be a finite fraction greater than -1.");
+        }
+        if self.commit_events && self.event_store.is_none() {
+            bail!("commit_events needs an event_store to keep the payloads in.");
+        }
+        if !self.witnesses.is_empty() && self.identity.is_none() && self.identity_path.is_none() {
+            bail!("witnesses need an identity to check the generator signature of every slot.");
+        }
+        // Zero would count every slot as finalised without a single co-signature.
+        if !self.witnesses.is_empty() && self.witness_threshold == 0 {
+            bail!("witness_threshold must be at least 1 when witnesses are configured.");
+        }
+        if self.witness_threshold > self.witnesses.len() {
+            bail!("witness_threshold cannot exceed the {} configured witnesses.", self.witnesses.len());
+        }
+        self.poh_thread.validate()?;
+        self.delivery_thread.validate()?;
+        return self.ingest_thread.validate();
+    }
+
+    /// Get the configured identity, loading it from `identity_path` if it isn't set directly.
+    pub fn resolve_identity(&self) -> Result<Option<Identity>> {
+        return match (&self.identity, &self.identity_path) {
+            (Some(identity), _) => Ok(Some(identity.clone())),
+            (None, Some(path)) => Ok(Some(Identity::load(path)?)),
+            (None, None) => Ok(None),
+        };
+    }
+}
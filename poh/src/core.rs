@@ -1,7 +1,8 @@
 use std::fmt::{Display, Formatter, Result};
-use std::time::Instant;
+use std::thread::{self, ScopedJoinHandle};
+use std::time::{Duration, Instant};
 
-use crate::types::{PoH, PoHRecord};
+use crate::types::{Event, PoH, PoHEntry, PoHRecord, RecordView};
 
 use lib::utils::hash;
 use lib::{DEFAULT_HASHES_PER_TICK, DEFAULT_SLOTS_PER_EPOCH, DEFAULT_TICKS_PER_SLOT, DEFAULT_US_PER_TICK};
@@ -10,22 +11,76 @@ use hex::encode;
 
 impl Display for PoHRecord {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let _event_desc: String = match &self.event {
-            Some(data) => format!("Event: {} bytes", data.len()),
-            None => "No Event".to_string(),
+        let event_desc: String = match (&self.event, &self.commitment) {
+            (Some(event), _) => format!("Event: {}", event),
+            (None, Some(commitment)) => format!("Commitment: 0x{}...", &encode(commitment)[..17]),
+            (None, None) => "No Event".to_string(),
         };
         return write!(
             f,
-            "Epoch {}, Slot {}, Tick {}, Timestamp {}ms, Hash 0x{}...",
+            "Epoch {}, Slot {}, Tick {}, Timestamp {}ms, Hash 0x{}..., {}",
             self.epoch_index,
             self.slot_index,
             self.tick_index,
             self.timestamp_ms,
-            &encode(self.hash)[..17]
+            &encode(self.hash)[..17],
+            event_desc
         );
     }
 }
 
+impl PoHEntry {
+    /// Re-attach the out-of-band event payload, if any.
+    pub fn into_record(self, event: Option<Event>) -> PoHRecord {
+        return PoHRecord {
+            tick_index: self.tick_index,
+            slot_index: self.slot_index,
+            epoch_index: self.epoch_index,
+            hash: self.hash,
+            timestamp_ms: self.timestamp_ms,
+            num_hashes: DEFAULT_HASHES_PER_TICK,
+            commitment: self.commitment,
+            event,
+            signature: None,
+            witnesses: Vec::new(),
+        };
+    }
+}
+
+impl PoHRecord {
+    pub fn default_num_hashes() -> u64 {
+        return DEFAULT_HASHES_PER_TICK;
+    }
+
+    /// Check if this is the last tick of its slot, whose hash stands for the whole slot.
+    pub fn is_slot_end(&self) -> bool {
+        return self.tick_index.saturating_add(1) % DEFAULT_TICKS_PER_SLOT == 0;
+    }
+
+    /// Check if an event was mixed into this tick, whether or not its payload is attached.
+    pub fn has_event(&self) -> bool {
+        return self.event.is_some() || self.commitment.is_some();
+    }
+
+    /// Hash identifying the event of this tick, the commitment when only the hash was mixed in.
+    pub fn event_hash(&self) -> Option<[u8; 32]> {
+        return self.commitment.or_else(|| self.event.as_ref().map(Event::commitment));
+    }
+
+    /// Check that this record extends the record at `prev_tick` whose hash is `prev_hash`.
+    pub fn follows(&self, prev_hash: &[u8; 32], prev_tick: u64) -> bool {
+        // Only the canonical encoding is hashed, never the serde form.
+        // Committed ticks verify without their payload, an attached payload must match the commitment.
+        let event_data: Option<Vec<u8>> = match (&self.commitment, &self.event) {
+            (Some(commitment), Some(event)) if event.commitment() != *commitment => return false,
+            (Some(commitment), _) => Some(commitment.to_vec()),
+            (None, event) => event.as_ref().map(Event::encode),
+        };
+        let indices: [u64; 3] = [self.tick_index, self.slot_index, self.epoch_index];
+        return verify_link(prev_hash, prev_tick, &self.hash, indices, self.num_hashes, event_data.as_deref());
+    }
+}
+
 impl PoH {
     pub fn new(seed: &[u8]) -> Self {
         let current_hash: [u8; 32] = hash::hash(seed);
@@ -35,20 +90,66 @@ impl PoH {
             slot_count: 0,
             epoch_count: 0,
             start_time: Instant::now(),
+            event_buffer: Vec::new(),
+        };
+    }
+
+    /// Continue a chain right after a past record, e.g. to branch a fork from it.
+    pub fn from_record(record: &PoHRecord) -> Self {
+        let tick_count: u64 = record.tick_index.checked_add(1).expect("tick_count overflow");
+        // Back-date the start so timestamps keep increasing from the record.
+        let start_time: Instant = Instant::now().checked_sub(Duration::from_millis(record.timestamp_ms)).unwrap_or_else(Instant::now);
+        return Self {
+            current_hash: record.hash,
+            tick_count,
+            slot_count: (tick_count / DEFAULT_TICKS_PER_SLOT) % DEFAULT_SLOTS_PER_EPOCH,
+            epoch_count: record.epoch_index,
+            start_time,
+            event_buffer: Vec::new(),
         };
     }
 
     pub fn next_tick(&mut self) -> PoHRecord {
-        return self.core(None);
+        return self.core(None, false).into_record(None);
+    }
+
+    pub fn insert_event(&mut self, event: Event) -> PoHRecord {
+        return self.core(Some(&event), false).into_record(Some(event));
+    }
+
+    /// Mix only the hash of `event` into the chain.
+    /// The returned record carries the commitment but not the payload, keep it in an [`EventStore`](crate::types::EventStore).
+    pub fn commit_event(&mut self, event: &Event) -> PoHRecord {
+        return self.core(Some(event), true).into_record(None);
+    }
+
+    /// Allocation-free variant of [`PoH::next_tick`] for the hashing loop.
+    pub fn next_entry(&mut self) -> PoHEntry {
+        return self.core(None, false);
     }
 
-    pub fn insert_event(&mut self, event_data: &[u8]) -> PoHRecord {
-        return self.core(Some(event_data));
+    /// Allocation-free variant of [`PoH::insert_event`], the caller keeps ownership of the event.
+    pub fn insert_event_entry(&mut self, event: &Event) -> PoHEntry {
+        return self.core(Some(event), false);
     }
 
-    fn core(&mut self, event_data: Option<&[u8]>) -> PoHRecord {
-        if let Some(event) = event_data {
-            self.current_hash = hash::hash_with_data(&self.current_hash, event);
+    /// Allocation-free variant of [`PoH::commit_event`].
+    pub fn commit_event_entry(&mut self, event: &Event) -> PoHEntry {
+        return self.core(Some(event), true);
+    }
+
+    fn core(&mut self, event: Option<&Event>, commit: bool) -> PoHEntry {
+        let mut commitment: Option<[u8; 32]> = None;
+
+        if let Some(event) = event {
+            event.encode_into(&mut self.event_buffer);
+            if commit {
+                let event_hash: [u8; 32] = hash::hash(&self.event_buffer);
+                self.current_hash = hash::hash_with_data(&self.current_hash, &event_hash);
+                commitment = Some(event_hash);
+            } else {
+                self.current_hash = hash::hash_with_data(&self.current_hash, &self.event_buffer);
+            }
         }
 
         self.current_hash = hash::extend_hash_chain(&self.current_hash, DEFAULT_HASHES_PER_TICK);
@@ -56,13 +157,14 @@ impl PoH {
         let tick_index: u64 = self.tick_count;
         let slot_index: u64 = tick_index / DEFAULT_TICKS_PER_SLOT;
         let epoch_index: u64 = slot_index / DEFAULT_SLOTS_PER_EPOCH;
-        let record: PoHRecord = PoHRecord {
+        let entry: PoHEntry = PoHEntry {
             tick_index,
             slot_index,
             epoch_index,
             hash: self.current_hash,
             timestamp_ms: self.start_time.elapsed().as_millis() as u64,
-            event: event_data.map(|d| d.to_vec()),
+            has_event: event.is_some(),
+            commitment,
         };
 
         self.tick_count = self.tick_count.checked_add(1).expect("tick_count overflow");
@@ -74,7 +176,7 @@ impl PoH {
             self.epoch_count = epoch_index;
             self.slot_count = 0;
         }
-        return record;
+        return entry;
     }
 
     pub fn verify_records(records: &[PoHRecord]) -> bool {
@@ -85,22 +187,73 @@ impl PoH {
         for window in records.windows(2) {
             let prev: &PoHRecord = &window[0];
             let curr: &PoHRecord = &window[1];
-            let event_data: Option<&[u8]> = curr.event.as_deref();
-
-            if !hash::verify_hash_chain(&prev.hash, &curr.hash, DEFAULT_HASHES_PER_TICK, event_data) {
+            if !curr.follows(&prev.hash, prev.tick_index) {
                 return false;
             }
+        }
+        return true;
+    }
+
+    /// Same as [`PoH::verify_records`] split over `threads` threads, neighbouring chunks share a record so every link is checked.
+    pub fn verify_records_parallel(records: &[PoHRecord], threads: usize) -> bool {
+        if records.is_empty() {
+            return false;
+        }
+        let chunk: usize = records.len().div_ceil(threads.max(1)).max(2);
+        return thread::scope(|scope| {
+            let handles: Vec<ScopedJoinHandle<bool>> = (0..records.len().saturating_sub(1))
+                .step_by(chunk.saturating_sub(1))
+                .map(|start| {
+                    let end: usize = start.saturating_add(chunk).min(records.len());
+                    scope.spawn(move || Self::verify_records(&records[start..end]))
+                })
+                .collect();
+            let mut valid: bool = true;
+            for handle in handles {
+                valid &= handle.join().unwrap_or(false);
+            }
+            return valid;
+        });
+    }
 
-            // Verify sequence numbers.
-            let tick_index_valid: bool = curr.tick_index == prev.tick_index.saturating_add(1);
-            let slot_index_valid: bool = curr.slot_index == curr.tick_index / DEFAULT_TICKS_PER_SLOT;
-            let epoch_valid: bool = curr.epoch_index == curr.tick_index / (DEFAULT_TICKS_PER_SLOT * DEFAULT_SLOTS_PER_EPOCH);
+    /// Verify borrowed records in place, e.g. from a [`MappedLedger`](crate::types::MappedLedger), nothing is decoded or copied.
+    pub fn verify_views<'a>(views: impl IntoIterator<Item = anyhow::Result<RecordView<'a>>>) -> anyhow::Result<bool> {
+        let mut views = views.into_iter();
+        let Some(first) = views.next() else {
+            return Ok(false);
+        };
+        let mut prev: RecordView = first?;
+        for view in views {
+            let curr: RecordView = view?;
+            // A committed event is mixed in by its hash, the attached encoding must hash to it.
+            let event_data: Option<&[u8]> = match (curr.commitment, curr.event) {
+                (Some(commitment), Some(event)) if hash::hash(event) != *commitment => return Ok(false),
+                (Some(commitment), _) => Some(commitment),
+                (None, event) => event,
+            };
+            let indices: [u64; 3] = [curr.tick_index, curr.slot_index, curr.epoch_index];
+            if !verify_link(prev.hash, prev.tick_index, curr.hash, indices, curr.num_hashes, event_data) {
+                return Ok(false);
+            }
+            prev = curr;
+        }
+        return Ok(true);
+    }
 
-            if !(tick_index_valid && slot_index_valid && epoch_valid) {
-                return false;
+    /// Verify records as they are read, e.g. straight from ledger segments, holding about one slot at a time.
+    pub fn verify_stream(records: impl IntoIterator<Item = anyhow::Result<PoHRecord>>) -> anyhow::Result<bool> {
+        let mut chunk: Vec<PoHRecord> = Vec::with_capacity(DEFAULT_TICKS_PER_SLOT as usize);
+        for record in records {
+            chunk.push(record?);
+            if chunk.len() > DEFAULT_TICKS_PER_SLOT as usize {
+                if !Self::verify_records(&chunk) {
+                    return Ok(false);
+                }
+                // The last record anchors the next chunk.
+                chunk.drain(..chunk.len().saturating_sub(1));
             }
         }
-        return true;
+        return Ok(Self::verify_records(&chunk));
     }
 
     pub fn verify_timestamps(records: &[PoHRecord], log_failures: bool) -> bool {
@@ -109,11 +262,14 @@ impl PoH {
         }
 
         let first_timestamp: u64 = records[0].timestamp_ms;
+        let first_tick: u64 = records[0].tick_index;
         // let mut all_valid: bool = true;
 
         for (i, record) in records.iter().enumerate() {
             let timestamp: u64 = record.timestamp_ms;
-            let expected_timestamp: u64 = first_timestamp.saturating_add((i as u64).checked_mul(DEFAULT_US_PER_TICK).unwrap_or(0) / 1000);
+            // Expect time by tick distance so sparse records line up too.
+            let ticks: u64 = record.tick_index.saturating_sub(first_tick);
+            let expected_timestamp: u64 = first_timestamp.saturating_add(ticks.checked_mul(DEFAULT_US_PER_TICK).unwrap_or(0) / 1000);
             // Adjust tolerance based on whether this is an event tick.
             let allowed_drift: u64 = 8; // ~8ms tolerance, relaxed.
             // Ensure we don't underflow.
@@ -146,3 +302,24 @@ impl PoH {
         return true;
     }
 }
+
+// Check that a record with `hash`, its tick, slot and epoch `indices` and `num_hashes` follows the record at `prev_tick` with `prev_hash`.
+fn verify_link(prev_hash: &[u8; 32], prev_tick: u64, hash: &[u8; 32], indices: [u64; 3], num_hashes: u64, event_data: Option<&[u8]>) -> bool {
+    let [tick_index, slot_index, epoch_index] = indices;
+    // Collapsed records span whole empty ticks, an event is only ever mixed into a single tick.
+    let ticks: u64 = num_hashes / DEFAULT_HASHES_PER_TICK;
+    if ticks == 0 || num_hashes % DEFAULT_HASHES_PER_TICK != 0 || (event_data.is_some() && ticks != 1) {
+        return false;
+    }
+
+    // Verify sequence numbers before hashing, so a huge claimed `num_hashes` is refused without being replayed.
+    // A gap may never skip the last tick of a slot.
+    let next_tick: u64 = prev_tick.saturating_add(1);
+    let tick_index_valid: bool = tick_index == prev_tick.saturating_add(ticks) && tick_index / DEFAULT_TICKS_PER_SLOT == next_tick / DEFAULT_TICKS_PER_SLOT;
+    let slot_index_valid: bool = slot_index == tick_index / DEFAULT_TICKS_PER_SLOT;
+    let epoch_valid: bool = epoch_index == tick_index / (DEFAULT_TICKS_PER_SLOT * DEFAULT_SLOTS_PER_EPOCH);
+    if !(tick_index_valid && slot_index_valid && epoch_valid) {
+        return false;
+    }
+    return hash::verify_hash_chain(prev_hash, hash, num_hashes, event_data);
+}
@@ -0,0 +1,187 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::mem;
+
+use crate::types::Event;
+
+use lib::utils::hash;
+
+use anyhow::{Result, anyhow, bail};
+use hex::encode;
+
+// Encoding tags, never reuse or renumber them since they are part of every hash.
+const TAG_DATA: u8 = 0;
+const TAG_DOCUMENT: u8 = 1;
+const TAG_WALL_CLOCK: u8 = 2;
+const TAG_SLOT_MARKER: u8 = 3;
+const TAG_CROSS_CHAIN: u8 = 4;
+const TAG_SCHEDULE_SLIP: u8 = 5;
+const TAG_REFERENCED: u8 = 6;
+
+impl Event {
+    /// Canonical binary encoding, this is exactly what gets mixed into the hash chain.
+    /// A tag byte followed by little-endian fields, variable-length fields carry a u32 length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+        self.encode_into(&mut buffer);
+        return buffer;
+    }
+
+    /// Same as [`Event::encode`] but reuses `buffer`, which is cleared first.
+    pub fn encode_into(&self, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        put_event(buffer, self);
+    }
+
+    /// Name of the variant, as in the `type` field of the JSON form.
+    pub fn kind(&self) -> &'static str {
+        return match self {
+            Event::Data { .. } => "data",
+            Event::Document { .. } => "document",
+            Event::WallClock { .. } => "wall_clock",
+            Event::SlotMarker { .. } => "slot_marker",
+            Event::CrossChain { .. } => "cross_chain",
+            Event::ScheduleSlip { .. } => "schedule_slip",
+            Event::Referenced { .. } => "referenced",
+        };
+    }
+
+    /// Slot hash the event refers to and how many slots after it the event may still be recorded, for [`Event::Referenced`].
+    pub fn reference(&self) -> Option<(&[u8; 32], u64)> {
+        return match self {
+            Event::Referenced { reference, max_age_slots, .. } => Some((reference, *max_age_slots)),
+            _ => None,
+        };
+    }
+
+    /// Hash of the canonical encoding, what gets mixed into the chain when events are committed by hash only.
+    pub fn commitment(&self) -> [u8; 32] {
+        return hash::hash(&self.encode());
+    }
+
+    /// Decode a canonical encoding, trailing bytes are rejected.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let (tag, mut rest) = data.split_first().ok_or_else(|| anyhow!("Empty event encoding."))?;
+        let event: Event = match *tag {
+            TAG_DATA => Event::Data {
+                bytes: take_bytes(&mut rest)?.to_vec(),
+            },
+            TAG_DOCUMENT => Event::Document { hash: take_hash(&mut rest)? },
+            TAG_WALL_CLOCK => Event::WallClock { unix_ms: take_u64(&mut rest)? },
+            TAG_SLOT_MARKER => Event::SlotMarker { slot: take_u64(&mut rest)? },
+            TAG_CROSS_CHAIN => Event::CrossChain {
+                chain: String::from_utf8(take_bytes(&mut rest)?.to_vec())?,
+                height: take_u64(&mut rest)?,
+                hash: take_hash(&mut rest)?,
+            },
+            TAG_SCHEDULE_SLIP => Event::ScheduleSlip {
+                tick: take_u64(&mut rest)?,
+                lateness_us: take_u64(&mut rest)?,
+            },
+            TAG_REFERENCED => {
+                let reference: [u8; 32] = take_hash(&mut rest)?;
+                let max_age_slots: u64 = take_u64(&mut rest)?;
+                let event: Event = Event::decode(mem::take(&mut rest))?;
+                if event.reference().is_some() {
+                    bail!("Nested event references.");
+                }
+                Event::Referenced {
+                    reference,
+                    max_age_slots,
+                    event: Box::new(event),
+                }
+            }
+            other => bail!("Unknown event tag {}.", other),
+        };
+        if !rest.is_empty() {
+            bail!("Event encoding has {} trailing bytes.", rest.len());
+        }
+        return Ok(event);
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        return match self {
+            Event::Data { bytes } => write!(f, "Data {} bytes", bytes.len()),
+            Event::Document { hash } => write!(f, "Document 0x{}...", &encode(hash)[..17]),
+            Event::WallClock { unix_ms } => write!(f, "Wall clock {}ms", unix_ms),
+            Event::SlotMarker { slot } => write!(f, "Slot marker {}", slot),
+            Event::CrossChain { chain, height, hash } => write!(f, "Anchor {}@{} 0x{}...", chain, height, &encode(hash)[..17]),
+            Event::ScheduleSlip { tick, lateness_us } => write!(f, "Schedule slip at tick {}, {}us behind", tick, lateness_us),
+            Event::Referenced { reference, max_age_slots, event } => write!(f, "{} within {} slots of 0x{}...", event, max_age_slots, &encode(reference)[..17]),
+        };
+    }
+}
+
+// Append the canonical encoding of `event` to `buffer`.
+fn put_event(buffer: &mut Vec<u8>, event: &Event) {
+    match event {
+        Event::Data { bytes } => {
+            buffer.push(TAG_DATA);
+            put_bytes(buffer, bytes);
+        }
+        Event::Document { hash } => {
+            buffer.push(TAG_DOCUMENT);
+            buffer.extend_from_slice(hash);
+        }
+        Event::WallClock { unix_ms } => {
+            buffer.push(TAG_WALL_CLOCK);
+            buffer.extend_from_slice(&unix_ms.to_le_bytes());
+        }
+        Event::SlotMarker { slot } => {
+            buffer.push(TAG_SLOT_MARKER);
+            buffer.extend_from_slice(&slot.to_le_bytes());
+        }
+        Event::CrossChain { chain, height, hash } => {
+            buffer.push(TAG_CROSS_CHAIN);
+            put_bytes(buffer, chain.as_bytes());
+            buffer.extend_from_slice(&height.to_le_bytes());
+            buffer.extend_from_slice(hash);
+        }
+        Event::ScheduleSlip { tick, lateness_us } => {
+            buffer.push(TAG_SCHEDULE_SLIP);
+            buffer.extend_from_slice(&tick.to_le_bytes());
+            buffer.extend_from_slice(&lateness_us.to_le_bytes());
+        }
+        Event::Referenced { reference, max_age_slots, event } => {
+            buffer.push(TAG_REFERENCED);
+            buffer.extend_from_slice(reference);
+            buffer.extend_from_slice(&max_age_slots.to_le_bytes());
+            // The wrapped encoding comes last, so it needs no length prefix.
+            put_event(buffer, event);
+        }
+    }
+}
+
+fn put_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    let length: u32 = u32::try_from(bytes.len()).expect("event field exceeds 4 GiB");
+    buffer.extend_from_slice(&length.to_le_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+fn take<'a>(rest: &mut &'a [u8], length: usize) -> Result<&'a [u8]> {
+    if rest.len() < length {
+        bail!("Truncated event encoding.");
+    }
+    let (head, tail) = rest.split_at(length);
+    *rest = tail;
+    return Ok(head);
+}
+
+fn take_u64(rest: &mut &[u8]) -> Result<u64> {
+    let mut bytes: [u8; 8] = [0u8; 8];
+    bytes.copy_from_slice(take(rest, 8)?);
+    return Ok(u64::from_le_bytes(bytes));
+}
+
+fn take_hash(rest: &mut &[u8]) -> Result<[u8; 32]> {
+    let mut hash: [u8; 32] = [0u8; 32];
+    hash.copy_from_slice(take(rest, 32)?);
+    return Ok(hash);
+}
+
+fn take_bytes<'a>(rest: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let mut length: [u8; 4] = [0u8; 4];
+    length.copy_from_slice(take(rest, 4)?);
+    return take(rest, u32::from_le_bytes(length) as usize);
+}
@@ -0,0 +1,162 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::types::{ForkChoice, ForkKey, ForkNode, ForkTree, PoH, PoHRecord};
+
+use anyhow::{Result, anyhow, bail};
+
+impl ForkKey {
+    pub fn of(record: &PoHRecord) -> Self {
+        return Self {
+            slot: record.slot_index,
+            hash: record.hash,
+        };
+    }
+}
+
+impl Default for ForkChoice {
+    fn default() -> Self {
+        return Self::Longest;
+    }
+}
+
+impl ForkTree {
+    /// Start a tree whose every fork descends from `root`.
+    pub fn new(root: PoHRecord, choice: ForkChoice) -> Self {
+        let key: ForkKey = ForkKey::of(&root);
+        let node: ForkNode = ForkNode {
+            event_count: u64::from(root.has_event()),
+            record: root,
+            parent: None,
+            children: Vec::new(),
+            weight: 0,
+        };
+        return Self {
+            nodes: HashMap::from([(key, node)]),
+            root: key,
+            choice,
+        };
+    }
+
+    /// Attach a segment of records continuing the fork at `parent`.
+    /// The segment is verified against the parent before it is tracked.
+    ///
+    /// # Returns
+    /// The key of the new fork tip.
+    pub fn insert(&mut self, parent: &ForkKey, segment: &[PoHRecord]) -> Result<ForkKey> {
+        let parent_node: &ForkNode = self.nodes.get(parent).ok_or_else(|| anyhow!("Unknown parent fork at slot {}.", parent.slot))?;
+        let last: &PoHRecord = segment.last().ok_or_else(|| anyhow!("Cannot insert an empty segment."))?;
+        let key: ForkKey = ForkKey::of(last);
+
+        // Inserting the same segment twice is a no-op.
+        if let Some(existing) = self.nodes.get(&key) {
+            if existing.parent == Some(*parent) {
+                return Ok(key);
+            }
+            bail!("Fork at slot {} is already tracked under another parent.", key.slot);
+        }
+
+        let mut chain: Vec<PoHRecord> = Vec::with_capacity(segment.len().saturating_add(1));
+        chain.push(parent_node.record.clone());
+        chain.extend_from_slice(segment);
+        if !PoH::verify_records(&chain) {
+            bail!("Segment does not continue the fork at slot {}.", parent.slot);
+        }
+
+        let segment_events: u64 = segment.iter().filter(|r| r.has_event()).count() as u64;
+        let node: ForkNode = ForkNode {
+            record: last.clone(),
+            parent: Some(*parent),
+            children: Vec::new(),
+            event_count: parent_node.event_count.saturating_add(segment_events),
+            weight: 0,
+        };
+
+        self.nodes.insert(key, node);
+        if let Some(parent_node) = self.nodes.get_mut(parent) {
+            parent_node.children.push(key);
+        }
+        return Ok(key);
+    }
+
+    /// Assign an external weight to a fork, used by [`ForkChoice::Weighted`].
+    pub fn set_weight(&mut self, key: &ForkKey, weight: u64) -> Result<()> {
+        let node: &mut ForkNode = self.nodes.get_mut(key).ok_or_else(|| anyhow!("Unknown fork at slot {}.", key.slot))?;
+        node.weight = weight;
+        return Ok(());
+    }
+
+    /// Branch a new generator continuing from a tracked fork.
+    pub fn fork(&self, key: &ForkKey) -> Result<PoH> {
+        let node: &ForkNode = self.nodes.get(key).ok_or_else(|| anyhow!("Unknown fork at slot {}.", key.slot))?;
+        return Ok(PoH::from_record(&node.record));
+    }
+
+    /// Check if a fork is tracked.
+    pub fn contains(&self, key: &ForkKey) -> bool {
+        return self.nodes.contains_key(key);
+    }
+
+    /// Get the tips of all competing forks.
+    pub fn leaves(&self) -> Vec<ForkKey> {
+        let mut leaves: Vec<ForkKey> = self.nodes.iter().filter(|(_, node)| node.children.is_empty()).map(|(key, _)| *key).collect();
+        leaves.sort();
+        return leaves;
+    }
+
+    /// Select the heaviest fork tip according to the configured [`ForkChoice`].
+    /// Ties go to the lowest slot, then the lowest hash, so every observer picks the same fork.
+    pub fn heaviest(&self) -> ForkKey {
+        return self
+            .leaves()
+            .into_iter()
+            .max_by(|a, b| self.score(a).cmp(&self.score(b)).then_with(|| b.cmp(a)))
+            .unwrap_or(self.root);
+    }
+
+    fn score(&self, key: &ForkKey) -> u64 {
+        let Some(node) = self.nodes.get(key) else {
+            return 0;
+        };
+        return match self.choice {
+            ForkChoice::Longest => node.record.tick_index,
+            ForkChoice::MostEvents => node.event_count,
+            ForkChoice::Weighted => {
+                let mut total: u64 = 0;
+                let mut current: Option<&ForkNode> = Some(node);
+                while let Some(node) = current {
+                    total = total.saturating_add(node.weight);
+                    current = node.parent.and_then(|parent| self.nodes.get(&parent));
+                }
+                total
+            }
+        };
+    }
+
+    /// Root the tree at `key` and prune every fork that does not descend from it.
+    ///
+    /// # Returns
+    /// The number of pruned nodes.
+    pub fn set_root(&mut self, key: &ForkKey) -> Result<usize> {
+        if !self.nodes.contains_key(key) {
+            bail!("Unknown fork at slot {}.", key.slot);
+        }
+
+        let mut keep: HashSet<ForkKey> = HashSet::new();
+        let mut pending: Vec<ForkKey> = vec![*key];
+        while let Some(current) = pending.pop() {
+            if let Some(node) = self.nodes.get(&current) {
+                pending.extend(node.children.iter().copied());
+            }
+            keep.insert(current);
+        }
+
+        let before: usize = self.nodes.len();
+        self.nodes.retain(|node_key, _| keep.contains(node_key));
+        if let Some(root) = self.nodes.get_mut(key) {
+            root.parent = None;
+        }
+        self.root = *key;
+
+        return Ok(before.saturating_sub(self.nodes.len()));
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::types::{Identity, PoH, PoHRecord};
+
+use anyhow::{Context, Result, anyhow, bail};
+use hex::encode;
+use ring::pkcs8::Document;
+use ring::rand::SystemRandom;
+use ring::signature::{ED25519, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+
+impl Identity {
+    /// Generate a fresh random keypair.
+    pub fn generate() -> Result<Self> {
+        let pkcs8: Document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|_| anyhow!("Failed to generate an Ed25519 keypair."))?;
+        return Self::from_pkcs8(pkcs8.as_ref());
+    }
+
+    /// Load a keypair from a PKCS#8 v2 document.
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        let keypair: Ed25519KeyPair = Ed25519KeyPair::from_pkcs8(pkcs8).map_err(|e| anyhow!("Invalid Ed25519 keypair: {}.", e))?;
+        return Ok(Self {
+            keypair: Arc::new(keypair),
+            pkcs8: Arc::from(pkcs8),
+        });
+    }
+
+    /// Write the keypair to a new file only the owner can read, an existing file is never overwritten.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut options: OpenOptions = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file: File = options.open(path).with_context(|| format!("Failed to create keypair file {}", path.display()))?;
+        file.write_all(&self.pkcs8)?;
+        file.sync_all()?;
+        return Ok(());
+    }
+
+    /// Read a keypair file written by [`Identity::save`].
+    /// Files readable by anyone but the owner are refused.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut file: File = File::open(path).with_context(|| format!("Failed to open keypair file {}", path.display()))?;
+        #[cfg(unix)]
+        {
+            let mode: u32 = file.metadata()?.permissions().mode();
+            if mode & 0o077 != 0 {
+                bail!(
+                    "Keypair file {} is accessible by others (mode {:o}), restrict it to 600.",
+                    path.display(),
+                    mode & 0o777
+                );
+            }
+        }
+
+        let mut pkcs8: Vec<u8> = Vec::new();
+        file.read_to_end(&mut pkcs8)?;
+        return Self::from_pkcs8(&pkcs8).with_context(|| format!("Corrupted keypair file {}", path.display()));
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        let mut public_key: [u8; 32] = [0u8; 32];
+        public_key.copy_from_slice(self.keypair.public_key().as_ref());
+        return public_key;
+    }
+
+    pub fn public_key_hex(&self) -> String {
+        return encode(self.public_key());
+    }
+
+    pub fn public_key_base58(&self) -> String {
+        return bs58::encode(self.public_key()).into_string();
+    }
+
+    /// Sign the hash of the slot `record` closes.
+    pub fn sign_slot(&self, record: &PoHRecord) -> [u8; 64] {
+        let mut signature: [u8; 64] = [0u8; 64];
+        signature.copy_from_slice(self.keypair.sign(&record.slot_message()).as_ref());
+        return signature;
+    }
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        return write!(f, "{} (0x{})", self.public_key_base58(), self.public_key_hex());
+    }
+}
+
+impl Debug for Identity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        return write!(f, "Identity(0x{})", encode(self.public_key()));
+    }
+}
+
+impl PoHRecord {
+    /// Message signed for a slot, the slot index followed by its last hash.
+    pub fn slot_message(&self) -> [u8; 40] {
+        return Self::message_for_slot(self.slot_index, &self.hash);
+    }
+
+    /// Message signed for slot `slot_index` closed by `hash`, for checking a signature without the record at hand.
+    pub fn message_for_slot(slot_index: u64, hash: &[u8; 32]) -> [u8; 40] {
+        let mut message: [u8; 40] = [0u8; 40];
+        message[..8].copy_from_slice(&slot_index.to_le_bytes());
+        message[8..].copy_from_slice(hash);
+        return message;
+    }
+}
+
+impl PoH {
+    /// Verify the chain and require every slot in it to be signed by `public_key`.
+    /// Signatures anywhere but on the last record of a slot are rejected.
+    pub fn verify_signed_records(records: &[PoHRecord], public_key: &[u8; 32]) -> bool {
+        if !Self::verify_records(records) {
+            return false;
+        }
+
+        let key: UnparsedPublicKey<&[u8; 32]> = UnparsedPublicKey::new(&ED25519, public_key);
+        return records.iter().all(|record| match (&record.signature, record.is_slot_end()) {
+            (Some(signature), true) => key.verify(&record.slot_message(), signature).is_ok(),
+            (None, false) => true,
+            _ => false,
+        });
+    }
+}
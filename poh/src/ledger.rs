@@ -0,0 +1,801 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::types::{
+    Checkpoint, CompressionStats, FsyncPolicy, Ledger, LedgerConfig, LedgerEntry, LedgerHeader, LedgerQuery, LedgerReader, LedgerRecords, PoH, PoHConfig, PoHRecord,
+    RecordDecoder, RetentionPolicy, SeekEntry, SlotLocation,
+};
+
+use lib::{DEFAULT_SLOTS_PER_EPOCH, DEFAULT_TICKS_PER_SLOT};
+
+use anyhow::{Context, Error, Result, bail};
+
+// Slot index file kept next to the segments.
+const INDEX_FILE: &str = "slots.idx";
+// Event index file kept next to the segments.
+const EVENTS_FILE: &str = "events.idx";
+// Segments are named by their sequence number with this extension.
+const SEGMENT_EXTENSION: &str = "seg";
+// Closed segments replaced by their zstd-compressed form.
+const COMPRESSED_EXTENSION: &str = "zst";
+// Slot, segment and offset of one index entry.
+const INDEX_ENTRY_SIZE: usize = 24;
+// Event hash and tick of one event index entry.
+const EVENT_ENTRY_SIZE: usize = 32 + 8;
+
+impl Ledger {
+    /// Open the ledger in `dir`, creating it if needed.
+    /// A torn write at the end of the last segment is truncated away and appending continues after the last intact record.
+    /// An existing ledger must have been written with the chain settings of `poh_config`.
+    pub fn open(dir: &Path, config: LedgerConfig, poh_config: &PoHConfig) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create ledger directory {}", dir.display()))?;
+
+        let mut segments: Vec<u64> = list_segments(dir)?;
+        // A compressed copy next to its raw segment is left from an interrupted compression, the raw file is kept.
+        for &segment in &segments {
+            if segment_path(dir, segment).exists() && compressed_path(dir, segment).exists() {
+                fs::remove_file(compressed_path(dir, segment))?;
+            }
+        }
+        // Segments up to the checkpoint are left from an interrupted prune.
+        let checkpoint: Option<Checkpoint> = Checkpoint::load(dir)?;
+        if let Some(checkpoint) = &checkpoint {
+            for &segment in segments.iter().filter(|segment| **segment <= checkpoint.segment) {
+                remove_segment(dir, segment)?;
+            }
+            segments.retain(|segment| *segment > checkpoint.segment);
+        }
+
+        let mut index: BTreeMap<u64, SlotLocation> = BTreeMap::new();
+        let mut last: Option<PoHRecord> = None;
+        let mut offset: u64 = 0;
+        let mut closed: bool = false;
+        // A crash while rolling can leave the newest segment without a complete header.
+        while let Some(&segment) = segments.last() {
+            let path: PathBuf = segment_path(dir, segment);
+            if !path.exists() {
+                // Compressed segments are closed, appending continues in the next one.
+                last = Self::last_compressed_record(&compressed_path(dir, segment))?;
+                closed = true;
+                break;
+            }
+            match recover(&path, segment, &mut index)? {
+                Some((end, record)) => {
+                    offset = end;
+                    last = record;
+                    break;
+                }
+                None => {
+                    eprintln!("Warning: removing segment {} with a torn header.", path.display());
+                    fs::remove_file(&path)?;
+                    segments.pop();
+                }
+            }
+        }
+        // A segment just rolled to has no records yet, the chain continues from the segment before it or the checkpoint.
+        if last.is_none() {
+            if let Some(&previous) = segments.iter().rev().nth(1) {
+                last = last_record(&segment_files(dir, previous)?[0])?;
+            }
+            last = last.or_else(|| checkpoint.as_ref().map(Checkpoint::anchor));
+        }
+
+        // Readers take the chain settings from the header, records appended under a header that doesn't describe them would be misread.
+        if let Some(&newest) = segments.last() {
+            let header: LedgerHeader = LedgerHeader::read_from(&mut open_segment(&segment_files(dir, newest)?[0])?)?;
+            header.validate().with_context(|| format!("Ledger in {}", dir.display()))?;
+            if (header.sparse, header.commit_events) != (poh_config.sparse, poh_config.commit_events) {
+                bail!(
+                    "Ledger in {} was written with sparse={} and commit_events={}, resume it with the same settings.",
+                    dir.display(),
+                    header.sparse,
+                    header.commit_events
+                );
+            }
+        }
+        let segment: u64 = match segments.last() {
+            Some(&segment) if closed => segment.saturating_add(1),
+            Some(&segment) => segment,
+            None => checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.segment.saturating_add(1)),
+        };
+        let writer: BufWriter<File> = match segments.is_empty() || closed {
+            true => {
+                let (writer, header_size) = create_segment(dir, segment, poh_config)?;
+                offset = header_size;
+                writer
+            }
+            false => BufWriter::new(OpenOptions::new().append(true).open(segment_path(dir, segment))?),
+        };
+        // Entries for the last segment were rebuilt from the scan, older ones come from the index file.
+        let first: u64 = checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.segment.saturating_add(1));
+        index.extend(
+            read_index(dir)?
+                .into_iter()
+                .filter(|(_, location)| location.segment >= first && location.segment < segment),
+        );
+        let index_file: File = write_index(dir, &index)?;
+        // Entries of records lost to a torn write stay behind, lookups check the record they point to.
+        let events_file: File = OpenOptions::new().create(true).append(true).open(dir.join(EVENTS_FILE))?;
+
+        let mut ledger: Self = Self {
+            dir: dir.to_path_buf(),
+            config,
+            poh_config: poh_config.clone(),
+            segment,
+            writer,
+            offset,
+            index,
+            index_file,
+            events_file,
+            last,
+            checkpoint,
+            buffer: Vec::new(),
+            compression: CompressionStats::default(),
+        };
+        // Compress segments the previous run closed but did not get to, or all of them when compression was just turned on.
+        if ledger.config.compress {
+            ledger.compress_closed()?;
+        }
+        ledger.prune()?;
+        // The previous run may have stopped between filling a segment and rolling it.
+        if !closed && ledger.last.as_ref().is_some_and(PoHRecord::is_slot_end) && ledger.offset >= ledger.config.segment_bytes {
+            ledger.roll()?;
+        }
+        return Ok(ledger);
+    }
+
+    /// Append the next record, records must follow the last one.
+    pub fn append(&mut self, record: &PoHRecord) -> Result<()> {
+        if let Some(last) = self.last.as_ref().filter(|last| record.tick_index <= last.tick_index) {
+            bail!(
+                "Record at tick {} does not follow the last ledger record at tick {}.",
+                record.tick_index,
+                last.tick_index
+            );
+        }
+
+        if self.last.as_ref().is_none_or(|last| last.slot_index != record.slot_index) {
+            let location: SlotLocation = SlotLocation {
+                segment: self.segment,
+                offset: self.offset,
+            };
+            self.index_file.write_all(&encode_entry(record.slot_index, &location))?;
+            self.index.insert(record.slot_index, location);
+        }
+
+        self.buffer.clear();
+        record.encode_into(&mut self.buffer)?;
+        self.writer.write_all(&self.buffer)?;
+        self.offset = self.offset.saturating_add(self.buffer.len() as u64);
+        self.last = Some(record.clone());
+        if let Some(event_hash) = record.event_hash() {
+            let mut entry: [u8; EVENT_ENTRY_SIZE] = [0u8; EVENT_ENTRY_SIZE];
+            entry[..32].copy_from_slice(&event_hash);
+            entry[32..].copy_from_slice(&record.tick_index.to_le_bytes());
+            self.events_file.write_all(&entry)?;
+        }
+
+        let slot_end: bool = record.is_slot_end();
+        if self.config.fsync == FsyncPolicy::Record || (self.config.fsync == FsyncPolicy::Slot && slot_end) {
+            self.sync()?;
+        }
+        // Segments only roll between slots, so a slot can be read from a single file.
+        if slot_end && self.offset >= self.config.segment_bytes {
+            self.roll()?;
+        }
+        return Ok(());
+    }
+
+    /// Force everything appended so far to stable storage.
+    pub fn sync(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        self.index_file.sync_data()?;
+        self.events_file.sync_data()?;
+        return Ok(());
+    }
+
+    /// Read every record of `slot` through the index.
+    pub fn read_slot(&mut self, slot: u64) -> Result<Vec<PoHRecord>> {
+        let Some(location) = self.index.get(&slot).copied() else {
+            return Ok(Vec::new());
+        };
+        // The slot may still sit in the write buffer.
+        self.writer.flush()?;
+
+        let path: PathBuf = segment_path(&self.dir, location.segment);
+        if !path.exists() {
+            return Self::read_compressed_slot(&compressed_path(&self.dir, location.segment), slot);
+        }
+        let mut file: File = File::open(path)?;
+        file.seek(SeekFrom::Start(location.offset))?;
+        let mut reader: BufReader<File> = BufReader::new(file);
+        let mut records: Vec<PoHRecord> = Vec::new();
+        while let Some(record) = PoHRecord::read_from(&mut reader)? {
+            if record.slot_index != slot {
+                break;
+            }
+            records.push(record);
+        }
+        return Ok(records);
+    }
+
+    /// Paths of the segment files, oldest first.
+    pub fn segments(&self) -> Result<Vec<PathBuf>> {
+        return segment_files(&self.dir, 0);
+    }
+
+    /// Stream every record appended so far, compressed segments are decompressed on the fly.
+    pub fn records(&mut self) -> Result<LedgerRecords> {
+        self.writer.flush()?;
+        return Ok(LedgerRecords::new(self.segments()?));
+    }
+
+    /// Verify the whole chain straight from the segments.
+    /// A pruned ledger verifies from its checkpoint, which must be signed by the expected generator `public_key` or match a known
+    /// `checkpoint` digest. Its own file can't vouch for it, so verifying a pruned ledger without either is an error.
+    pub fn verify(&mut self, public_key: Option<&[u8; 32]>, checkpoint: Option<&[u8; 32]>) -> Result<bool> {
+        if let Some(current) = &self.checkpoint {
+            if public_key.is_none() && checkpoint.is_none() {
+                bail!(
+                    "Ledger {} is pruned, verifying it needs the generator public key or a known checkpoint digest.",
+                    self.dir.display()
+                );
+            }
+            let trusted: bool = public_key.is_some_and(|public_key| current.verify(public_key)) || checkpoint.is_some_and(|digest| current.digest() == *digest);
+            if !trusted {
+                return Ok(false);
+            }
+        }
+        let anchor: Option<Result<PoHRecord>> = self.checkpoint.as_ref().map(|checkpoint| Ok(checkpoint.anchor()));
+        return PoH::verify_stream(anchor.into_iter().chain(self.records()?));
+    }
+
+    /// Delete the oldest segments the retention policy no longer keeps.
+    /// A checkpoint on the last deleted record is saved first, so what is left still verifies.
+    ///
+    /// # Returns
+    /// The new checkpoint, `None` if nothing was deleted.
+    pub fn prune(&mut self) -> Result<Option<Checkpoint>> {
+        let closed: Vec<u64> = list_segments(&self.dir)?.into_iter().filter(|segment| *segment < self.segment).collect();
+        let count: usize = self.prunable(&closed)?;
+        let Some(&segment) = closed[..count].last() else {
+            return Ok(None);
+        };
+        let path: PathBuf = segment_files(&self.dir, segment)?.remove(0);
+        let Some(record) = last_record(&path)? else {
+            bail!("Ledger segment {} has no records.", path.display());
+        };
+
+        let checkpoint: Checkpoint = Checkpoint::new(segment, &record, self.checkpoint.as_ref(), self.poh_config.identity.as_ref());
+        checkpoint.save(&self.dir)?;
+        for &pruned in &closed[..count] {
+            remove_segment(&self.dir, pruned)?;
+        }
+        self.index.retain(|_, location| location.segment > segment);
+        self.index_file = write_index(&self.dir, &self.index)?;
+        self.events_file = prune_events(&self.dir, record.tick_index)?;
+        File::open(&self.dir)?.sync_all()?;
+
+        self.checkpoint = Some(checkpoint.clone());
+        return Ok(Some(checkpoint));
+    }
+
+    // Close the current segment and start the next one.
+    fn roll(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        if self.config.fsync != FsyncPolicy::Never {
+            self.writer.get_ref().sync_data()?;
+        }
+
+        let segment: u64 = self.segment.saturating_add(1);
+        let (writer, header_size) = create_segment(&self.dir, segment, &self.poh_config)?;
+        if self.config.fsync != FsyncPolicy::Never {
+            // Make the new directory entry durable too.
+            File::open(&self.dir)?.sync_all()?;
+        }
+        self.writer = writer;
+        self.segment = segment;
+        self.offset = header_size;
+        if self.config.compress {
+            self.compress_closed()?;
+        }
+        self.prune()?;
+        return Ok(());
+    }
+
+    // How many of the `closed` segments, oldest first, the retention policy lets go.
+    fn prunable(&self, closed: &[u64]) -> Result<usize> {
+        // A segment ends where the next one starts.
+        let next_start = |i: usize| -> Option<(u64, SlotLocation)> {
+            let next: u64 = closed.get(i.saturating_add(1)).copied().unwrap_or(self.segment);
+            return self
+                .index
+                .iter()
+                .find(|(_, location)| location.segment == next)
+                .map(|(slot, location)| (*slot, *location));
+        };
+        let mut count: usize = 0;
+        match self.config.retention {
+            RetentionPolicy::KeepAll => {}
+            RetentionPolicy::Epochs(epochs) => {
+                let current: u64 = self.last.as_ref().map_or(0, |last| last.epoch_index);
+                let first_kept_slot: u64 = current.saturating_add(1).saturating_sub(epochs).saturating_mul(DEFAULT_SLOTS_PER_EPOCH);
+                while count < closed.len() && next_start(count).is_some_and(|(slot, _)| slot <= first_kept_slot) {
+                    count = count.saturating_add(1);
+                }
+            }
+            RetentionPolicy::Bytes(bytes) => {
+                let mut total: u64 = self.offset;
+                let mut sizes: Vec<u64> = Vec::with_capacity(closed.len());
+                for path in segment_files(&self.dir, 0)?.iter().take(closed.len()) {
+                    sizes.push(fs::metadata(path)?.len());
+                }
+                total = total.saturating_add(sizes.iter().sum());
+                while count < closed.len() && total > bytes {
+                    total = total.saturating_sub(sizes[count]);
+                    count = count.saturating_add(1);
+                }
+            }
+            RetentionPolicy::After(timestamp_ms) => {
+                while let Some((slot, location)) = next_start(count).filter(|_| count < closed.len()) {
+                    let first: Option<PoHRecord> = LedgerRecords::from_slot(segment_files(&self.dir, location.segment)?, slot, location.offset)?
+                        .next()
+                        .transpose()?;
+                    if first.is_none_or(|record| record.timestamp_ms >= timestamp_ms) {
+                        break;
+                    }
+                    count = count.saturating_add(1);
+                }
+            }
+        }
+        return Ok(count);
+    }
+
+    // Replace every raw segment before the current one with its compressed form.
+    fn compress_closed(&mut self) -> Result<()> {
+        for segment in list_segments(&self.dir)? {
+            let raw: PathBuf = segment_path(&self.dir, segment);
+            if segment >= self.segment || !raw.exists() {
+                continue;
+            }
+            let stats: CompressionStats = Self::compress_segment(&raw, &compressed_path(&self.dir, segment), self.config.compression_level)?;
+            if self.config.fsync != FsyncPolicy::Never {
+                // The compressed file must be durable before the raw one goes away.
+                File::open(&self.dir)?.sync_all()?;
+            }
+            fs::remove_file(&raw)?;
+            self.compression.add(&stats);
+        }
+        return Ok(());
+    }
+}
+
+impl LedgerRecords {
+    pub fn new(segments: Vec<PathBuf>) -> Self {
+        return Self {
+            segments: segments.into(),
+            current: None,
+        };
+    }
+
+    /// Stream from the start of `slot`, found at `offset` of the first of `segments` or through its seek table when compressed.
+    pub fn from_slot(segments: Vec<PathBuf>, slot: u64, offset: u64) -> Result<Self> {
+        let mut records: Self = Self::new(segments);
+        let Some(path) = records.segments.pop_front() else {
+            return Ok(records);
+        };
+        let header: LedgerHeader = LedgerHeader::read_from(&mut open_segment(&path)?)?;
+        let mut file: File = File::open(&path)?;
+        let reader: Box<dyn Read> = match is_compressed(&path) {
+            true => {
+                let table: Vec<SeekEntry> = SeekEntry::read_table(&mut file)?;
+                let Some(entry) = table.iter().find(|entry| entry.slot >= slot) else {
+                    return Ok(records);
+                };
+                file.seek(SeekFrom::Start(entry.offset))?;
+                Box::new(zstd::Decoder::new(file)?)
+            }
+            false => {
+                file.seek(SeekFrom::Start(offset))?;
+                Box::new(BufReader::new(file))
+            }
+        };
+        records.current = Some(RecordDecoder { reader, header });
+        return Ok(records);
+    }
+
+    fn open_next(&mut self) -> Result<bool> {
+        let Some(path) = self.segments.pop_front() else {
+            return Ok(false);
+        };
+        self.current = Some(RecordDecoder::new(open_segment(&path)?)?);
+        return Ok(true);
+    }
+}
+
+impl Iterator for LedgerRecords {
+    type Item = Result<PoHRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(decoder) = self.current.as_mut() {
+                match decoder.read() {
+                    Ok(Some(record)) => return Some(Ok(record)),
+                    Ok(None) => self.current = None,
+                    Err(e) => {
+                        self.segments.clear();
+                        self.current = None;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            match self.open_next() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl LedgerReader {
+    /// Open the ledger in `dir` for queries, nothing is written or recovered.
+    pub fn open(dir: &Path) -> Result<Self> {
+        if list_segments(dir)
+            .with_context(|| format!("Failed to read ledger directory {}", dir.display()))?
+            .is_empty()
+        {
+            bail!("No ledger segments in {}.", dir.display());
+        }
+        return Ok(Self {
+            dir: dir.to_path_buf(),
+            index: read_index(dir)?.into_iter().collect(),
+            events: read_events(dir)?,
+            checkpoint: Checkpoint::load(dir)?,
+        });
+    }
+
+    /// Header of the oldest segment, with the algorithm and chain parameters the chain was generated with.
+    pub fn header(&self) -> Result<LedgerHeader> {
+        let Some(path) = segment_files(&self.dir, 0)?.into_iter().next() else {
+            bail!("No ledger segments in {}.", self.dir.display());
+        };
+        return LedgerHeader::read_from(&mut open_segment(&path)?);
+    }
+
+    /// Records whose tick index is in `ticks`.
+    pub fn ticks(&self, ticks: Range<u64>) -> Result<LedgerQuery> {
+        let slot: u64 = ticks.start / DEFAULT_TICKS_PER_SLOT;
+        return self.query(slot, Box::new(move |record| position(record.tick_index, &ticks)));
+    }
+
+    /// Records of the slots in `slots`.
+    pub fn slots(&self, slots: Range<u64>) -> Result<LedgerQuery> {
+        let slot: u64 = slots.start;
+        return self.query(slot, Box::new(move |record| position(record.slot_index, &slots)));
+    }
+
+    /// Records of the epochs in `epochs`.
+    pub fn epochs(&self, epochs: Range<u64>) -> Result<LedgerQuery> {
+        let slot: u64 = epochs.start.saturating_mul(DEFAULT_SLOTS_PER_EPOCH);
+        return self.query(slot, Box::new(move |record| position(record.epoch_index, &epochs)));
+    }
+
+    /// Records stamped within `timestamps_ms`, in milliseconds since the chain started like [`PoHRecord::timestamp_ms`].
+    pub fn time(&self, timestamps_ms: Range<u64>) -> Result<LedgerQuery> {
+        // Timestamps grow with the ticks, so the first slot starting inside the range is found by bisecting the index.
+        let slots: Vec<u64> = self.index.keys().copied().collect();
+        let (mut low, mut high): (usize, usize) = (0, slots.len());
+        while low < high {
+            let middle: usize = low.saturating_add(high.saturating_sub(low) / 2);
+            if self.first_record(slots[middle])?.is_some_and(|record| record.timestamp_ms < timestamps_ms.start) {
+                low = middle.saturating_add(1);
+            } else {
+                high = middle;
+            }
+        }
+        let slot: u64 = slots.get(low).copied().unwrap_or(u64::MAX);
+        return self.query(slot, Box::new(move |record| position(record.timestamp_ms, &timestamps_ms)));
+    }
+
+    /// Record carrying the event with `hash`, see [`PoHRecord::event_hash`].
+    pub fn event(&self, hash: &[u8; 32]) -> Result<Option<LedgerEntry>> {
+        for &tick in self.events.get(hash).into_iter().flatten() {
+            for found in self.ticks(tick..tick.saturating_add(1))? {
+                let found: LedgerEntry = found?;
+                if found.record.event_hash().as_ref() == Some(hash) {
+                    return Ok(Some(found));
+                }
+            }
+        }
+        return Ok(None);
+    }
+
+    // Stream from the slot before `slot`, so the first record in range comes with its anchor.
+    fn query(&self, slot: u64, position: Box<dyn Fn(&PoHRecord) -> Ordering>) -> Result<LedgerQuery> {
+        return Ok(match self.index.range(..slot).next_back() {
+            Some((&start, location)) => LedgerQuery {
+                records: LedgerRecords::from_slot(segment_files(&self.dir, location.segment)?, start, location.offset)?,
+                previous: None,
+                position,
+            },
+            // From the start of a pruned ledger, the checkpoint anchors the first record.
+            None => LedgerQuery {
+                records: LedgerRecords::new(segment_files(&self.dir, 0)?),
+                previous: self.checkpoint.as_ref().map(|checkpoint| (checkpoint.tick_index, checkpoint.hash)),
+                position,
+            },
+        });
+    }
+
+    fn first_record(&self, slot: u64) -> Result<Option<PoHRecord>> {
+        let Some(location) = self.index.get(&slot) else {
+            return Ok(None);
+        };
+        return LedgerRecords::from_slot(segment_files(&self.dir, location.segment)?, slot, location.offset)?
+            .next()
+            .transpose();
+    }
+}
+
+impl Iterator for LedgerQuery {
+    type Item = Result<LedgerEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record: PoHRecord = match self.records.next()? {
+                Ok(record) => record,
+                Err(e) => return Some(Err(e)),
+            };
+            let anchor: Option<(u64, [u8; 32])> = self.previous.replace((record.tick_index, record.hash));
+            match (self.position)(&record) {
+                Ordering::Less => continue,
+                Ordering::Equal => return Some(Ok(LedgerEntry { record, anchor })),
+                Ordering::Greater => {
+                    // Records only move forward, nothing later can be in range.
+                    self.records.segments.clear();
+                    self.records.current = None;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl LedgerEntry {
+    /// Check the record against its anchor, the first record of a ledger has none to be checked against.
+    pub fn verify(&self) -> bool {
+        return self.anchor.is_some_and(|(tick, hash)| self.record.follows(&hash, tick));
+    }
+}
+
+// Where `value` falls relative to `range`.
+fn position(value: u64, range: &Range<u64>) -> Ordering {
+    if value < range.start {
+        return Ordering::Less;
+    }
+    if value < range.end {
+        return Ordering::Equal;
+    }
+    return Ordering::Greater;
+}
+
+fn segment_path(dir: &Path, segment: u64) -> PathBuf {
+    return dir.join(format!("{:010}.{}", segment, SEGMENT_EXTENSION));
+}
+
+fn compressed_path(dir: &Path, segment: u64) -> PathBuf {
+    return dir.join(format!("{:010}.{}", segment, COMPRESSED_EXTENSION));
+}
+
+fn is_compressed(path: &Path) -> bool {
+    return path.extension().is_some_and(|extension| extension == COMPRESSED_EXTENSION);
+}
+
+// Open a segment from its start, zstd skips the seek table frame of compressed ones.
+fn open_segment(path: &Path) -> Result<Box<dyn Read>> {
+    let file: File = File::open(path).with_context(|| format!("Failed to open ledger segment {}", path.display()))?;
+    return Ok(match is_compressed(path) {
+        true => Box::new(zstd::Decoder::new(file)?),
+        false => Box::new(BufReader::new(file)),
+    });
+}
+
+// Paths of the segments from `first` on, the raw file while a segment is not compressed.
+fn segment_files(dir: &Path, first: u64) -> Result<Vec<PathBuf>> {
+    return Ok(list_segments(dir)?
+        .into_iter()
+        .filter(|segment| *segment >= first)
+        .map(|segment| {
+            let path: PathBuf = segment_path(dir, segment);
+            if path.exists() { path } else { compressed_path(dir, segment) }
+        })
+        .collect());
+}
+
+// Last record of the segment at `path`.
+fn last_record(path: &Path) -> Result<Option<PoHRecord>> {
+    if is_compressed(path) {
+        return Ledger::last_compressed_record(path);
+    }
+    let mut last: Option<PoHRecord> = None;
+    for record in LedgerRecords::new(vec![path.to_path_buf()]) {
+        last = Some(record?);
+    }
+    return Ok(last);
+}
+
+// Delete a segment in whichever form it is.
+fn remove_segment(dir: &Path, segment: u64) -> Result<()> {
+    for path in [segment_path(dir, segment), compressed_path(dir, segment)] {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    return Ok(());
+}
+
+// Sequence numbers of the segments in `dir`, raw or compressed, in order.
+fn list_segments(dir: &Path) -> Result<Vec<u64>> {
+    let mut segments: Vec<u64> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path: PathBuf = entry?.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != SEGMENT_EXTENSION && extension != COMPRESSED_EXTENSION)
+        {
+            continue;
+        }
+        if let Some(segment) = path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse::<u64>().ok()) {
+            segments.push(segment);
+        }
+    }
+    segments.sort_unstable();
+    segments.dedup();
+    return Ok(segments);
+}
+
+// Start a segment with its header, returning the writer and the header size.
+fn create_segment(dir: &Path, segment: u64, poh_config: &PoHConfig) -> Result<(BufWriter<File>, u64)> {
+    let mut header: Vec<u8> = Vec::new();
+    LedgerHeader::new(poh_config).write_to(&mut header)?;
+
+    let path: PathBuf = segment_path(dir, segment);
+    let file: File = OpenOptions::new()
+        .append(true)
+        .create_new(true)
+        .open(&path)
+        .with_context(|| format!("Failed to create ledger segment {}", path.display()))?;
+    let mut writer: BufWriter<File> = BufWriter::new(file);
+    writer.write_all(&header)?;
+    return Ok((writer, header.len() as u64));
+}
+
+// Scan the last segment and index its slots. A record cut short by the end of the file is a torn write and truncated,
+// any other damage is corruption and an error.
+//
+// # Returns
+// The end of the intact records and the last of them, `None` if not even the header is intact.
+fn recover(path: &Path, segment: u64, index: &mut BTreeMap<u64, SlotLocation>) -> Result<Option<(u64, Option<PoHRecord>)>> {
+    let bytes: Vec<u8> = fs::read(path)?;
+    let mut rest: &[u8] = &bytes;
+    match LedgerHeader::read_from(&mut rest) {
+        Ok(header) => header.validate().with_context(|| format!("Ledger segment {}", path.display()))?,
+        Err(e) if is_torn(&e) => return Ok(None),
+        Err(e) => return Err(e.context(format!("Ledger segment {}", path.display()))),
+    }
+
+    let mut end: u64 = bytes.len().saturating_sub(rest.len()) as u64;
+    let mut last: Option<PoHRecord> = None;
+    loop {
+        let record: PoHRecord = match PoHRecord::read_from(&mut rest) {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(e) if !is_torn(&e) => {
+                return Err(e.context(format!("Corrupted record at offset {} of ledger segment {}", end, path.display())));
+            }
+            Err(_) => {
+                eprintln!(
+                    "Warning: truncating torn write of {} bytes at the end of {}.",
+                    (bytes.len() as u64).saturating_sub(end),
+                    path.display()
+                );
+                let file: File = OpenOptions::new().write(true).open(path)?;
+                file.set_len(end)?;
+                file.sync_all()?;
+                break;
+            }
+        };
+        if last.as_ref().is_none_or(|last| last.slot_index != record.slot_index) {
+            index.insert(record.slot_index, SlotLocation { segment, offset: end });
+        }
+        end = bytes.len().saturating_sub(rest.len()) as u64;
+        last = Some(record);
+    }
+    return Ok(Some((end, last)));
+}
+
+// Whether reading stopped because the data ends early.
+fn is_torn(error: &Error) -> bool {
+    return error.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == ErrorKind::UnexpectedEof);
+}
+
+fn encode_entry(slot: u64, location: &SlotLocation) -> [u8; INDEX_ENTRY_SIZE] {
+    let mut entry: [u8; INDEX_ENTRY_SIZE] = [0u8; INDEX_ENTRY_SIZE];
+    entry[..8].copy_from_slice(&slot.to_le_bytes());
+    entry[8..16].copy_from_slice(&location.segment.to_le_bytes());
+    entry[16..].copy_from_slice(&location.offset.to_le_bytes());
+    return entry;
+}
+
+// Entries of the index file, a torn last entry is ignored.
+fn read_index(dir: &Path) -> Result<Vec<(u64, SlotLocation)>> {
+    let bytes: Vec<u8> = match fs::read(dir.join(INDEX_FILE)) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    return Ok(bytes
+        .chunks_exact(INDEX_ENTRY_SIZE)
+        .map(|entry| {
+            let field = |at: usize| -> u64 { u64::from_le_bytes(entry[at..at.saturating_add(8)].try_into().unwrap_or_default()) };
+            (
+                field(0),
+                SlotLocation {
+                    segment: field(8),
+                    offset: field(16),
+                },
+            )
+        })
+        .collect());
+}
+
+// Ticks of every event in the event index by hash, a torn last entry is ignored.
+fn read_events(dir: &Path) -> Result<HashMap<[u8; 32], Vec<u64>>> {
+    let bytes: Vec<u8> = match fs::read(dir.join(EVENTS_FILE)) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut events: HashMap<[u8; 32], Vec<u64>> = HashMap::new();
+    for entry in bytes.chunks_exact(EVENT_ENTRY_SIZE) {
+        let (hash, tick) = entry.split_at(32);
+        events.entry(hash.try_into()?).or_default().push(u64::from_le_bytes(tick.try_into()?));
+    }
+    return Ok(events);
+}
+
+// Drop event index entries at or before `tick`, returning the file open for appending.
+fn prune_events(dir: &Path, tick: u64) -> Result<File> {
+    let path: PathBuf = dir.join(EVENTS_FILE);
+    let bytes: Vec<u8> = fs::read(&path)?;
+    let kept: Vec<u8> = bytes
+        .chunks_exact(EVENT_ENTRY_SIZE)
+        .filter(|entry| u64::from_le_bytes(entry[32..].try_into().unwrap_or_default()) > tick)
+        .flatten()
+        .copied()
+        .collect();
+    fs::write(&path, &kept)?;
+    let file: File = OpenOptions::new().append(true).open(&path)?;
+    file.sync_all()?;
+    return Ok(file);
+}
+
+// Rewrite the index file from `index` and keep it open for appending.
+fn write_index(dir: &Path, index: &BTreeMap<u64, SlotLocation>) -> Result<File> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(index.len().saturating_mul(INDEX_ENTRY_SIZE));
+    for (slot, location) in index {
+        bytes.extend_from_slice(&encode_entry(*slot, location));
+    }
+    let path: PathBuf = dir.join(INDEX_FILE);
+    fs::write(&path, &bytes)?;
+    let file: File = OpenOptions::new().append(true).open(&path)?;
+    file.sync_all()?;
+    return Ok(file);
+}
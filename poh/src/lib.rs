@@ -1,3 +1,24 @@
+pub mod beacon;
+mod checkpoint;
+mod codec;
+mod compress;
+mod config;
 mod core;
+mod event;
+mod fork;
+mod identity;
+mod ledger;
+mod mapped;
+pub mod preflight;
+pub mod proof;
+mod receipt;
+mod recent;
+mod ring;
+mod sink;
+mod sparse;
+mod sqlite;
+mod status;
+mod store;
 pub mod thread;
 pub mod types;
+pub mod witness;
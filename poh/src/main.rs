@@ -1,29 +1,69 @@
 use std::env;
-use std::io::stdout;
+use std::fs::File;
+use std::io::{BufWriter, Write, stderr, stdout};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::sync::mpsc::Receiver;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use chrono::{DateTime, Utc};
 
-use poh::thread::{cleanup_threads, thread};
-use poh::types::PoHRecord;
+use poh::thread::{cleanup_threads, resume, service};
+use poh::types::{
+    CsvSink, Event, FsyncPolicy, Identity, JsonSink, Ledger, LedgerConfig, LedgerReader, PoHConfig, PoHRecord, PoHService, PoHStatus, Receipt, RecordEncoder, RecordSink,
+    RetentionPolicy, SlipPolicy, SqliteLedger, TerminalSink, Witness,
+};
+use poh::witness::serve;
 
-use lib::utils::args::{OutputType, parse_args, print_usage};
-use lib::utils::json::write;
+use lib::utils::args::{Args, Command, Output, parse_args, print_usage};
+use lib::utils::json::JsonLinesWriter;
 use lib::{DEFAULT_HASHES_PER_TICK, DEFAULT_SLOTS_PER_EPOCH, DEFAULT_TICKS_PER_SLOT};
+use thread::native::types::{Config, CoreAllocation};
 
+use anyhow::{Context, Result, anyhow, bail};
 use crossterm::{
     cursor::MoveTo,
     execute,
     style::{Color, Print, ResetColor, SetForegroundColor},
     terminal::{Clear, ClearType},
 };
+use hex::{decode, encode};
+
+// Default seed - 64 bytes of '0'.
+const SEED: [u8; 64] = [b'0'; 64];
 
 fn main() {
     // Process command line arguments.
     let args: Vec<String> = env::args().collect();
-    let output_type: OutputType = match parse_args(&args) {
-        Ok(out) => out,
+    let Args {
+        command,
+        outputs,
+        allow_slow_hardware,
+        slip_policy,
+        slip_threshold_us,
+        slip_tolerance_ticks,
+        poh_core,
+        poh_priority,
+        delivery_core,
+        ingest_core,
+        sparse,
+        commit_events,
+        identity,
+        witness,
+        witness_state,
+        witnesses,
+        witness_threshold,
+        ledger,
+        fsync,
+        compress,
+        retain,
+        digest,
+        receipt,
+        public_key,
+        trust_receipt_key,
+    } = match parse_args(&args) {
+        Ok(parsed) => parsed,
         Err(msg) => {
             eprintln!("{}", msg);
             print_usage();
@@ -31,10 +71,38 @@ fn main() {
         }
     };
 
-    execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0)).unwrap();
+    // Required options were checked while parsing the arguments.
+    let result: Option<Result<()>> = match (command, receipt.as_deref()) {
+        (Some(Command::Stamp), Some(receipt)) => Some(run_stamp(
+            Path::new(ledger.as_deref().unwrap_or_default()),
+            identity.as_deref().unwrap_or_default(),
+            digest.as_deref().unwrap_or_default(),
+            Path::new(receipt),
+            allow_slow_hardware,
+        )),
+        (Some(Command::VerifyReceipt), Some(receipt)) => Some(run_verify_receipt(Path::new(receipt), public_key.as_deref(), trust_receipt_key)),
+        (Some(Command::UpgradeReceipt), Some(receipt)) => Some(run_upgrade_receipt(Path::new(receipt), Path::new(ledger.as_deref().unwrap_or_default()))),
+        _ => None,
+    };
+    if let Some(result) = result {
+        if let Err(e) = result {
+            eprintln!("{:#}", e);
+        }
+        return;
+    }
+
+    if let Some(socket) = witness {
+        if let Err(e) = run_witness(&socket, identity.as_deref(), witness_state.as_deref()) {
+            eprintln!("{:#}", e);
+        }
+        return;
+    }
+
+    // The display moves to stderr when records are streamed to stdout.
+    let to_stderr: bool = outputs.iter().any(|output| matches!(output, Output::JsonLinesFile(path) if path == "-"));
+    let mut ui: Box<dyn Write> = display(to_stderr);
+    execute!(ui, Clear(ClearType::All), MoveTo(0, 0)).unwrap();
 
-    // Default seed - 64 bytes of '0'.
-    let seed: [u8; 64] = [b'0'; 64];
     // Generate enough ticks for 5 minutes (about 48,000 ticks at 6.25ms per tick).
     // 5 minutes = 300 seconds.
     // 300 seconds / (6.25 / 1000) seconds per tick = 48,000 ticks.
@@ -44,98 +112,352 @@ fn main() {
     let slots_approx: u64 = target_ticks / DEFAULT_TICKS_PER_SLOT;
     let duration_approx: f64 = target_ticks as f64 * 6.25 / 1000.0;
 
-    println!("Fixed Values:                    Testing Targets:");
-    println!("  | 1 Tick equals 12500 hashes     | Terminated at {} ticks ({} hashes)", target_ticks, hashes_approx);
-    println!("  | 1 Tick should be 6.25ms        | Approximate {} slots", slots_approx);
-    println!("  | 1 Slot is 64 Ticks             | Approximate duration is {} seconds\n  |", duration_approx);
+    writeln!(ui, "Fixed Values:                    Testing Targets:").unwrap();
+    writeln!(
+        ui,
+        "  | 1 Tick equals 12500 hashes     | Terminated at {} ticks ({} hashes)",
+        target_ticks, hashes_approx
+    )
+    .unwrap();
+    writeln!(ui, "  | 1 Tick should be 6.25ms        | Approximate {} slots", slots_approx).unwrap();
+    writeln!(ui, "  | 1 Slot is 64 Ticks             | Approximate duration is {} seconds\n  |", duration_approx).unwrap();
 
-    let start_time: Instant = Instant::now();
-    let rx: Receiver<PoHRecord> = thread(&seed, target_ticks).expect("Failed to spawn PoH thread.");
-
-    let mut records_received: u64 = 0;
-
-    // Performance tracking.
-    let mut last_update: Instant = Instant::now();
-    let mut last_tick_count: u64 = 0;
-
-    // Pre-allocate vector with expected capacity if writing to JSON.
-    let mut all_records: Vec<PoHRecord> = match output_type {
-        OutputType::JsonFile(_) => Vec::with_capacity(target_ticks as usize),
-        _ => Vec::new(),
+    let identity: Option<Identity> = match identity.map(|path| load_or_generate(Path::new(&path))).transpose() {
+        Ok(identity) => identity,
+        Err(e) => {
+            execute!(ui, SetForegroundColor(Color::Red), Print(format!("  |\n{:#}\n", e)), ResetColor).unwrap();
+            return;
+        }
     };
 
-    // Common progress display code.
-    let update_progress = |records: u64, tick_count: u64, last_time: Instant| -> (u64, Instant) {
-        let now: Instant = Instant::now();
-        let elapsed: f64 = now.duration_since(last_time).as_secs_f64();
-
-        if elapsed >= 1.0 {
-            let ticks: u64 = records.saturating_sub(tick_count);
-            let tick_per_second: f64 = ticks as f64 / elapsed;
-            let hash_per_second: f64 = tick_per_second * DEFAULT_HASHES_PER_TICK as f64;
-            let progress_percent: f64 = (records as f64 / target_ticks as f64) * 100.0;
-
-            execute!(
-                stdout(),
-                Clear(ClearType::CurrentLine),
-                MoveTo(0, 6),
-                SetForegroundColor(Color::Green),
-                Print(format!(
-                    "  | {:.1}% - {:.2} ticks/s - {:.3} MH/s\n",
-                    progress_percent,
-                    tick_per_second,
-                    hash_per_second / 1_000_000.0
-                )),
-                ResetColor
-            )
-            .unwrap();
-
-            return (records, now);
+    let start_time: Instant = Instant::now();
+    let defaults: PoHConfig = PoHConfig::default();
+    let config: PoHConfig = PoHConfig {
+        enforce_self_test: !allow_slow_hardware,
+        // Already validated while parsing the arguments.
+        slip_policy: match slip_policy.as_deref() {
+            Some("marker") => SlipPolicy::Marker,
+            Some("abort") => SlipPolicy::Abort,
+            _ => SlipPolicy::Warn,
+        },
+        slip_threshold_us: slip_threshold_us.unwrap_or(defaults.slip_threshold_us),
+        slip_tolerance_ticks: slip_tolerance_ticks.unwrap_or(defaults.slip_tolerance_ticks),
+        poh_thread: Config {
+            priority: poh_priority,
+            ..thread_config(poh_core)
+        },
+        delivery_thread: thread_config(delivery_core),
+        ingest_thread: thread_config(ingest_core),
+        sparse,
+        commit_events: commit_events.is_some(),
+        event_store: commit_events.map(PathBuf::from),
+        identity: identity.clone(),
+        witness_threshold: witness_threshold.unwrap_or(witnesses.len().div_ceil(2)),
+        witnesses: witnesses.iter().map(PathBuf::from).collect(),
+        ..Default::default()
+    };
+    // Outputs are opened up front so a bad path fails before generation starts.
+    let mut sinks: Vec<(Option<String>, Box<dyn RecordSink>)> = Vec::new();
+    for output in &outputs {
+        match open_sink(output, &config) {
+            Ok(Some(sink)) => sinks.push((Some(output.to_string()), sink)),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Error opening {}: {:#}", output, e);
+                return;
+            }
+        }
+    }
+    let ledger: Option<Ledger> = match ledger
+        .map(|dir| open_ledger(Path::new(&dir), fsync.as_deref(), compress, retain.as_deref(), &config))
+        .transpose()
+    {
+        Ok(ledger) => ledger,
+        Err(e) => {
+            execute!(ui, SetForegroundColor(Color::Red), Print(format!("  |\n{:#}\n", e)), ResetColor).unwrap();
+            return;
+        }
+    };
+    // A ledger with records continues the chain after its last durable record.
+    let last: Option<PoHRecord> = ledger.as_ref().and_then(|ledger| ledger.last.clone());
+    let resumed_ticks: u64 = last.as_ref().map_or(0, |record| record.tick_index.saturating_add(1));
+    let started: Result<PoHService> = match &last {
+        Some(record) => resume(record, target_ticks, config),
+        None => service(&SEED, target_ticks, config),
+    };
+    let poh_service: PoHService = match started {
+        Ok(poh_service) => poh_service,
+        Err(e) => {
+            execute!(ui, SetForegroundColor(Color::Red), Print(format!("  |\n{}\n", e)), ResetColor).unwrap();
+            writeln!(ui, "Use --allow-slow-hardware to start anyway.").unwrap();
+            cleanup_threads();
+            return;
         }
-        return (tick_count, last_time);
     };
+    let rx: Receiver<PoHRecord> = poh_service.receiver;
+    let status: Arc<PoHStatus> = poh_service.status;
 
-    execute!(stdout(), SetForegroundColor(Color::Green)).unwrap();
+    match &poh_service.self_test {
+        Some(report) => writeln!(ui, "  | {}", report).unwrap(),
+        None => writeln!(ui, "  | Hash-rate self-test skipped").unwrap(),
+    }
+    if let Some(identity) = &identity {
+        writeln!(ui, "  | Identity: {}", identity).unwrap();
+    }
+    if let Some(ledger) = &ledger {
+        writeln!(ui, "  | Ledger: {}, resuming after {} ticks", ledger.dir.display(), resumed_ticks).unwrap();
+    }
+    // Live output starts below the header, which is one line longer with an identity or a ledger.
+    let top: u16 = 8u16.saturating_add(u16::from(identity.is_some())).saturating_add(u16::from(ledger.is_some()));
+    let threads: Vec<String> = poh_service.threads.iter().map(|policy| policy.to_string()).collect();
+    writeln!(ui, "  | {}", threads.join(" | ")).unwrap();
+
+    if let Some(ledger) = ledger {
+        sinks.push((Some(format!("ledger {}", ledger.dir.display())), Box::new(ledger)));
+    }
+    if outputs.iter().any(|output| matches!(output, Output::Terminal)) {
+        let terminal: TerminalSink = TerminalSink::new(display(to_stderr), top, target_ticks, status.clone(), resumed_ticks);
+        sinks.insert(0, (None, Box::new(terminal)));
+    }
+
+    let mut records_received: u64 = 0;
+    // Sparse records cover several ticks, progress follows the tick index.
+    let mut ticks_received: u64 = 0;
+
+    execute!(ui, SetForegroundColor(Color::Green)).unwrap();
 
     let counting_since: u64 = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs();
     let datetime: DateTime<Utc> = DateTime::<Utc>::from_timestamp(counting_since as i64, 0).expect("Invalid timestamp");
     let formatted_time: String = datetime.format("%Y-%m-%d %H:%M:%S UTC").to_string();
 
     // Standardized output message for both modes.
-    println!("Proof of History (PoH) has been counting since {} (Unix epoch time)", formatted_time);
-    execute!(stdout(), ResetColor).unwrap();
+    writeln!(ui, "Proof of History (PoH) has been counting since {} (Unix epoch time)", formatted_time).unwrap();
+    execute!(ui, ResetColor).unwrap();
     // Process incoming records.
     while let Ok(record) = rx.recv() {
-        if let OutputType::JsonFile(_) = &output_type {
-            all_records.push(record.clone())
-        }
-        // Show progress.
-        let result: (u64, Instant) = update_progress(records_received, last_tick_count, last_update);
-        last_tick_count = result.0;
-        last_update = result.1;
-        execute!(stdout(), MoveTo(0, 7), Clear(ClearType::CurrentLine), Print(format!("  |\n{}\n", record))).unwrap();
+        // A failing output is dropped, the others keep going.
+        sinks.retain_mut(|(name, sink)| match sink.write_record(&record) {
+            Ok(_) => true,
+            Err(e) => {
+                eprintln!("Error writing to {}, dropping it: {:#}", name.as_deref().unwrap_or("terminal"), e);
+                false
+            }
+        });
         records_received = records_received.saturating_add(1);
+        ticks_received = record.tick_index.saturating_add(1).saturating_sub(resumed_ticks);
     }
-    // Save to JSON if needed.
-    if let OutputType::JsonFile(filename) = output_type {
-        match write(&all_records, &filename) {
-            Ok(_) => println!("Successfully saved {} records to file {}.", records_received, filename),
-            Err(e) => eprintln!("Error saving file: {}", e),
+    for (name, sink) in sinks.iter_mut() {
+        match (sink.finish(), name) {
+            (Ok(_), Some(name)) => writeln!(ui, "Successfully saved {} records to {}.", records_received, name).unwrap(),
+            (Ok(_), None) => {}
+            (Err(e), name) => eprintln!("Error saving to {}: {:#}", name.as_deref().unwrap_or("terminal"), e),
         }
     }
 
     let duration: Duration = start_time.elapsed();
     let seconds: f64 = duration.as_secs_f64();
-    let ticks_per_second: f64 = records_received as f64 / seconds;
+    let ticks_per_second: f64 = ticks_received as f64 / seconds;
     let ticks_per_epoch: u64 = DEFAULT_SLOTS_PER_EPOCH * DEFAULT_TICKS_PER_SLOT;
 
-    execute!(stdout(), SetForegroundColor(Color::Cyan), MoveTo(0, 9),).unwrap();
-    println!("  |\nFinished:");
-    println!("  | Received {} records", records_received);
-    println!("  | {} slots", records_received / DEFAULT_TICKS_PER_SLOT);
-    println!("  | Elapsed time: {:.2} seconds", seconds);
-    println!("  | Average speed: {:.2} ticks/s", ticks_per_second);
-    println!("  | For reference: 1 epoch = {} slots = {} ticks", DEFAULT_SLOTS_PER_EPOCH, ticks_per_epoch);
-    execute!(stdout(), ResetColor).unwrap();
+    execute!(ui, SetForegroundColor(Color::Cyan), MoveTo(0, top.saturating_add(3)),).unwrap();
+    writeln!(ui, "  |\nFinished:").unwrap();
+    writeln!(ui, "  | Received {} records", records_received).unwrap();
+    writeln!(ui, "  | {} ticks, {} slots", ticks_received, ticks_received / DEFAULT_TICKS_PER_SLOT).unwrap();
+    writeln!(ui, "  | Elapsed time: {:.2} seconds", seconds).unwrap();
+    writeln!(ui, "  | Average speed: {:.2} ticks/s", ticks_per_second).unwrap();
+    writeln!(
+        ui,
+        "  | Schedule error: {:.2}ms cumulative, {:.2}ms worst, {} late ticks, {} slips",
+        status.schedule_error_us() as f64 / 1_000.0,
+        status.max_lateness_us() as f64 / 1_000.0,
+        status.late_ticks(),
+        status.slips()
+    )
+    .unwrap();
+    if status.is_aborted() {
+        writeln!(ui, "  | Aborted: sustained schedule slip").unwrap();
+    }
+    if !witnesses.is_empty() {
+        writeln!(ui, "  | Finalised: {} slots co-signed by the witness threshold", status.finalised_slots()).unwrap();
+    }
+    for summary in sinks.iter().filter_map(|(_, sink)| sink.summary()) {
+        writeln!(ui, "  | {}", summary).unwrap();
+    }
+    writeln!(ui, "  | For reference: 1 epoch = {} slots = {} ticks", DEFAULT_SLOTS_PER_EPOCH, ticks_per_epoch).unwrap();
+    execute!(ui, ResetColor).unwrap();
     cleanup_threads();
 }
+
+// Serve witness requests on `socket` until the process is stopped.
+fn run_witness(socket: &str, identity: Option<&str>, state: Option<&str>) -> Result<()> {
+    let path: &str = identity.ok_or_else(|| anyhow!("A witness needs an --identity=FILE."))?;
+    let state: &str = state.ok_or_else(|| anyhow!("A witness needs a --witness-state=FILE to remember what it co-signed."))?;
+    let identity: Identity = load_or_generate(Path::new(path))?;
+    let witness: Witness = Witness::open(identity, Path::new(state))?;
+    let listener: UnixListener = UnixListener::bind(socket).with_context(|| format!("Failed to listen on {}", socket))?;
+
+    println!("Witness {} listening on {}", witness.identity, socket);
+    return serve(listener, witness);
+}
+
+// Record `digest` in the ledger in `dir`, continuing its chain until the slot is signed, and save a receipt for it.
+fn run_stamp(dir: &Path, identity: &str, digest: &str, receipt: &Path, allow_slow_hardware: bool) -> Result<()> {
+    let identity: Identity = load_or_generate(Path::new(identity))?;
+    let digest: [u8; 32] = parse_hash(digest)?;
+    let config: PoHConfig = PoHConfig {
+        enforce_self_test: !allow_slow_hardware,
+        identity: Some(identity.clone()),
+        ..Default::default()
+    };
+    let mut ledger: Ledger = open_ledger(dir, None, false, None, &config)?;
+
+    // Two slots always reach the end of the slot the digest lands in.
+    let ticks: u64 = DEFAULT_TICKS_PER_SLOT.saturating_mul(2);
+    let poh_service: PoHService = match &ledger.last {
+        Some(record) => resume(record, ticks, config)?,
+        None => service(&SEED, ticks, config)?,
+    };
+    poh_service.events.send(Event::Document { hash: digest })?;
+    while let Ok(record) = poh_service.receiver.recv() {
+        ledger.append(&record)?;
+    }
+    ledger.sync()?;
+    cleanup_threads();
+
+    let stamped: Receipt = Receipt::stamp(&LedgerReader::open(dir)?, digest, identity.public_key())?;
+    stamped.save(receipt)?;
+    println!(
+        "Stamped 0x{} at tick {}, slot {} signed by {}.",
+        encode(digest),
+        stamped.proof.record.tick_index,
+        stamped.anchor().slot_index,
+        identity
+    );
+    println!("Receipt saved to {}.", receipt.display());
+    return Ok(());
+}
+
+// Check the receipt at `path` against `public_key`, or the key it names only when that is explicitly `trusted`.
+fn run_verify_receipt(path: &Path, public_key: Option<&str>, trusted: bool) -> Result<()> {
+    let receipt: Receipt = Receipt::load(path)?;
+    let public_key: [u8; 32] = match (public_key, trusted) {
+        (Some(public_key), _) => parse_hash(public_key)?,
+        (None, true) => {
+            println!("Trusting the generator key 0x{} named by the receipt.", encode(receipt.public_key));
+            receipt.public_key
+        }
+        (None, false) => bail!("A receipt can only be checked against a known generator key, pass --public-key."),
+    };
+    let anchor: &PoHRecord = receipt.verify(&public_key)?;
+    println!(
+        "Valid receipt: 0x{} recorded at tick {}, {}ms into the chain.",
+        encode(receipt.digest),
+        receipt.proof.record.tick_index,
+        receipt.proof.record.timestamp_ms
+    );
+    println!(
+        "Anchored to the signed hash of slot {} at tick {} with {} witness co-signatures, {} upgrades.",
+        anchor.slot_index,
+        anchor.tick_index,
+        anchor.witnesses.len(),
+        receipt.upgrades.len()
+    );
+    return Ok(());
+}
+
+// Extend the receipt at `path` to the newest signed slot hash of the ledger in `dir`.
+fn run_upgrade_receipt(path: &Path, dir: &Path) -> Result<()> {
+    let mut receipt: Receipt = Receipt::load(path)?;
+    if !receipt.upgrade(&LedgerReader::open(dir)?)? {
+        println!("Receipt already reaches the newest signed slot hash in {}.", dir.display());
+        return Ok(());
+    }
+    // Only checks the new segments are consistent before saving, whom to trust is decided when the receipt is verified.
+    receipt.verify(&receipt.public_key)?;
+    receipt.save(path)?;
+    println!("Receipt upgraded to the signed hash of slot {}.", receipt.anchor().slot_index);
+    return Ok(());
+}
+
+// Decode a 32-byte digest or key, already checked to be 64 hex characters.
+fn parse_hash(value: &str) -> Result<[u8; 32]> {
+    let bytes: Vec<u8> = decode(value)?;
+    return bytes.try_into().map_err(|_| anyhow!("Expected 32 bytes: {}", value));
+}
+
+// Open the file or socket behind `output`, the terminal view is set up once the service runs.
+fn open_sink(output: &Output, config: &PoHConfig) -> Result<Option<Box<dyn RecordSink>>> {
+    return Ok(match output {
+        Output::Terminal => None,
+        Output::JsonFile(filename) => Some(Box::new(JsonSink::new(filename))),
+        // JSON Lines are written as records arrive, so memory use stays flat.
+        Output::JsonLinesFile(path) => Some(Box::new(JsonLinesWriter::create(path)?)),
+        // Binary output is streamed as records arrive, the header carries the chain parameters.
+        Output::BinaryFile(filename) => Some(Box::new(RecordEncoder::new(BufWriter::new(File::create(filename)?), config)?)),
+        Output::CsvFile(path) => Some(Box::new(CsvSink::create(path)?)),
+        Output::Socket(path) => {
+            let stream: UnixStream = UnixStream::connect(path).with_context(|| format!("Failed to connect to {}", path))?;
+            Some(Box::new(JsonLinesWriter::new(stream)))
+        }
+        Output::Sqlite(path) => {
+            let mut database: SqliteLedger = SqliteLedger::open(Path::new(path))?;
+            database.start_run(config)?;
+            Some(Box::new(database))
+        }
+    });
+}
+
+// Terminal output, on stderr when stdout carries records.
+fn display(to_stderr: bool) -> Box<dyn Write> {
+    return match to_stderr {
+        true => Box::new(stderr()),
+        false => Box::new(stdout()),
+    };
+}
+
+// Open the ledger in `dir` with the `--fsync` policy, compressing closed segments with `--compress` and pruning them with `--retain`.
+fn open_ledger(dir: &Path, fsync: Option<&str>, compress: bool, retain: Option<&str>, config: &PoHConfig) -> Result<Ledger> {
+    let fsync: FsyncPolicy = match fsync {
+        Some("never") => FsyncPolicy::Never,
+        Some("record") => FsyncPolicy::Record,
+        _ => FsyncPolicy::Slot,
+    };
+    // Already validated while parsing the arguments.
+    let retention: RetentionPolicy = match retain.and_then(|policy| policy.split_once(':')) {
+        Some(("epochs", value)) => RetentionPolicy::Epochs(value.parse()?),
+        Some(("bytes", value)) => RetentionPolicy::Bytes(value.parse()?),
+        Some(("after", value)) => RetentionPolicy::After(value.parse()?),
+        _ => RetentionPolicy::KeepAll,
+    };
+    return Ledger::open(
+        dir,
+        LedgerConfig {
+            fsync,
+            compress,
+            retention,
+            ..Default::default()
+        },
+        config,
+    );
+}
+
+// Load the keypair at `path`, or create it on first use.
+fn load_or_generate(path: &Path) -> Result<Identity> {
+    if path.exists() {
+        return Identity::load(path);
+    }
+    let identity: Identity = Identity::generate()?;
+    identity.save(path)?;
+    return Ok(identity);
+}
+
+// Dedicate a single core when one is requested, otherwise leave placement to the OS.
+fn thread_config(core: Option<usize>) -> Config {
+    return match core {
+        Some(core) => Config {
+            core_allocation: CoreAllocation::DedicatedCoreSet { min: core, max: core },
+            ..Default::default()
+        },
+        None => Config::default(),
+    };
+}
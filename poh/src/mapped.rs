@@ -0,0 +1,94 @@
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+use std::thread::{self, ScopedJoinHandle};
+
+use crate::types::{LedgerHeader, MappedLedger, PoH, RecordView, RecordViews};
+
+use anyhow::{Context, Result, anyhow};
+use memmap2::Mmap;
+
+impl MappedLedger {
+    /// Map the binary ledger at `path`, e.g. a `--bin` output or a raw ledger segment, and check its header.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file: File = File::open(path).with_context(|| format!("Failed to open ledger {}", path.display()))?;
+        // SAFETY: the map is read-only and ledgers are only ever appended to, so mapped bytes do not change underneath.
+        // Recovery truncates a torn tail, so a segment still being written must not be mapped.
+        let map: Mmap = unsafe { Mmap::map(&file)? };
+        let mut rest: &[u8] = &map;
+        let header: LedgerHeader = LedgerHeader::read_from(&mut rest).with_context(|| format!("Ledger {}", path.display()))?;
+        header.validate()?;
+        let start: usize = map.len().saturating_sub(rest.len());
+
+        return Ok(Self { map, header, start });
+    }
+
+    pub fn records(&self) -> RecordViews<'_> {
+        return RecordViews::new(&self.map[self.start..]);
+    }
+
+    /// Verify the chain in a single pass over the mapped records.
+    pub fn verify(&self) -> Result<bool> {
+        return PoH::verify_views(self.records());
+    }
+
+    /// Verify the chain on `threads` threads, each taking a run of records about the same size.
+    pub fn verify_parallel(&self, threads: usize) -> Result<bool> {
+        let bytes: &[u8] = &self.map[self.start..];
+        let chunks: Vec<Range<usize>> = split(bytes, threads)?;
+        return thread::scope(|scope| {
+            let handles: Vec<ScopedJoinHandle<Result<bool>>> = chunks
+                .into_iter()
+                .map(|range| {
+                    let chunk: &[u8] = &bytes[range];
+                    scope.spawn(move || PoH::verify_views(RecordViews::new(chunk)))
+                })
+                .collect();
+            let mut valid: bool = true;
+            for handle in handles {
+                valid &= handle.join().map_err(|_| anyhow!("Verifier thread panicked."))??;
+            }
+            return Ok(valid);
+        });
+    }
+}
+
+impl<'a> RecordViews<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        return Self { rest: bytes };
+    }
+}
+
+impl<'a> Iterator for RecordViews<'a> {
+    type Item = Result<RecordView<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let view: Result<Option<RecordView<'a>>> = RecordView::parse(&mut self.rest);
+        // Nothing after a corrupt record can be trusted to start at a record boundary.
+        if view.is_err() {
+            self.rest = &[];
+        }
+        return view.transpose();
+    }
+}
+
+// Cut `bytes` at record boundaries into about `threads` runs, each starting with the last record of the one before so every link is checked.
+fn split(bytes: &[u8], threads: usize) -> Result<Vec<Range<usize>>> {
+    let target: usize = bytes.len().div_ceil(threads.max(1));
+    let mut chunks: Vec<Range<usize>> = Vec::new();
+    let mut start: usize = 0;
+    let mut rest: &[u8] = bytes;
+    loop {
+        let offset: usize = bytes.len().saturating_sub(rest.len());
+        if RecordView::parse(&mut rest)?.is_none() {
+            break;
+        }
+        let end: usize = bytes.len().saturating_sub(rest.len());
+        if offset > start && end.saturating_sub(start) >= target && !rest.is_empty() {
+            chunks.push(start..end);
+            start = offset;
+        }
+    }
+    chunks.push(start..bytes.len());
+    return Ok(chunks);
+}
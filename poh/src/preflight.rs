@@ -0,0 +1,56 @@
+use std::fmt::{Display, Formatter, Result};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::types::SelfTestReport;
+
+use lib::utils::hash;
+use lib::{DEFAULT_HASHES_PER_TICK, DEFAULT_US_PER_TICK};
+
+impl SelfTestReport {
+    /// Check if the measured headroom is at least `min_headroom`.
+    pub fn passes(&self, min_headroom: f64) -> bool {
+        return self.headroom >= min_headroom;
+    }
+}
+
+impl Display for SelfTestReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        return write!(
+            f,
+            "{} self-test: {:.3} MH/s measured, {:.3} MH/s required, {:+.1}% headroom",
+            self.algorithm,
+            self.hashes_per_second / 1_000_000.0,
+            self.required_hashes_per_second / 1_000_000.0,
+            self.headroom * 100.0
+        );
+    }
+}
+
+/// Measure the hash rate of the current thread with the configured algorithm.
+/// Hashes whole ticks until `window_ms` has elapsed, so call it from the thread that will run the PoH loop.
+pub fn measure_hash_rate(window_ms: u64) -> SelfTestReport {
+    let window: Duration = Duration::from_millis(window_ms);
+    let mut current_hash: [u8; 32] = [0u8; 32];
+    let mut hashes: u64 = 0;
+
+    let start: Instant = Instant::now();
+    while start.elapsed() < window || hashes == 0 {
+        current_hash = hash::extend_hash_chain(&current_hash, DEFAULT_HASHES_PER_TICK);
+        hashes = hashes.saturating_add(DEFAULT_HASHES_PER_TICK);
+    }
+    let elapsed: Duration = start.elapsed();
+    black_box(current_hash);
+
+    let hashes_per_second: f64 = hashes as f64 / elapsed.as_secs_f64();
+    let required_hashes_per_second: f64 = DEFAULT_HASHES_PER_TICK as f64 * 1_000_000.0 / DEFAULT_US_PER_TICK as f64;
+
+    return SelfTestReport {
+        algorithm: hash::get_algorithm_name().to_string(),
+        hashes,
+        elapsed_us: elapsed.as_micros() as u64,
+        hashes_per_second,
+        required_hashes_per_second,
+        headroom: hashes_per_second / required_hashes_per_second - 1.0,
+    };
+}
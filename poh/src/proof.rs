@@ -0,0 +1,290 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::types::{Event, InclusionProof, LedgerEntry, LedgerReader, OrderingProof, PoHRecord, ProofStep};
+
+use lib::utils::hash;
+use lib::{DEFAULT_HASHES_PER_TICK, DEFAULT_TICKS_PER_SLOT};
+
+use anyhow::{Context, Result, anyhow, bail};
+use hex::encode;
+use ring::signature::{ED25519, UnparsedPublicKey};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Build an inclusion proof for the event with `event_hash`, see [`PoHRecord::event_hash`].
+/// The slot the event was recorded in must be closed and signed.
+pub fn prove(reader: &LedgerReader, event_hash: &[u8; 32]) -> Result<InclusionProof> {
+    let entry: LedgerEntry = reader
+        .event(event_hash)?
+        .ok_or_else(|| anyhow!("Event 0x{} is not in the ledger.", encode(event_hash)))?;
+    let Some((prev_tick, prev_hash)) = entry.anchor else {
+        bail!("Event at tick {} has no record before it to anchor a proof.", entry.record.tick_index);
+    };
+
+    let end: u64 = entry.record.slot_index.saturating_add(1).saturating_mul(DEFAULT_TICKS_PER_SLOT);
+    let mut records: Vec<PoHRecord> = Vec::new();
+    for found in reader.ticks(entry.record.tick_index.saturating_add(1)..end)? {
+        records.push(found?.record);
+    }
+    let slot_end: Option<PoHRecord> = match entry.record.is_slot_end() {
+        true => None,
+        false => Some(
+            records
+                .pop()
+                .filter(PoHRecord::is_slot_end)
+                .ok_or_else(|| anyhow!("Slot {} is not closed yet.", entry.record.slot_index))?,
+        ),
+    };
+    if slot_end.as_ref().unwrap_or(&entry.record).signature.is_none() {
+        bail!("Slot {} is not signed.", entry.record.slot_index);
+    }
+
+    return Ok(InclusionProof {
+        algorithm: hash::get_current_algorithm(),
+        prev_tick,
+        prev_hash,
+        record: entry.record,
+        steps: ProofStep::from_records(&records),
+        slot_end,
+    });
+}
+
+/// Check an inclusion proof on its own: the event record extends the previous hash, the steps lead to the slot end
+/// and the slot hash is signed by `public_key`.
+pub fn verify_proof(proof: &InclusionProof, public_key: &[u8; 32]) -> Result<()> {
+    check_algorithm(proof.algorithm)?;
+    if !proof.record.has_event() {
+        bail!("Proof record at tick {} carries no event.", proof.record.tick_index);
+    }
+    if !proof.record.follows(&proof.prev_hash, proof.prev_tick) {
+        bail!("Event record does not extend the previous hash.");
+    }
+
+    let slot_end: &PoHRecord = match &proof.slot_end {
+        Some(slot_end) => {
+            if !ProofStep::connects(&proof.steps, &proof.record, slot_end)? {
+                bail!("Proof steps do not lead to the slot end.");
+            }
+            slot_end
+        }
+        None if proof.steps.is_empty() => &proof.record,
+        None => bail!("Proof steps lead nowhere without a slot end."),
+    };
+    if slot_end.slot_index != proof.record.slot_index || !slot_end.is_slot_end() {
+        bail!("Proof does not end on the last tick of slot {}.", proof.record.slot_index);
+    }
+
+    let signature: &[u8; 64] = slot_end.signature.as_ref().ok_or_else(|| anyhow!("Slot {} is not signed.", slot_end.slot_index))?;
+    if UnparsedPublicKey::new(&ED25519, public_key).verify(&slot_end.slot_message(), signature).is_err() {
+        bail!("Slot {} signature does not match the public key.", slot_end.slot_index);
+    }
+    return Ok(());
+}
+
+/// Prove that `first` was recorded before `second`, both records of the ledger behind `reader`, and the least time between them
+/// for a generator hashing at most `max_hash_rate` hashes per second.
+pub fn prove_order(reader: &LedgerReader, first: &PoHRecord, second: &PoHRecord, max_hash_rate: u64) -> Result<OrderingProof> {
+    if second.tick_index <= first.tick_index {
+        bail!("Record at tick {} does not come after tick {}.", second.tick_index, first.tick_index);
+    }
+    if max_hash_rate == 0 {
+        bail!("max_hash_rate must be greater than 0.");
+    }
+
+    let mut anchor: Option<(u64, [u8; 32])> = None;
+    let mut steps: Vec<ProofStep> = Vec::new();
+    let mut end: Option<PoHRecord> = None;
+    for found in reader.ticks(first.tick_index..second.tick_index.saturating_add(1))? {
+        let found: LedgerEntry = found?;
+        if found.record.tick_index == first.tick_index {
+            if found.record.hash != first.hash {
+                bail!("Record at tick {} is not in the ledger.", first.tick_index);
+            }
+            anchor = found.anchor;
+        } else if found.record.tick_index == second.tick_index {
+            end = Some(found.record);
+        } else {
+            ProofStep::append(&mut steps, &found.record);
+        }
+    }
+    let Some((prev_tick, prev_hash)) = anchor else {
+        bail!("Record at tick {} has no record before it to anchor a proof.", first.tick_index);
+    };
+    if end.is_none_or(|end| end.hash != second.hash) {
+        bail!("Record at tick {} is not in the ledger.", second.tick_index);
+    }
+
+    let mut proof: OrderingProof = OrderingProof {
+        algorithm: hash::get_current_algorithm(),
+        prev_tick,
+        prev_hash,
+        first: first.clone(),
+        steps,
+        second: second.clone(),
+        max_hash_rate,
+        min_elapsed_us: 0,
+    };
+    proof.min_elapsed_us = proof.min_elapsed().as_micros().try_into().unwrap_or(u64::MAX);
+    return Ok(proof);
+}
+
+/// Check an ordering proof on its own: `second` extends `first` through the steps and the claimed time is covered by the hashes between them
+/// at the declared hash rate. Returns the minimum elapsed time the proof supports.
+/// The declared `max_hash_rate` is taken as given, check it against what the generator hardware can actually reach.
+pub fn verify_order(proof: &OrderingProof) -> Result<Duration> {
+    check_algorithm(proof.algorithm)?;
+    if proof.max_hash_rate == 0 {
+        bail!("Proof declares no hash rate.");
+    }
+    if !proof.first.follows(&proof.prev_hash, proof.prev_tick) {
+        bail!("First record does not extend the previous hash.");
+    }
+    if !ProofStep::connects(&proof.steps, &proof.first, &proof.second)? {
+        bail!("Proof steps do not lead from tick {} to tick {}.", proof.first.tick_index, proof.second.tick_index);
+    }
+
+    let min_elapsed: Duration = proof.min_elapsed();
+    if u128::from(proof.min_elapsed_us) > min_elapsed.as_micros() {
+        bail!(
+            "Proof claims {}us between the records, {} hashes at {} hashes/s only cover {}us.",
+            proof.min_elapsed_us,
+            proof.hashes(),
+            proof.max_hash_rate,
+            min_elapsed.as_micros()
+        );
+    }
+    return Ok(min_elapsed);
+}
+
+// Check that the chain was generated with the hash algorithm of this build.
+fn check_algorithm(algorithm: u8) -> Result<()> {
+    if algorithm != hash::get_current_algorithm() {
+        bail!("Proof uses hash algorithm {}, this build uses {}.", algorithm, hash::get_current_algorithm());
+    }
+    return Ok(());
+}
+
+impl InclusionProof {
+    /// Total hashes from the event to the signed slot hash.
+    pub fn hashes(&self) -> u64 {
+        let steps: u64 = self.steps.iter().fold(0, |total: u64, step| total.saturating_add(step.num_hashes));
+        return steps.saturating_add(self.slot_end.as_ref().map_or(0, |slot_end| slot_end.num_hashes));
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        return save_json(self, path);
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        return load_json(path);
+    }
+}
+
+impl OrderingProof {
+    /// Hashes from the first record to the second.
+    pub fn hashes(&self) -> u64 {
+        let steps: u64 = self.steps.iter().fold(0, |total: u64, step| total.saturating_add(step.num_hashes));
+        return steps.saturating_add(self.second.num_hashes);
+    }
+
+    /// Least wall time the hashes between the records take at `max_hash_rate`.
+    pub fn min_elapsed(&self) -> Duration {
+        let nanos: u128 = u128::from(self.hashes())
+            .saturating_mul(1_000_000_000)
+            .checked_div(u128::from(self.max_hash_rate))
+            .unwrap_or(u128::MAX);
+        return Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX));
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        return save_json(self, path);
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        return load_json(path);
+    }
+}
+
+impl ProofStep {
+    /// Steps standing for consecutive `records`, empty ticks are folded into the step before them.
+    pub fn from_records(records: &[PoHRecord]) -> Vec<Self> {
+        let mut steps: Vec<Self> = Vec::new();
+        for record in records {
+            Self::append(&mut steps, record);
+        }
+        return steps;
+    }
+
+    /// Extend `steps` by the record following them.
+    pub fn append(steps: &mut Vec<Self>, record: &PoHRecord) {
+        // Mixed in the same way as the generator does, the commitment in place of a committed event.
+        let data: Option<Vec<u8>> = record
+            .commitment
+            .map(|commitment| commitment.to_vec())
+            .or_else(|| record.event.as_ref().map(Event::encode));
+        match (data, steps.last_mut()) {
+            (None, Some(step)) => step.num_hashes = step.num_hashes.saturating_add(record.num_hashes),
+            (data, _) => steps.push(Self {
+                data,
+                num_hashes: record.num_hashes,
+            }),
+        }
+    }
+
+    /// Hash at the end of `steps` starting from `hash`, which must span exactly `ticks`.
+    /// The span is checked before anything is hashed, so steps claiming a huge hash count are refused up front.
+    pub fn replay(steps: &[Self], hash: &[u8; 32], ticks: u64) -> Result<[u8; 32]> {
+        let hashes: u64 = steps
+            .iter()
+            .try_fold(0u64, |total, step| total.checked_add(step.num_hashes))
+            .ok_or_else(|| anyhow!("Proof hash count overflows."))?;
+        if hashes % DEFAULT_HASHES_PER_TICK != 0 {
+            bail!("Proof steps of {} hashes do not span whole ticks.", hashes);
+        }
+        if hashes / DEFAULT_HASHES_PER_TICK != ticks {
+            bail!("Proof steps span {} ticks, the records are {} apart.", hashes / DEFAULT_HASHES_PER_TICK, ticks);
+        }
+
+        let mut current: [u8; 32] = *hash;
+        for step in steps {
+            if let Some(data) = &step.data {
+                current = hash::hash_with_data(&current, data);
+            }
+            current = hash::extend_hash_chain(&current, step.num_hashes);
+        }
+        return Ok(current);
+    }
+
+    /// Check that `end` extends the hash `steps` lead to from `start`.
+    pub fn connects(steps: &[Self], start: &PoHRecord, end: &PoHRecord) -> Result<bool> {
+        // The steps cover every tick between the two records except those of `end` itself.
+        let Some(ticks) = end
+            .tick_index
+            .checked_sub(start.tick_index)
+            .and_then(|span| span.checked_sub(end.num_hashes / DEFAULT_HASHES_PER_TICK))
+        else {
+            return Ok(false);
+        };
+        let hash: [u8; 32] = Self::replay(steps, &start.hash, ticks)?;
+        return Ok(end.follows(&hash, start.tick_index.saturating_add(ticks)));
+    }
+}
+
+// Write a proof as pretty JSON, aside first so a crash never leaves a partial file.
+fn save_json<T: Serialize>(proof: &T, path: &Path) -> Result<()> {
+    let temporary: PathBuf = path.with_extension("tmp");
+    let mut file: File = File::create(&temporary)?;
+    file.write_all(&serde_json::to_vec_pretty(proof)?)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)?;
+    return Ok(());
+}
+
+// Read a proof written by `save_json`.
+fn load_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let bytes: Vec<u8> = fs::read(path).with_context(|| format!("Failed to read proof {}", path.display()))?;
+    return serde_json::from_slice(&bytes).with_context(|| format!("Corrupted proof {}", path.display()));
+}
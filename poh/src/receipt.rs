@@ -0,0 +1,116 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::proof::{prove, verify_proof};
+use crate::types::{Event, InclusionProof, LedgerEntry, LedgerReader, PoHRecord, ProofStep, Receipt, ReceiptSegment};
+
+use anyhow::{Context, Result, bail};
+use ring::signature::{ED25519, UnparsedPublicKey};
+
+// Receipts saved under this extension are written as JSON, any other as binary.
+const JSON_EXTENSION: &str = "json";
+
+impl Receipt {
+    /// Receipt for `digest`, already recorded as an [`Event::Document`] in the ledger behind `reader` and signed by `public_key`.
+    pub fn stamp(reader: &LedgerReader, digest: [u8; 32], public_key: [u8; 32]) -> Result<Self> {
+        let proof: InclusionProof = prove(reader, &Event::Document { hash: digest }.commitment())?;
+        return Ok(Self::new(digest, reader.header()?, public_key, proof));
+    }
+
+    /// Signed slot end the receipt reaches furthest, the stamped record if it closes its slot and was never upgraded.
+    pub fn anchor(&self) -> &PoHRecord {
+        return match (self.upgrades.last(), &self.proof.slot_end) {
+            (Some(upgrade), _) => &upgrade.anchor,
+            (None, Some(slot_end)) => slot_end,
+            (None, None) => &self.proof.record,
+        };
+    }
+
+    /// Check the whole receipt against a trusted generator `public_key` and return the latest anchor.
+    pub fn verify(&self, public_key: &[u8; 32]) -> Result<&PoHRecord> {
+        self.validate()?;
+        if self.public_key != *public_key {
+            bail!("Receipt is signed by another generator key.");
+        }
+        if self.proof.record.event_hash() != Some(Event::Document { hash: self.digest }.commitment()) {
+            bail!("Receipt record does not carry the stamped digest.");
+        }
+        verify_proof(&self.proof, public_key)?;
+
+        let key: UnparsedPublicKey<&[u8; 32]> = UnparsedPublicKey::new(&ED25519, public_key);
+        let mut anchor: &PoHRecord = self.proof.slot_end.as_ref().unwrap_or(&self.proof.record);
+        for upgrade in &self.upgrades {
+            if !ProofStep::connects(&upgrade.steps, anchor, &upgrade.anchor)? {
+                bail!("Upgrade does not extend the chain from tick {}.", anchor.tick_index);
+            }
+            let signed: bool = upgrade
+                .anchor
+                .signature
+                .is_some_and(|signature| key.verify(&upgrade.anchor.slot_message(), &signature).is_ok());
+            if !upgrade.anchor.is_slot_end() || !signed {
+                bail!("Upgrade anchor at tick {} is not a signed slot hash.", upgrade.anchor.tick_index);
+            }
+            anchor = &upgrade.anchor;
+        }
+        return Ok(anchor);
+    }
+
+    /// Extend the receipt to the newest signed slot hash in the ledger behind `reader`.
+    ///
+    /// # Returns
+    /// `false` if the ledger has no signed slot hash past the current anchor.
+    pub fn upgrade(&mut self, reader: &LedgerReader) -> Result<bool> {
+        let from: PoHRecord = self.anchor().clone();
+        let mut steps: Vec<ProofStep> = Vec::new();
+        // Newest signed slot end with the number of steps before it and the hash count of the last of them,
+        // which later empty ticks are folded into.
+        let mut newest: Option<(PoHRecord, usize, u64)> = None;
+        for (i, found) in reader.ticks(from.tick_index.saturating_add(1)..u64::MAX)?.enumerate() {
+            let found: LedgerEntry = found?;
+            if i == 0 && found.anchor != Some((from.tick_index, from.hash)) {
+                bail!("Ledger does not continue the receipt chain after tick {}.", from.tick_index);
+            }
+            if found.record.is_slot_end() && found.record.signature.is_some() {
+                let hashes: u64 = steps.last().map_or(0, |step| step.num_hashes);
+                newest = Some((found.record.clone(), steps.len(), hashes));
+            }
+            ProofStep::append(&mut steps, &found.record);
+        }
+
+        let Some((anchor, length, hashes)) = newest else {
+            return Ok(false);
+        };
+        steps.truncate(length);
+        if let Some(step) = steps.last_mut() {
+            step.num_hashes = hashes;
+        }
+        self.upgrades.push(ReceiptSegment { steps, anchor });
+        return Ok(true);
+    }
+
+    /// Read a receipt in either format.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes: Vec<u8> = fs::read(path).with_context(|| format!("Failed to read receipt {}", path.display()))?;
+        let receipt: Self = match bytes.first() {
+            Some(b'{') => serde_json::from_slice(&bytes).with_context(|| format!("Corrupted receipt {}", path.display()))?,
+            _ => Self::decode(&mut &bytes[..]).with_context(|| format!("Corrupted receipt {}", path.display()))?,
+        };
+        receipt.validate()?;
+        return Ok(receipt);
+    }
+
+    /// Write the receipt as JSON under a `.json` path and in the binary layout otherwise.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let bytes: Vec<u8> = match path.extension().is_some_and(|extension| extension == JSON_EXTENSION) {
+            true => serde_json::to_vec_pretty(self)?,
+            false => self.encode()?,
+        };
+        let temporary: PathBuf = path.with_extension("tmp");
+        let mut file: File = File::create(&temporary)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)?;
+        return Ok(());
+    }
+}
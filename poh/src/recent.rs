@@ -0,0 +1,135 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+
+use crate::types::{Admission, Event, EventRejection, PoH, PoHRecord, PoHService, RecentHashes, ReferenceReply};
+
+use anyhow::{Result, anyhow, bail};
+use hex::encode;
+
+impl RecentHashes {
+    pub fn new(capacity: usize) -> Self {
+        return Self {
+            hashes: VecDeque::with_capacity(capacity),
+            capacity,
+        };
+    }
+
+    /// Add the hash closing `slot`, dropping the oldest once the ring is full.
+    pub fn push(&mut self, slot: u64, hash: [u8; 32]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.hashes.len() == self.capacity {
+            self.hashes.pop_front();
+        }
+        self.hashes.push_back((slot, hash));
+    }
+
+    /// Slot closed by `hash`, if it is one of the recent slot hashes.
+    pub fn slot_of(&self, hash: &[u8; 32]) -> Option<u64> {
+        return self.hashes.iter().rev().find(|(_, recent)| recent == hash).map(|(slot, _)| *slot);
+    }
+
+    /// Check that an event referencing `hash` may still be recorded in `slot`, returns the slot closed by `hash`.
+    pub fn check(&self, hash: &[u8; 32], max_age_slots: u64, slot: u64) -> Result<u64, EventRejection> {
+        let referenced: u64 = self.slot_of(hash).ok_or(EventRejection::UnknownReference)?;
+        let age: u64 = slot.saturating_sub(referenced);
+        if age > max_age_slots {
+            return Err(EventRejection::Expired {
+                slot: referenced,
+                age,
+                max_age_slots,
+            });
+        }
+        return Ok(referenced);
+    }
+
+    /// Check a submitted `event` against the ring before it is handed to the hashing loop, in the slot after the newest hash.
+    ///
+    /// # Returns
+    /// The slot closed by the referenced hash, `None` for an event without a reference.
+    pub fn admit(&self, event: &Event) -> Result<Option<u64>, EventRejection> {
+        let Event::Referenced { reference, max_age_slots, event } = event else {
+            return Ok(None);
+        };
+        if event.reference().is_some() {
+            return Err(EventRejection::Nested);
+        }
+        let slot: u64 = self.hashes.back().map_or(0, |(newest, _)| newest.saturating_add(1));
+        return self.check(reference, *max_age_slots, slot).map(Some);
+    }
+}
+
+impl Admission {
+    /// Why the event may no longer be recorded in `slot`, without touching the ring.
+    pub fn expired(&self, slot: u64) -> Option<EventRejection> {
+        let (referenced, (_, max_age_slots)) = self.referenced_slot.zip(self.event.reference())?;
+        let age: u64 = slot.saturating_sub(referenced);
+        return (age > max_age_slots).then_some(EventRejection::Expired {
+            slot: referenced,
+            age,
+            max_age_slots,
+        });
+    }
+}
+
+impl PoH {
+    /// Verify that every [`Event::Referenced`] in `records` refers to the hash closing a slot earlier in `records`,
+    /// at most its `max_age_slots` slots before the record. The records should already form a valid chain.
+    /// Events whose payload was withheld cannot be checked and are skipped.
+    pub fn verify_referenced_records(records: &[PoHRecord]) -> bool {
+        let mut slots: HashMap<[u8; 32], u64> = HashMap::new();
+        for record in records {
+            if let Some((reference, max_age_slots)) = record.event.as_ref().and_then(Event::reference) {
+                let Some(referenced) = slots.get(reference) else {
+                    return false;
+                };
+                if record.slot_index.saturating_sub(*referenced) > max_age_slots {
+                    return false;
+                }
+            }
+            if record.is_slot_end() {
+                slots.insert(record.hash, record.slot_index);
+            }
+        }
+        return true;
+    }
+}
+
+impl Display for EventRejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        return match self {
+            EventRejection::UnknownReference => write!(f, "Referenced hash is not a recent slot hash"),
+            EventRejection::Expired { slot, age, max_age_slots } => {
+                write!(f, "Referenced slot {} is {} slots old, the event expired after {}", slot, age, max_age_slots)
+            }
+            EventRejection::Nested => write!(f, "Event references more than one slot hash"),
+        };
+    }
+}
+
+impl PoHService {
+    /// Submit `event` to be recorded only while the slot closed by `reference` is at most `max_age_slots` slots old,
+    /// so a delayed copy of it cannot be replayed later. It is recorded wrapped in an [`Event::Referenced`],
+    /// which carries the reference into the chain for [`PoH::verify_referenced_records`].
+    ///
+    /// # Returns
+    /// Receives the tick the event was recorded at or why it was rejected, and disconnects if the service stops first.
+    pub fn submit_referenced(&self, event: Event, reference: &[u8; 32], max_age_slots: u64) -> Result<Receiver<ReferenceReply>> {
+        if event.reference().is_some() {
+            bail!("Event already references a slot hash.");
+        }
+        // One answer per event, so replying never blocks the hashing loop.
+        let (reply, answer): (SyncSender<ReferenceReply>, Receiver<ReferenceReply>) = sync_channel(1);
+        let referenced: Event = Event::Referenced {
+            reference: *reference,
+            max_age_slots,
+            event: Box::new(event),
+        };
+        self.referenced
+            .send((referenced, reply))
+            .map_err(|_| anyhow!("PoH service stopped before the event referencing 0x{} was submitted.", encode(reference)))?;
+        return Ok(answer);
+    }
+}
@@ -0,0 +1,234 @@
+use std::cell::UnsafeCell;
+use std::hint::spin_loop;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread as std_thread;
+use std::time::Duration;
+
+use crate::types::CachePadded;
+
+// Number of failed attempts either side spins before it starts yielding.
+const SPIN_LIMIT: u32 = 64;
+// Number of empty polls the consumer yields before it starts sleeping.
+const YIELD_LIMIT: u32 = 128;
+
+/// Pre-allocated single-producer single-consumer ring of fixed-size values.
+/// Only reachable through the two halves returned by [`RingBuffer::channel`], there is never a second producer or consumer.
+pub struct RingBuffer<T> {
+    /// Slot storage, the length is always a power of two.
+    buffer: Box<[UnsafeCell<T>]>,
+    /// Index mask used to wrap positions into the buffer.
+    mask: usize,
+    /// Next position to be read, only advanced by the consumer.
+    head: CachePadded<AtomicUsize>,
+    /// Next position to be written, only advanced by the producer.
+    tail: CachePadded<AtomicUsize>,
+    /// Set once either side has been dropped.
+    closed: AtomicBool,
+}
+
+/// Writing half of a [`RingBuffer`].
+pub struct RingProducer<T> {
+    ring: Arc<RingBuffer<T>>,
+    /// Last observed consumer position, refreshed only when the ring looks full.
+    cached_head: usize,
+}
+
+/// Reading half of a [`RingBuffer`].
+pub struct RingConsumer<T> {
+    ring: Arc<RingBuffer<T>>,
+    /// Last observed producer position, refreshed only when the ring looks empty.
+    cached_tail: usize,
+}
+
+// Each slot is only ever touched by one side at a time, ownership is handed over through `head` and `tail`.
+// Sound only because the halves are the sole handles on the ring and neither can be cloned.
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T: Copy + Default> RingBuffer<T> {
+    /// Allocates a ring able to hold at least `capacity` values and splits it into its two halves.
+    /// The capacity is rounded up to the next power of two.
+    pub fn channel(capacity: usize) -> (RingProducer<T>, RingConsumer<T>) {
+        let size: usize = capacity.max(2).checked_next_power_of_two().expect("ring capacity overflow");
+        let buffer: Box<[UnsafeCell<T>]> = (0..size).map(|_| UnsafeCell::new(T::default())).collect();
+        let ring: Arc<RingBuffer<T>> = Arc::new(Self {
+            buffer,
+            mask: size.wrapping_sub(1),
+            head: CachePadded(AtomicUsize::new(0)),
+            tail: CachePadded(AtomicUsize::new(0)),
+            closed: AtomicBool::new(false),
+        });
+
+        let producer: RingProducer<T> = RingProducer {
+            ring: ring.clone(),
+            cached_head: 0,
+        };
+        let consumer: RingConsumer<T> = RingConsumer { ring, cached_tail: 0 };
+
+        return (producer, consumer);
+    }
+
+    fn capacity(&self) -> usize {
+        return self.buffer.len();
+    }
+
+    fn len(&self) -> usize {
+        let tail: usize = self.tail.0.load(Ordering::Acquire);
+        let head: usize = self.head.0.load(Ordering::Acquire);
+        return tail.wrapping_sub(head);
+    }
+
+    fn is_closed(&self) -> bool {
+        return self.closed.load(Ordering::Acquire);
+    }
+}
+
+impl<T: Copy + Default> RingProducer<T> {
+    /// Number of values the ring can hold.
+    pub fn capacity(&self) -> usize {
+        return self.ring.capacity();
+    }
+
+    /// Number of values currently waiting to be consumed.
+    pub fn len(&self) -> usize {
+        return self.ring.len();
+    }
+
+    /// Check if there is nothing waiting to be consumed.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Check if the consumer has been dropped.
+    pub fn is_closed(&self) -> bool {
+        return self.ring.is_closed();
+    }
+
+    /// Try to write a value without waiting.
+    ///
+    /// # Returns
+    /// `Err(value)` if the ring is full or the consumer is gone.
+    pub fn try_push(&mut self, value: T) -> Result<(), T> {
+        if self.ring.is_closed() {
+            return Err(value);
+        }
+
+        let tail: usize = self.ring.tail.0.load(Ordering::Relaxed);
+
+        if tail.wrapping_sub(self.cached_head) >= self.ring.capacity() {
+            self.cached_head = self.ring.head.0.load(Ordering::Acquire);
+            if tail.wrapping_sub(self.cached_head) >= self.ring.capacity() {
+                return Err(value);
+            }
+        }
+        // The slot at `tail` has already been released by the consumer.
+        unsafe {
+            *self.ring.buffer[tail & self.ring.mask].get() = value;
+        }
+        self.ring.tail.0.store(tail.wrapping_add(1), Ordering::Release);
+
+        return Ok(());
+    }
+
+    /// Write a value, spinning and then yielding while the ring is full.
+    ///
+    /// # Returns
+    /// `Err(value)` if the consumer has been dropped.
+    pub fn push(&mut self, mut value: T) -> Result<(), T> {
+        let mut idle: u32 = 0;
+
+        loop {
+            match self.try_push(value) {
+                Ok(()) => return Ok(()),
+                Err(rejected) if self.ring.is_closed() => return Err(rejected),
+                Err(rejected) => {
+                    value = rejected;
+                    // Never sleep here, the consumer only needs a chance to run.
+                    if idle < SPIN_LIMIT {
+                        spin_loop();
+                    } else {
+                        std_thread::yield_now();
+                    }
+                    idle = idle.saturating_add(1);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Copy + Default> RingConsumer<T> {
+    /// Number of values the ring can hold.
+    pub fn capacity(&self) -> usize {
+        return self.ring.capacity();
+    }
+
+    /// Number of values currently waiting to be consumed.
+    pub fn len(&self) -> usize {
+        return self.ring.len();
+    }
+
+    /// Check if there is nothing waiting to be consumed.
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Check if the producer has been dropped.
+    pub fn is_closed(&self) -> bool {
+        return self.ring.is_closed();
+    }
+
+    /// Try to read a value without waiting.
+    pub fn try_pop(&mut self) -> Option<T> {
+        let head: usize = self.ring.head.0.load(Ordering::Relaxed);
+
+        if head == self.cached_tail {
+            self.cached_tail = self.ring.tail.0.load(Ordering::Acquire);
+            if head == self.cached_tail {
+                return None;
+            }
+        }
+        // The slot at `head` has already been published by the producer.
+        let value: T = unsafe { *self.ring.buffer[head & self.ring.mask].get() };
+        self.ring.head.0.store(head.wrapping_add(1), Ordering::Release);
+
+        return Some(value);
+    }
+
+    /// Read a value, backing off from spinning to sleeping while the ring is empty.
+    ///
+    /// # Returns
+    /// `None` once the producer has been dropped and every value has been read.
+    pub fn pop(&mut self) -> Option<T> {
+        let mut idle: u32 = 0;
+
+        loop {
+            if let Some(value) = self.try_pop() {
+                return Some(value);
+            }
+            // Re-check after observing the close flag so a final write is never missed.
+            if self.ring.is_closed() {
+                return self.try_pop();
+            }
+            if idle < SPIN_LIMIT {
+                spin_loop();
+            } else if idle < YIELD_LIMIT {
+                std_thread::yield_now();
+            } else {
+                std_thread::sleep(Duration::from_micros(50));
+            }
+            idle = idle.saturating_add(1);
+        }
+    }
+}
+
+impl<T> Drop for RingProducer<T> {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Drop for RingConsumer<T> {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::types::{CsvSink, JsonSink, Ledger, PoHRecord, PoHStatus, RecordEncoder, RecordSink, TerminalSink};
+
+use lib::DEFAULT_HASHES_PER_TICK;
+use lib::utils::json::{JsonLinesWriter, write};
+
+use anyhow::Result;
+use crossterm::{
+    cursor::MoveTo,
+    execute,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+use hex::encode;
+
+// Column names of the CSV output.
+const CSV_HEADER: &str = "tick_index,slot_index,epoch_index,timestamp_ms,num_hashes,hash,commitment,signature,witnesses,event";
+
+impl TerminalSink {
+    pub fn new(out: Box<dyn Write>, top: u16, target_ticks: u64, status: Arc<PoHStatus>, first_tick: u64) -> Self {
+        return Self {
+            out,
+            top,
+            target_ticks,
+            status,
+            first_tick,
+            ticks: 0,
+            last_update: Instant::now(),
+            last_tick_count: 0,
+        };
+    }
+
+    // Redraw the progress line at most once a second.
+    fn update_progress(&mut self) -> Result<()> {
+        let now: Instant = Instant::now();
+        let elapsed: f64 = now.duration_since(self.last_update).as_secs_f64();
+        if elapsed < 1.0 {
+            return Ok(());
+        }
+
+        let tick_per_second: f64 = self.ticks.saturating_sub(self.last_tick_count) as f64 / elapsed;
+        let hash_per_second: f64 = tick_per_second * DEFAULT_HASHES_PER_TICK as f64;
+        let progress_percent: f64 = (self.ticks as f64 / self.target_ticks as f64) * 100.0;
+        execute!(
+            self.out,
+            Clear(ClearType::CurrentLine),
+            MoveTo(0, self.top),
+            SetForegroundColor(Color::Green),
+            Print(format!(
+                "  | {:.1}% - {:.2} ticks/s - {:.3} MH/s - {:.2}ms behind\n",
+                progress_percent,
+                tick_per_second,
+                hash_per_second / 1_000_000.0,
+                self.status.last_lateness_us() as f64 / 1_000.0
+            )),
+            ResetColor
+        )?;
+        self.last_tick_count = self.ticks;
+        self.last_update = now;
+        return Ok(());
+    }
+}
+
+impl RecordSink for TerminalSink {
+    fn write_record(&mut self, record: &PoHRecord) -> Result<()> {
+        self.update_progress()?;
+        execute!(
+            self.out,
+            MoveTo(0, self.top.saturating_add(1)),
+            Clear(ClearType::CurrentLine),
+            Print(format!("  |\n{}\n", record))
+        )?;
+        // Sparse records cover several ticks, progress follows the tick index.
+        self.ticks = record.tick_index.saturating_add(1).saturating_sub(self.first_tick);
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        return self.flush();
+    }
+}
+
+impl JsonSink {
+    pub fn new(filename: &str) -> Self {
+        return Self {
+            filename: filename.to_string(),
+            records: Vec::new(),
+        };
+    }
+}
+
+impl RecordSink for JsonSink {
+    fn write_record(&mut self, record: &PoHRecord) -> Result<()> {
+        self.records.push(record.clone());
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        write(&self.records, &self.filename)?;
+        return Ok(());
+    }
+}
+
+impl CsvSink<File> {
+    pub fn create(path: &str) -> Result<Self> {
+        return Self::new(File::create(path)?);
+    }
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Start the output with the header line.
+    pub fn new(writer: W) -> Result<Self> {
+        let mut writer: BufWriter<W> = BufWriter::new(writer);
+        writeln!(writer, "{}", CSV_HEADER)?;
+        return Ok(Self { writer });
+    }
+}
+
+impl<W: Write> RecordSink for CsvSink<W> {
+    fn write_record(&mut self, record: &PoHRecord) -> Result<()> {
+        // The event is the only field that can contain commas or quotes.
+        let event: String = match &record.event {
+            Some(event) => format!("\"{}\"", serde_json::to_string(event)?.replace('"', "\"\"")),
+            None => String::new(),
+        };
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{},{},{}",
+            record.tick_index,
+            record.slot_index,
+            record.epoch_index,
+            record.timestamp_ms,
+            record.num_hashes,
+            encode(record.hash),
+            record.commitment.map(encode).unwrap_or_default(),
+            record.signature.map(encode).unwrap_or_default(),
+            record.witnesses.len(),
+            event
+        )?;
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        return self.flush();
+    }
+}
+
+impl<W: Write> RecordSink for JsonLinesWriter<W> {
+    fn write_record(&mut self, record: &PoHRecord) -> Result<()> {
+        self.write(record)?;
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        JsonLinesWriter::flush(self)?;
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        return RecordSink::flush(self);
+    }
+}
+
+impl<W: Write> RecordSink for RecordEncoder<W> {
+    fn write_record(&mut self, record: &PoHRecord) -> Result<()> {
+        return self.write(record);
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return RecordEncoder::flush(self);
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        return RecordEncoder::flush(self);
+    }
+}
+
+impl RecordSink for Ledger {
+    fn write_record(&mut self, record: &PoHRecord) -> Result<()> {
+        return self.append(record);
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        return self.sync();
+    }
+
+    fn summary(&self) -> Option<String> {
+        return (self.compression.segments > 0).then(|| format!("Ledger: {}", self.compression));
+    }
+}
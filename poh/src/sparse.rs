@@ -0,0 +1,46 @@
+use crate::types::{PoHRecord, SparseLedger};
+
+impl SparseLedger {
+    pub fn new() -> Self {
+        return Self { pending: None };
+    }
+
+    /// Feed the next full record.
+    ///
+    /// # Returns
+    /// Up to two records to emit, a flushed run of empty ticks first.
+    pub fn push(&mut self, record: PoHRecord) -> [Option<PoHRecord>; 2] {
+        if record.has_event() {
+            return [self.pending.take(), Some(record)];
+        }
+
+        let merged: PoHRecord = match self.pending.take() {
+            Some(pending) => PoHRecord {
+                num_hashes: pending.num_hashes.saturating_add(record.num_hashes),
+                ..record
+            },
+            None => record,
+        };
+        // The last tick of a slot is mandatory so every slot hash stays in the ledger.
+        if merged.is_slot_end() {
+            return [Some(merged), None];
+        }
+        self.pending = Some(merged);
+
+        return [None, None];
+    }
+
+    /// Flush the trailing run of empty ticks, if any.
+    pub fn finish(&mut self) -> Option<PoHRecord> {
+        return self.pending.take();
+    }
+
+    /// Collapse a whole sequence of full records at once.
+    pub fn collapse(records: impl IntoIterator<Item = PoHRecord>) -> Vec<PoHRecord> {
+        let mut sparse: SparseLedger = SparseLedger::new();
+        let mut collapsed: Vec<PoHRecord> = records.into_iter().flat_map(|record| sparse.push(record)).flatten().collect();
+
+        collapsed.extend(sparse.finish());
+        return collapsed;
+    }
+}
@@ -0,0 +1,220 @@
+use std::ops::Range;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::types::{LedgerHeader, PoHConfig, PoHRecord, RecordSink, SqliteLedger};
+
+use lib::{DEFAULT_HASHES_PER_TICK, DEFAULT_SQLITE_BATCH_SIZE};
+
+use anyhow::{Context, Result, anyhow};
+use hex::encode;
+use rusqlite::{CachedStatement, Connection, Transaction, params};
+
+// Tables and indices, created on first open.
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        started_ms INTEGER NOT NULL,
+        algorithm INTEGER NOT NULL,
+        hashes_per_tick INTEGER NOT NULL,
+        ticks_per_slot INTEGER NOT NULL,
+        slots_per_epoch INTEGER NOT NULL,
+        config TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS records (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        tick_index INTEGER NOT NULL,
+        slot_index INTEGER NOT NULL,
+        epoch_index INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        timestamp_ms INTEGER NOT NULL,
+        num_hashes INTEGER NOT NULL,
+        signed INTEGER NOT NULL,
+        witnesses INTEGER NOT NULL,
+        record BLOB NOT NULL,
+        PRIMARY KEY (run_id, tick_index)
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        tick_index INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        kind TEXT,
+        event TEXT,
+        PRIMARY KEY (run_id, tick_index)
+    );
+    CREATE TABLE IF NOT EXISTS slots (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        slot_index INTEGER NOT NULL,
+        first_tick INTEGER NOT NULL,
+        last_tick INTEGER NOT NULL,
+        records INTEGER NOT NULL,
+        last_hash TEXT NOT NULL,
+        signed INTEGER NOT NULL,
+        witnesses INTEGER NOT NULL,
+        PRIMARY KEY (run_id, slot_index)
+    );
+    CREATE INDEX IF NOT EXISTS records_tick ON records (tick_index);
+    CREATE INDEX IF NOT EXISTS records_slot ON records (run_id, slot_index);
+    CREATE INDEX IF NOT EXISTS events_hash ON events (hash);
+";
+
+impl SqliteLedger {
+    /// Open or create the database at `path`, queries look at the latest run until [`SqliteLedger::start_run`].
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection: Connection = Connection::open(path).with_context(|| format!("Failed to open database {}", path.display()))?;
+        connection.execute_batch(SCHEMA)?;
+        let run_id: Option<i64> = connection.query_row("SELECT MAX(id) FROM runs", [], |row| row.get(0))?;
+
+        return Ok(Self {
+            connection,
+            run_id,
+            pending: Vec::new(),
+            batch_size: DEFAULT_SQLITE_BATCH_SIZE,
+        });
+    }
+
+    /// Record a new run generated with `config`, records written from now on belong to it.
+    pub fn start_run(&mut self, config: &PoHConfig) -> Result<i64> {
+        self.flush_pending()?;
+        let header: LedgerHeader = LedgerHeader::new(config);
+        let started_ms: u64 = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        self.connection.execute(
+            "INSERT INTO runs (started_ms, algorithm, hashes_per_tick, ticks_per_slot, slots_per_epoch, config) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                started_ms,
+                header.algorithm,
+                header.hashes_per_tick,
+                header.ticks_per_slot,
+                header.slots_per_epoch,
+                serde_json::to_string(&header)?
+            ],
+        )?;
+        let run_id: i64 = self.connection.last_insert_rowid();
+        self.run_id = Some(run_id);
+        return Ok(run_id);
+    }
+
+    /// Records of the slots in `slots`, in tick order.
+    pub fn records(&mut self, slots: Range<u64>) -> Result<Vec<PoHRecord>> {
+        self.flush_pending()?;
+        let mut statement: CachedStatement = self
+            .connection
+            .prepare_cached("SELECT record FROM records WHERE run_id = ?1 AND slot_index >= ?2 AND slot_index < ?3 ORDER BY tick_index")?;
+        let blobs: Vec<Vec<u8>> = statement
+            .query_map(params![self.run_id, slots.start, slots.end], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        return blobs.iter().map(|blob| decode(blob)).collect();
+    }
+
+    /// Records carrying an event whose hash starts with the hex `prefix`, in tick order.
+    pub fn events(&mut self, prefix: &str) -> Result<Vec<PoHRecord>> {
+        self.flush_pending()?;
+        let prefix: String = prefix.to_ascii_lowercase();
+        // Every lowercase hex string starting with `prefix` sorts below `prefix` followed by 'g', so the index can serve the range.
+        let mut statement: CachedStatement = self.connection.prepare_cached(
+            "SELECT records.record FROM events JOIN records USING (run_id, tick_index)
+             WHERE events.run_id = ?1 AND events.hash >= ?2 AND events.hash < ?3 ORDER BY tick_index",
+        )?;
+        let blobs: Vec<Vec<u8>> = statement
+            .query_map(params![self.run_id, prefix, format!("{}g", prefix)], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        return blobs.iter().map(|blob| decode(blob)).collect();
+    }
+
+    // Insert the pending records in one transaction.
+    fn flush_pending(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let run_id: i64 = self.run_id.ok_or_else(|| anyhow!("No run started in the database."))?;
+        let transaction: Transaction = self.connection.transaction()?;
+        let mut buffer: Vec<u8> = Vec::new();
+        for record in &self.pending {
+            buffer.clear();
+            record.encode_into(&mut buffer)?;
+            insert(&transaction, run_id, record, &buffer)?;
+        }
+        transaction.commit()?;
+        self.pending.clear();
+        return Ok(());
+    }
+}
+
+impl RecordSink for SqliteLedger {
+    fn write_record(&mut self, record: &PoHRecord) -> Result<()> {
+        self.pending.push(record.clone());
+        if self.pending.len() >= self.batch_size {
+            self.flush_pending()?;
+        }
+        return Ok(());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        return self.flush_pending();
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        return self.flush_pending();
+    }
+}
+
+fn insert(transaction: &Transaction, run_id: i64, record: &PoHRecord, encoded: &[u8]) -> Result<()> {
+    let hash: String = encode(record.hash);
+    let signed: bool = record.signature.is_some();
+    transaction
+        .prepare_cached(
+            "INSERT INTO records (run_id, tick_index, slot_index, epoch_index, hash, timestamp_ms, num_hashes, signed, witnesses, record)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )?
+        .execute(params![
+            run_id,
+            record.tick_index,
+            record.slot_index,
+            record.epoch_index,
+            hash,
+            record.timestamp_ms,
+            record.num_hashes,
+            signed,
+            record.witnesses.len(),
+            encoded
+        ])?;
+
+    // Committed events only have their hash, the payload stays off-chain.
+    let event: Option<(String, Option<&str>, Option<String>)> = match (&record.event, &record.commitment) {
+        (Some(event), _) => Some((encode(event.commitment()), Some(event.kind()), Some(serde_json::to_string(event)?))),
+        (None, Some(commitment)) => Some((encode(commitment), None, None)),
+        (None, None) => None,
+    };
+    if let Some((event_hash, kind, json)) = event {
+        transaction
+            .prepare_cached("INSERT INTO events (run_id, tick_index, hash, kind, event) VALUES (?1, ?2, ?3, ?4, ?5)")?
+            .execute(params![run_id, record.tick_index, event_hash, kind, json])?;
+    }
+
+    // A sparse record stands for several ticks, the first of them opens the slot.
+    let ticks: u64 = (record.num_hashes / DEFAULT_HASHES_PER_TICK).max(1);
+    let first_tick: u64 = record.tick_index.saturating_add(1).saturating_sub(ticks);
+    transaction
+        .prepare_cached(
+            "INSERT INTO slots (run_id, slot_index, first_tick, last_tick, records, last_hash, signed, witnesses)
+             VALUES (?1, ?2, ?3, ?4, 1, ?5, ?6, ?7)
+             ON CONFLICT (run_id, slot_index) DO UPDATE SET
+                last_tick = excluded.last_tick, records = records + 1, last_hash = excluded.last_hash,
+                signed = excluded.signed, witnesses = excluded.witnesses",
+        )?
+        .execute(params![
+            run_id,
+            record.slot_index,
+            first_tick,
+            record.tick_index,
+            hash,
+            signed,
+            record.witnesses.len()
+        ])?;
+    return Ok(());
+}
+
+fn decode(blob: &[u8]) -> Result<PoHRecord> {
+    return PoHRecord::read_from(&mut &blob[..])?.ok_or_else(|| anyhow!("Empty record in the database."));
+}
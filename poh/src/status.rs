@@ -0,0 +1,69 @@
+use std::sync::atomic::Ordering;
+
+use crate::types::PoHStatus;
+
+impl PoHStatus {
+    /// Record the lateness of one tick.
+    ///
+    /// # Returns
+    /// `true` if the tick finished past `threshold_us`.
+    pub fn record_tick(&self, lateness_us: u64, threshold_us: u64) -> bool {
+        let late: bool = lateness_us > threshold_us;
+
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.last_lateness_us.store(lateness_us, Ordering::Relaxed);
+        self.max_lateness_us.fetch_max(lateness_us, Ordering::Relaxed);
+        if lateness_us > 0 {
+            self.schedule_error_us.fetch_add(lateness_us, Ordering::Relaxed);
+        }
+        if late {
+            self.late_ticks.fetch_add(1, Ordering::Relaxed);
+        }
+        return late;
+    }
+
+    /// Get the number of ticks generated so far.
+    pub fn ticks(&self) -> u64 {
+        return self.ticks.load(Ordering::Relaxed);
+    }
+
+    /// Get the lateness of the most recent tick in microseconds.
+    pub fn last_lateness_us(&self) -> u64 {
+        return self.last_lateness_us.load(Ordering::Relaxed);
+    }
+
+    /// Get the worst lateness observed in microseconds.
+    pub fn max_lateness_us(&self) -> u64 {
+        return self.max_lateness_us.load(Ordering::Relaxed);
+    }
+
+    /// Get the cumulative schedule error in microseconds.
+    pub fn schedule_error_us(&self) -> u64 {
+        return self.schedule_error_us.load(Ordering::Relaxed);
+    }
+
+    /// Get the number of ticks that finished past the slip threshold.
+    pub fn late_ticks(&self) -> u64 {
+        return self.late_ticks.load(Ordering::Relaxed);
+    }
+
+    /// Get the number of times the slip policy has been applied.
+    pub fn slips(&self) -> u64 {
+        return self.slips.load(Ordering::Relaxed);
+    }
+
+    /// Get the number of slots signed by at least the witness threshold.
+    pub fn finalised_slots(&self) -> u64 {
+        return self.finalised_slots.load(Ordering::Relaxed);
+    }
+
+    /// Check if the hashing loop has stopped.
+    pub fn is_finished(&self) -> bool {
+        return self.finished.load(Ordering::Acquire);
+    }
+
+    /// Check if the loop was stopped by the abort policy.
+    pub fn is_aborted(&self) -> bool {
+        return self.aborted.load(Ordering::Acquire);
+    }
+}
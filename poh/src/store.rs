@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::types::{Event, EventStore, PoH, PoHRecord, StoredEvent};
+
+use anyhow::{Context, Result};
+
+impl EventStore {
+    /// Store kept in memory only.
+    pub fn new() -> Self {
+        return Self {
+            events: HashMap::new(),
+            path: None,
+            file: None,
+        };
+    }
+
+    /// Store persisted in the sidecar file at `path`, created if missing and appended to from then on.
+    /// A torn last line, left by a crash while appending, is dropped, any other damage is an error.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut store: Self = Self::new();
+        let bytes: Vec<u8> = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let complete: usize = bytes.iter().rposition(|byte| *byte == b'\n').map_or(0, |end| end.saturating_add(1));
+        for (i, line) in bytes[..complete].split(|byte| *byte == b'\n').filter(|line| !line.is_empty()).enumerate() {
+            let stored: StoredEvent =
+                serde_json::from_slice(line).with_context(|| format!("Corrupted entry {} in event store {}", i.saturating_add(1), path.display()))?;
+            store.remember(stored.tick_index, stored.event);
+        }
+
+        let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+        if complete < bytes.len() {
+            file.set_len(complete as u64)?;
+        }
+        store.path = Some(path.to_path_buf());
+        store.file = Some(BufWriter::new(file));
+        return Ok(store);
+    }
+
+    /// Keep the payload of an event committed at `tick_index`, appending it to the sidecar file if there is one.
+    ///
+    /// # Returns
+    /// The commitment the payload is stored under.
+    pub fn insert(&mut self, tick_index: u64, event: Event) -> Result<[u8; 32]> {
+        if let Some(file) = self.file.as_mut() {
+            let stored: StoredEvent = StoredEvent {
+                tick_index,
+                event: event.clone(),
+            };
+            serde_json::to_writer(&mut *file, &stored)?;
+            file.write_all(b"\n")?;
+        }
+        return Ok(self.remember(tick_index, event));
+    }
+
+    pub fn get(&self, commitment: &[u8; 32]) -> Option<&Event> {
+        return self.events.get(commitment).map(|stored| &stored.event);
+    }
+
+    pub fn remove(&mut self, commitment: &[u8; 32]) -> Result<Option<Event>> {
+        let removed: Option<Event> = self.events.remove(commitment).map(|stored| stored.event);
+        if removed.is_some() {
+            self.rewrite()?;
+        }
+        return Ok(removed);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.events.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.events.is_empty();
+    }
+
+    /// Drop every payload last committed before `tick_index`, the chain still verifies without them.
+    ///
+    /// # Returns
+    /// The number of pruned payloads.
+    pub fn prune_before(&mut self, tick_index: u64) -> Result<usize> {
+        let before: usize = self.events.len();
+        self.events.retain(|_, stored| stored.tick_index >= tick_index);
+        let pruned: usize = before.saturating_sub(self.events.len());
+        if pruned > 0 {
+            self.rewrite()?;
+        }
+        return Ok(pruned);
+    }
+
+    /// Move the payloads of committed records into the store, leaving only the commitments behind.
+    pub fn detach(&mut self, records: &mut [PoHRecord]) -> Result<()> {
+        for record in records.iter_mut().filter(|r| r.commitment.is_some()) {
+            if let Some(event) = record.event.take() {
+                self.insert(record.tick_index, event)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Re-attach stored payloads to committed records that are missing them.
+    pub fn attach(&self, records: &mut [PoHRecord]) {
+        for record in records.iter_mut().filter(|r| r.event.is_none()) {
+            if let Some(event) = record.commitment.and_then(|commitment| self.get(&commitment)) {
+                record.event = Some(event.clone());
+            }
+        }
+    }
+
+    /// Verify the chain, then check every stored payload referenced by it against its commitment.
+    /// Records whose payload is neither attached nor stored are verified by commitment alone.
+    pub fn verify(&self, records: &[PoHRecord]) -> bool {
+        if !PoH::verify_records(records) {
+            return false;
+        }
+        return records
+            .iter()
+            .filter_map(|record| record.commitment)
+            .all(|commitment| self.get(&commitment).is_none_or(|event| event.commitment() == commitment));
+    }
+
+    /// Make every payload inserted so far durable.
+    pub fn sync(&mut self) -> Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            file.flush()?;
+            file.get_ref().sync_all()?;
+        }
+        return Ok(());
+    }
+
+    fn remember(&mut self, tick_index: u64, event: Event) -> [u8; 32] {
+        let commitment: [u8; 32] = event.commitment();
+        let stored: &mut StoredEvent = self.events.entry(commitment).or_insert(StoredEvent { tick_index, event });
+        stored.tick_index = stored.tick_index.max(tick_index);
+        return commitment;
+    }
+
+    // Replace the sidecar file with the payloads still kept, aside first so a crash leaves either version.
+    fn rewrite(&mut self) -> Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        // Whatever is still buffered is superseded by the rewrite.
+        drop(self.file.take());
+        let temporary: PathBuf = path.with_extension("tmp");
+        let mut writer: BufWriter<File> = BufWriter::new(File::create(&temporary)?);
+        for stored in self.events.values() {
+            serde_json::to_writer(&mut writer, stored)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&temporary, &path)?;
+        self.file = Some(BufWriter::new(OpenOptions::new().append(true).open(&path)?));
+        return Ok(());
+    }
+}
@@ -1,52 +1,196 @@
-use std::sync::mpsc::{Receiver, SendError, SyncSender, sync_channel};
+use std::io::Write;
+use std::slice;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, SyncSender, channel, sync_channel};
 use std::sync::{Arc, Mutex};
 use std::thread as std_thread;
 use std::time::{Duration, Instant};
 
-use crate::types::{PoH, PoHRecord};
+use crate::preflight::measure_hash_rate;
+use crate::types::{
+    Admission, Event, EventStore, Identity, PoH, PoHConfig, PoHEntry, PoHRecord, PoHService, PoHStatus, RecentHashes, ReferenceReply, ReferencedSubmission, RingBuffer,
+    SelfTestReport, SlipPolicy, SlotHash, SparseLedger, WitnessPipeline,
+};
 
-use lib::{DEFAULT_BATCH_SIZE, DEFAULT_CHANNEL_CAPACITY, DEFAULT_SPINLOCK_THRESHOLD_US, DEFAULT_US_PER_TICK};
-use thread::native::types::{Config, JoinHandle, Manager, SharedJoinHandle, ThreadHandleGuard, ThreadHandlePool};
+use lib::{DEFAULT_CHANNEL_CAPACITY, DEFAULT_MS_PER_TICK, DEFAULT_RING_CAPACITY, DEFAULT_SPINLOCK_THRESHOLD_US, DEFAULT_TICKS_PER_SLOT, DEFAULT_US_PER_TICK};
+use thread::native::policy::applied_policy;
+use thread::native::types::{AppliedPolicy, JoinHandle, Manager, SharedJoinHandle, ThreadHandleGuard, ThreadHandlePool};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
+
+// Room for the longest simulated payload, "Event at tick 18446744073709551615.".
+const SIMULATED_EVENT_BYTES: usize = 36;
 
 lazy_static::lazy_static! {
     static ref ACTIVE_THREADS: ThreadHandlePool = Mutex::new(Vec::new());
 }
 
 pub fn thread(seed: &[u8], max_ticks: u64) -> Result<Receiver<PoHRecord>> {
+    return Ok(service(seed, max_ticks, PoHConfig::default())?.receiver);
+}
+
+pub fn service(seed: &[u8], max_ticks: u64, config: PoHConfig) -> Result<PoHService> {
+    return start(seed.to_vec(), None, max_ticks, config);
+}
+
+/// Continue a chain after `last`, e.g. the last durable record of an on-disk ledger, for another `max_ticks`.
+pub fn resume(last: &PoHRecord, max_ticks: u64, config: PoHConfig) -> Result<PoHService> {
+    return start(Vec::new(), Some(last.clone()), max_ticks, config);
+}
+
+fn start(seed: Vec<u8>, last: Option<PoHRecord>, max_ticks: u64, config: PoHConfig) -> Result<PoHService> {
+    config.validate()?;
+
     let (tx, rx) = sync_channel(DEFAULT_CHANNEL_CAPACITY);
-    let seed: Vec<u8> = seed.to_vec();
+    let (mut producer, mut consumer) = RingBuffer::<PoHEntry>::channel(DEFAULT_RING_CAPACITY);
+    // Event payloads bypass the ring, they are matched back to flagged entries in order.
+    // At most one per entry in the ring plus the one handed over on either side, so the bounded channel never blocks or allocates.
+    let (event_tx, event_rx): (SyncSender<Event>, Receiver<Event>) = sync_channel(producer.capacity().saturating_add(2));
+    // Submitted events pass through the ingestion thread before the hashing loop picks them up.
+    let (submit_tx, submit_rx): (SyncSender<Event>, Receiver<Event>) = sync_channel(DEFAULT_CHANNEL_CAPACITY);
+    let (referenced_tx, referenced_rx): (SyncSender<ReferencedSubmission>, Receiver<ReferencedSubmission>) = sync_channel(DEFAULT_CHANNEL_CAPACITY);
+    let (ingest_tx, ingest_rx): (SyncSender<Admission>, Receiver<Admission>) = sync_channel(DEFAULT_CHANNEL_CAPACITY);
+    // Slot hashes flow back to the ingest thread, which checks event references against them off the timing-critical path.
+    let (slot_tx, slot_rx): (SyncSender<SlotHash>, Receiver<SlotHash>) = sync_channel(config.recent_slot_hashes.max(1));
+    let mut recent: RecentHashes = RecentHashes::new(config.recent_slot_hashes);
+    // A resumed chain can be referenced from its last slot hash on.
+    if let Some(record) = last.as_ref().filter(|record| record.is_slot_end()) {
+        recent.push(record.slot_index, record.hash);
+    }
+    // The hashing thread reports its policy and self-test result before the loop is allowed to start.
+    let (self_test_tx, self_test_rx) = sync_channel::<(AppliedPolicy, Option<SelfTestReport>)>(1);
+    let (policy_tx, policy_rx): (Sender<AppliedPolicy>, Receiver<AppliedPolicy>) = channel();
+    let delivery_policy_tx: Sender<AppliedPolicy> = policy_tx.clone();
+    let status: Arc<PoHStatus> = Arc::new(PoHStatus::default());
+    let loop_status: Arc<PoHStatus> = status.clone();
+    let ingest_status: Arc<PoHStatus> = status.clone();
+    let identity: Option<Identity> = config.resolve_identity()?;
+    // Committed payloads are moved aside before any sink sees a record.
+    let mut store: Option<EventStore> = match (config.commit_events, &config.event_store) {
+        (true, Some(path)) => Some(EventStore::open(path)?),
+        _ => None,
+    };
+    // Witnesses are connected up front so an unreachable quorum fails the start.
+    let mut witnesses: Option<WitnessPipeline> = match config.witnesses.is_empty() {
+        true => None,
+        false => Some(WitnessPipeline::connect(
+            &config.witnesses,
+            config.witness_threshold,
+            config.witness_timeout_ms,
+            identity.as_ref().map(Identity::public_key).unwrap_or_default(),
+            status.clone(),
+        )?),
+    };
 
-    let manager: Manager = Manager::new("poh-thread".to_string(), Config::default())?;
-    let handle: SharedJoinHandle = Arc::new(Mutex::new(None));
+    let manager: Manager = Manager::new("poh-thread".to_string(), config.poh_thread.clone())?;
+    let delivery_manager: Manager = Manager::new("poh-delivery".to_string(), config.delivery_thread.clone())?;
+    let ingest_manager: Manager = Manager::new("poh-ingest".to_string(), config.ingest_thread.clone())?;
 
     let instance: JoinHandle<()> = manager
         .spawn(move || {
-            let mut poh: PoH = PoH::new(&seed);
-            let mut records_batch: Vec<PoHRecord> = Vec::with_capacity(DEFAULT_BATCH_SIZE);
+            // Measure on this thread so affinity and priority match the loop itself.
+            let report: Option<SelfTestReport> = (config.self_test_ms > 0).then(|| measure_hash_rate(config.self_test_ms));
+            let refused: bool = config.enforce_self_test && report.as_ref().is_some_and(|r| !r.passes(config.min_headroom));
+
+            let _ = self_test_tx.send((applied_policy(), report));
+            if refused {
+                loop_status.finished.store(true, Ordering::Release);
+                return;
+            }
+
+            let mut poh: PoH = match &last {
+                Some(record) => PoH::from_record(record),
+                None => PoH::new(&seed),
+            };
 
             let start: Instant = Instant::now();
             // Pre-calculate target completion times for each tick.
+            // Targets stay on the original schedule, so a late loop catches up by skipping sleeps.
             let mut next_tick_target_us: u64 = DEFAULT_US_PER_TICK;
+            let mut consecutive_late: u64 = 0;
+            let mut slip_marker: Option<Event> = None;
+
+            // Simulated events are written into this one buffer so the loop never allocates.
+            let mut simulated: Event = Event::Data {
+                bytes: Vec::with_capacity(SIMULATED_EVENT_BYTES),
+            };
 
             for i in 0..max_ticks {
-                // Simulate event insertion every 10 ticks.
-                let record: PoHRecord = if i % 10 == 0 {
-                    let event_data: String = format!("Event at tick {}.", i);
-                    poh.insert_event(event_data.as_bytes())
+                // Simulate event insertion every 10 ticks, slip markers and then submitted events take the next free tick.
+                let (event, reply): (Option<Event>, Option<SyncSender<ReferenceReply>>) = if i % 10 == 0 {
+                    (None, None)
+                } else if let Some(marker) = slip_marker.take() {
+                    (Some(marker), None)
                 } else {
-                    poh.next_tick()
+                    next_submission(&ingest_rx, poh.tick_count / DEFAULT_TICKS_PER_SLOT)
                 };
-
-                records_batch.push(record);
-                // Send in batches but don't let batch operations delay timing.
-                if records_batch.len() >= DEFAULT_BATCH_SIZE && send_batch(&tx, &mut records_batch).is_err() {
+                let entry: PoHEntry = match event {
+                    // Rewritten in place and never sent, the delivery thread rebuilds the payload from `i`.
+                    None if i % 10 == 0 => {
+                        if let Event::Data { bytes } = &mut simulated {
+                            simulated_payload(bytes, i);
+                        }
+                        if config.commit_events {
+                            poh.commit_event_entry(&simulated)
+                        } else {
+                            poh.insert_event_entry(&simulated)
+                        }
+                    }
+                    Some(event) => {
+                        let entry: PoHEntry = if config.commit_events {
+                            poh.commit_event_entry(&event)
+                        } else {
+                            poh.insert_event_entry(&event)
+                        };
+                        // The payload must be queued before its entry becomes visible to the consumer.
+                        if event_tx.send(event).is_err() {
+                            break;
+                        }
+                        entry
+                    }
+                    None => poh.next_entry(),
+                };
+                // Hand off without allocating, stops once the consumer is gone.
+                if producer.push(entry).is_err() {
                     break;
                 }
+                if let Some(reply) = reply {
+                    let _ = reply.try_send(Ok(entry.tick_index));
+                }
+                // Never blocks, a hash the ingest thread has no room for is simply unknown to later references.
+                if entry.tick_index.saturating_add(1) % DEFAULT_TICKS_PER_SLOT == 0 {
+                    let _ = slot_tx.try_send((entry.slot_index, entry.hash));
+                }
 
                 let elapsed_us: u64 = start.elapsed().as_micros() as u64;
                 let target_us: u64 = next_tick_target_us;
+                let lateness_us: u64 = elapsed_us.saturating_sub(target_us);
+
+                if loop_status.record_tick(lateness_us, config.slip_threshold_us) {
+                    consecutive_late = consecutive_late.saturating_add(1);
+                } else {
+                    consecutive_late = 0;
+                }
+                // Apply the policy once per sustained slip, it re-arms when a tick is back on time.
+                if consecutive_late == config.slip_tolerance_ticks {
+                    loop_status.slips.fetch_add(1, Ordering::Relaxed);
+                    match config.slip_policy {
+                        SlipPolicy::Warn => {
+                            eprintln!("Warning: PoH is {}us behind schedule at tick {}.", lateness_us, entry.tick_index);
+                        }
+                        SlipPolicy::Marker => {
+                            slip_marker = Some(Event::ScheduleSlip {
+                                tick: entry.tick_index,
+                                lateness_us,
+                            });
+                        }
+                        SlipPolicy::Abort => {
+                            eprintln!("Error: PoH is {}us behind schedule at tick {}, aborting.", lateness_us, entry.tick_index);
+                            loop_status.aborted.store(true, Ordering::Release);
+                            break;
+                        }
+                    }
+                }
 
                 if elapsed_us < target_us {
                     let sleep_us: u64 = target_us.saturating_sub(elapsed_us);
@@ -69,22 +213,163 @@ pub fn thread(seed: &[u8], max_ticks: u64) -> Result<Receiver<PoHRecord>> {
                 // Calculate next tick target time.
                 next_tick_target_us = next_tick_target_us.saturating_add(DEFAULT_US_PER_TICK);
             }
-            // Send any remaining records.
-            let _ = send_batch(&tx, &mut records_batch);
+            loop_status.finished.store(true, Ordering::Release);
         })
         .expect("Failed to spawn PoH thread.");
-    // Store the instance.
-    *handle.lock().unwrap() = Some(instance);
-    ACTIVE_THREADS.lock().unwrap().push(handle);
 
-    return Ok(rx);
+    let delivery: JoinHandle<()> = delivery_manager
+        .spawn(move || {
+            let _ = delivery_policy_tx.send(applied_policy());
+            let mut sparse: Option<SparseLedger> = config.sparse.then(SparseLedger::new);
+            // Loop index of the next entry, simulated events are rebuilt from it rather than sent by the hashing loop.
+            let mut i: u64 = 0;
+            // Rebuild full records off the timing-critical path.
+            while let Some(entry) = consumer.pop() {
+                let event: Option<Event> = match (entry.has_event, i % 10 == 0) {
+                    (false, _) => None,
+                    (true, true) => {
+                        let mut bytes: Vec<u8> = Vec::with_capacity(SIMULATED_EVENT_BYTES);
+                        simulated_payload(&mut bytes, i);
+                        Some(Event::Data { bytes })
+                    }
+                    (true, false) => event_rx.recv().ok(),
+                };
+                i = i.saturating_add(1);
+                let mut record: PoHRecord = entry.into_record(event);
+                if let Some(store) = store.as_mut() {
+                    let stored: Result<()> = match record.is_slot_end() {
+                        true => store.detach(slice::from_mut(&mut record)).and_then(|_| store.sync()),
+                        false => store.detach(slice::from_mut(&mut record)),
+                    };
+                    if let Err(e) = stored {
+                        eprintln!("Error: Failed to store the payload of tick {}, stopping delivery: {:#}", record.tick_index, e);
+                        return;
+                    }
+                }
+                // Signing stays off the hashing thread, and happens before sparse collapsing so slot ends keep their signature.
+                if let Some(identity) = identity.as_ref().filter(|_| record.is_slot_end()) {
+                    record.signature = Some(identity.sign_slot(&record));
+                }
+                let records: [Option<PoHRecord>; 2] = match sparse.as_mut() {
+                    Some(sparse) => sparse.push(record),
+                    None => [Some(record), None],
+                };
+                if !records.into_iter().flatten().all(|record| forward(record, &mut witnesses, &tx)) {
+                    return;
+                }
+            }
+            if let Some(Err(e)) = store.as_mut().map(EventStore::sync) {
+                eprintln!("Error: Failed to sync the event store: {:#}", e);
+            }
+            if let Some(record) = sparse.as_mut().and_then(SparseLedger::finish) {
+                forward(record, &mut witnesses, &tx);
+            }
+            for record in witnesses.as_mut().map(WitnessPipeline::finish).unwrap_or_default() {
+                let _ = tx.send(record);
+            }
+        })
+        .expect("Failed to spawn PoH delivery thread.");
+
+    let ingest: JoinHandle<()> = ingest_manager
+        .spawn(move || {
+            let _ = policy_tx.send(applied_policy());
+            // Forward submissions until every submitter is gone or the hashing loop has finished.
+            loop {
+                let received: Result<Event, RecvTimeoutError> = submit_rx.recv_timeout(Duration::from_millis(DEFAULT_MS_PER_TICK));
+                while let Ok((slot, hash)) = slot_rx.try_recv() {
+                    recent.push(slot, hash);
+                }
+                // Referenced events are picked up between plain ones, at most a tick late.
+                while let Ok((event, reply)) = referenced_rx.try_recv() {
+                    if !admit(&recent, event, Some(reply), &ingest_tx) {
+                        return;
+                    }
+                }
+                match received {
+                    Ok(event) => {
+                        if !admit(&recent, event, None, &ingest_tx) {
+                            break;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) if !ingest_status.is_finished() => {}
+                    Err(_) => break,
+                }
+            }
+        })
+        .expect("Failed to spawn PoH ingest thread.");
+    // Store the instances.
+    register(instance);
+    register(delivery);
+    register(ingest);
+
+    let (poh_policy, self_test) = self_test_rx.recv().map_err(|_| anyhow!("PoH thread exited before the self-test finished."))?;
+    let mut threads: Vec<AppliedPolicy> = policy_rx.iter().take(2).collect();
+
+    threads.sort_by(|a, b| a.thread_name.cmp(&b.thread_name));
+    threads.insert(0, poh_policy);
+
+    if let Some(report) = &self_test {
+        if config.enforce_self_test && !report.passes(config.min_headroom) {
+            bail!("Hardware too slow: {}, at least {:+.1}% is required.", report, config.min_headroom * 100.0);
+        }
+    }
+
+    return Ok(PoHService {
+        receiver: rx,
+        events: submit_tx,
+        referenced: referenced_tx,
+        status,
+        self_test,
+        threads,
+    });
+}
+
+// Write the payload of the event simulated at loop index `i`, reusing the capacity of `bytes`.
+fn simulated_payload(bytes: &mut Vec<u8>, i: u64) {
+    bytes.clear();
+    let _ = write!(bytes, "Event at tick {}.", i);
+}
+
+// Hand `event` to the hashing loop unless its reference is unknown or expired, which is reported to `reply`.
+// Plain submissions are checked too, so a copied referenced event cannot skip the check. False once the loop is gone.
+fn admit(recent: &RecentHashes, event: Event, reply: Option<SyncSender<ReferenceReply>>, ingest_tx: &SyncSender<Admission>) -> bool {
+    return match recent.admit(&event) {
+        Ok(referenced_slot) => ingest_tx.send(Admission { event, referenced_slot, reply }).is_ok(),
+        Err(rejection) => {
+            if let Some(reply) = reply {
+                let _ = reply.try_send(Err(rejection));
+            }
+            true
+        }
+    };
 }
 
-fn send_batch(tx: &SyncSender<PoHRecord>, batch: &mut Vec<PoHRecord>) -> Result<(), SendError<PoHRecord>> {
-    for record in batch.drain(..) {
-        tx.send(record)?;
+// Next submitted event and where to report its tick.
+// One that expired while queued is rejected and the tick stays empty, a constant-time check against `slot`.
+fn next_submission(ingest_rx: &Receiver<Admission>, slot: u64) -> (Option<Event>, Option<SyncSender<ReferenceReply>>) {
+    let Ok(admission) = ingest_rx.try_recv() else {
+        return (None, None);
+    };
+    if let Some(rejection) = admission.expired(slot) {
+        if let Some(reply) = admission.reply {
+            let _ = reply.try_send(Err(rejection));
+        }
+        return (None, None);
     }
-    return Ok(());
+    return (Some(admission.event), admission.reply);
+}
+
+// Pass a delivered record on, through the witnesses when there are any.
+fn forward(record: PoHRecord, witnesses: &mut Option<WitnessPipeline>, tx: &SyncSender<PoHRecord>) -> bool {
+    return match witnesses {
+        Some(witnesses) => witnesses.push(record).into_iter().all(|record| tx.send(record).is_ok()),
+        None => tx.send(record).is_ok(),
+    };
+}
+
+fn register(instance: JoinHandle<()>) {
+    let handle: SharedJoinHandle = Arc::new(Mutex::new(Some(instance)));
+    ACTIVE_THREADS.lock().unwrap().push(handle);
 }
 
 pub fn cleanup_threads() {
@@ -1,7 +1,21 @@
-use std::time::Instant;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::time::{Duration, Instant};
+
+// The ring halves keep their fields private to the ring module, nothing else may touch the shared positions.
+pub use crate::ring::{RingBuffer, RingConsumer, RingProducer};
 
 use lib::utils::serialization;
+use thread::native::types::{AppliedPolicy, Config};
 
+use ring::signature::Ed25519KeyPair;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,8 +26,146 @@ pub struct PoHRecord {
     #[serde(with = "serialization")]
     pub hash: [u8; 32],
     pub timestamp_ms: u64,
+    /// Hashes since the previous record, more than one tick's worth when empty ticks were collapsed.
+    #[serde(default = "PoHRecord::default_num_hashes")]
+    pub num_hashes: u64,
+    /// Hash of the event encoding, set when only the hash was mixed into the chain.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serialization::optional")]
+    pub commitment: Option<[u8; 32]>,
+    /// Event payload, may be withheld when `commitment` is set.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub event: Option<Vec<u8>>,
+    pub event: Option<Event>,
+    /// Generator signature over the slot hash, carried by the last record of each slot.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serialization::optional")]
+    pub signature: Option<[u8; 64]>,
+    /// Witness co-signatures over the slot hash, carried by the last record of each slot.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub witnesses: Vec<WitnessSignature>,
+}
+
+/// Signature of a witness over a slot hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WitnessSignature {
+    #[serde(with = "serialization")]
+    pub public_key: [u8; 32],
+    #[serde(with = "serialization::array")]
+    pub signature: [u8; 64],
+}
+
+/// Sent to a witness at every slot end, the records since the previous slot end.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WitnessRequest {
+    /// Generator whose chain this is, the slot end must carry its signature.
+    #[serde(with = "serialization")]
+    pub public_key: [u8; 32],
+    pub segment: Vec<PoHRecord>,
+}
+
+/// Answer of a witness to a [`WitnessRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum WitnessResponse {
+    Signed { signature: WitnessSignature },
+    Rejected { reason: String },
+}
+
+/// Witness state shared by every connection, continuity is checked per generator against the last slot co-signed for it.
+#[derive(Debug, Clone)]
+pub struct Witness {
+    pub identity: Identity,
+    /// Last co-signed slot of every generator, keyed by its public key.
+    pub generators: HashMap<[u8; 32], WitnessedSlot>,
+    /// File the state is saved to before every signature is released, `None` to keep it in memory.
+    pub path: Option<PathBuf>,
+}
+
+/// Last slot a witness co-signed for one generator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WitnessedSlot {
+    #[serde(with = "serialization")]
+    pub public_key: [u8; 32],
+    pub slot_index: u64,
+    pub tick_index: u64,
+    #[serde(with = "serialization")]
+    pub hash: [u8; 32],
+}
+
+/// Generator side of the witness protocol, run on the delivery thread without ever waiting on a witness.
+/// Slots are held back until every witness answered or their deadline passed.
+pub struct WitnessPipeline {
+    /// One connection per configured witness, `None` once it failed.
+    pub connections: Vec<Option<WitnessConnection>>,
+    pub threshold: usize,
+    /// Generator key sent along with every slot.
+    pub public_key: [u8; 32],
+    /// Time a witness gets to answer a slot before it is dropped.
+    pub timeout: Duration,
+    /// Records of the slot in progress.
+    pub segment: Vec<PoHRecord>,
+    /// Slots sent to the witnesses with the time they were sent, oldest first.
+    pub pending: VecDeque<(Vec<PoHRecord>, Instant)>,
+    /// Slots released so far, which is the sequence number of the oldest pending slot.
+    pub released: u64,
+    pub status: Arc<PoHStatus>,
+}
+
+pub struct WitnessConnection {
+    pub writer: UnixStream,
+    /// Answers read on a background thread, in request order.
+    pub responses: Receiver<WitnessResponse>,
+    /// Answers taken so far, which is the sequence number of the slot the next one is for.
+    pub answered: u64,
+}
+
+/// Typed payload recorded into the chain.
+/// Hashing always covers the canonical binary encoding, the serde form is for humans.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// Opaque application data.
+    Data {
+        #[serde(with = "serialization::bytes")]
+        bytes: Vec<u8>,
+    },
+    /// Stamp proving a document with this hash existed.
+    Document {
+        #[serde(with = "serialization")]
+        hash: [u8; 32],
+    },
+    /// Wall-clock reading taken when the event was submitted.
+    WallClock { unix_ms: u64 },
+    /// Marks the start of a slot.
+    SlotMarker { slot: u64 },
+    /// Anchors a block of another chain.
+    CrossChain {
+        chain: String,
+        height: u64,
+        #[serde(with = "serialization")]
+        hash: [u8; 32],
+    },
+    /// Recorded by the PoH loop after a sustained schedule slip.
+    ScheduleSlip { tick: u64, lateness_us: u64 },
+    /// Wraps an event that may only be recorded while the slot closed by `reference` is at most `max_age_slots` slots old.
+    Referenced {
+        #[serde(with = "serialization")]
+        reference: [u8; 32],
+        max_age_slots: u64,
+        event: Box<Event>,
+    },
+}
+
+/// Fixed-size tick produced by the hashing loop.
+/// Event payloads travel out-of-band, only their presence is flagged here.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoHEntry {
+    pub tick_index: u64,
+    pub slot_index: u64,
+    pub epoch_index: u64,
+    pub hash: [u8; 32],
+    pub timestamp_ms: u64,
+    pub has_event: bool,
+    /// Hash of the event encoding when the event was committed by hash only.
+    pub commitment: Option<[u8; 32]>,
 }
 
 pub struct PoH {
@@ -22,4 +174,575 @@ pub struct PoH {
     pub slot_count: u64,
     pub epoch_count: u64,
     pub start_time: Instant,
+    /// Reused for event encodings so ticks with events don't allocate.
+    pub event_buffer: Vec<u8>,
+}
+
+/// Ed25519 keypair a PoH generator signs its slot hashes with.
+#[derive(Clone)]
+pub struct Identity {
+    pub keypair: Arc<Ed25519KeyPair>,
+    /// PKCS#8 document the keypair was loaded from, this is what gets saved.
+    pub pkcs8: Arc<[u8]>,
+}
+
+/// Collapses runs of empty ticks into single records, see [`PoHConfig::sparse`].
+/// Event ticks and the last tick of every slot are always emitted.
+#[derive(Debug, Clone, Default)]
+pub struct SparseLedger {
+    /// Empty ticks merged so far, not emitted yet.
+    pub pending: Option<PoHRecord>,
+}
+
+/// Payloads of hash-committed events, kept apart from the chain so they can be withheld or pruned.
+#[derive(Debug, Default)]
+pub struct EventStore {
+    /// Payloads keyed by the commitment recorded in the chain.
+    pub events: HashMap<[u8; 32], StoredEvent>,
+    /// Sidecar file every payload is appended to as a JSON line, `None` for a store kept in memory.
+    pub path: Option<PathBuf>,
+    pub file: Option<BufWriter<File>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredEvent {
+    /// Latest tick that committed this payload.
+    pub tick_index: u64,
+    pub event: Event,
+}
+
+/// Identifies a fork by the slot and hash of its latest record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ForkKey {
+    pub slot: u64,
+    pub hash: [u8; 32],
+}
+
+/// How the heaviest fork is chosen among competing continuations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForkChoice {
+    /// Most ticks since the seed.
+    Longest,
+    /// Most events recorded since the root.
+    MostEvents,
+    /// Largest sum of externally assigned weights since the root.
+    Weighted,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForkNode {
+    /// Latest record of the segment this node stands for.
+    pub record: PoHRecord,
+    pub parent: Option<ForkKey>,
+    pub children: Vec<ForkKey>,
+    /// Events recorded from the root up to and including this node.
+    pub event_count: u64,
+    /// Externally assigned weight, e.g. stake voting for this fork.
+    pub weight: u64,
+}
+
+/// Tree of competing PoH continuations branching from a common root.
+#[derive(Debug, Clone)]
+pub struct ForkTree {
+    pub nodes: HashMap<ForkKey, ForkNode>,
+    pub root: ForkKey,
+    pub choice: ForkChoice,
+}
+
+/// What the PoH loop does once ticks keep finishing behind schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlipPolicy {
+    /// Print a warning and keep catching up.
+    Warn,
+    /// Record a slip marker event into the chain and keep catching up.
+    Marker,
+    /// Stop generating ticks.
+    Abort,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoHConfig {
+    pub slip_policy: SlipPolicy,
+    /// Lateness past a tick deadline that counts as slipping.
+    pub slip_threshold_us: u64,
+    /// Consecutive slipping ticks before `slip_policy` is applied.
+    pub slip_tolerance_ticks: u64,
+    /// Length of the startup hash-rate self-test, 0 skips it.
+    pub self_test_ms: u64,
+    /// Minimum headroom the self-test must show, as a fraction of the required hash rate.
+    pub min_headroom: f64,
+    /// Refuse to start when the self-test shows less than `min_headroom`, the binary turns this on unless `--allow-slow-hardware` is given.
+    pub enforce_self_test: bool,
+    /// Mix only the hash of each event into the chain, see [`EventStore`].
+    pub commit_events: bool,
+    /// Sidecar file the payloads of committed events are moved into before records are delivered, required with `commit_events`.
+    pub event_store: Option<PathBuf>,
+    /// Deliver records only at events and slot boundaries, see [`SparseLedger`].
+    pub sparse: bool,
+    /// Sign the last hash of every slot when set.
+    #[serde(skip)]
+    pub identity: Option<Identity>,
+    /// Keypair file to load the identity from when `identity` is not set.
+    pub identity_path: Option<PathBuf>,
+    /// Unix sockets of the witnesses co-signing every slot.
+    pub witnesses: Vec<PathBuf>,
+    /// Witness signatures needed for a slot to count as finalised.
+    pub witness_threshold: usize,
+    /// Time to wait for a witness response before the witness is dropped.
+    pub witness_timeout_ms: u64,
+    /// Slot hashes kept for events referencing a recent hash, see [`Event::Referenced`].
+    pub recent_slot_hashes: usize,
+    /// Thread settings for the hashing loop, ideally a dedicated isolated core with raised priority.
+    pub poh_thread: Config,
+    /// Thread settings for rebuilding and delivering records.
+    pub delivery_thread: Config,
+    /// Thread settings for accepting submitted events.
+    pub ingest_thread: Config,
+}
+
+/// Result of the startup hash-rate self-test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    pub algorithm: String,
+    pub hashes: u64,
+    pub elapsed_us: u64,
+    pub hashes_per_second: f64,
+    pub required_hashes_per_second: f64,
+    /// Spare hash rate as a fraction of the required rate, negative when too slow.
+    pub headroom: f64,
+}
+
+/// Live counters published by a running PoH service.
+#[derive(Debug, Default)]
+pub struct PoHStatus {
+    /// Number of ticks generated so far.
+    pub ticks: AtomicU64,
+    /// Lateness of the most recent tick past its scheduled deadline.
+    pub last_lateness_us: AtomicU64,
+    /// Worst lateness observed.
+    pub max_lateness_us: AtomicU64,
+    /// Sum of lateness over every tick.
+    pub schedule_error_us: AtomicU64,
+    /// Number of ticks that finished past the slip threshold.
+    pub late_ticks: AtomicU64,
+    /// Number of times the slip policy has been applied.
+    pub slips: AtomicU64,
+    /// Set when the loop stopped because of [`SlipPolicy::Abort`].
+    pub aborted: AtomicBool,
+    /// Set once the hashing loop has stopped for any reason.
+    pub finished: AtomicBool,
+    /// Number of slots signed by at least the witness threshold.
+    pub finalised_slots: AtomicU64,
+}
+
+/// Handle to a running PoH service.
+pub struct PoHService {
+    pub receiver: Receiver<PoHRecord>,
+    /// Submit events, each one is recorded in the next tick without another event.
+    pub events: SyncSender<Event>,
+    /// Submit events that expire some slots after a recent slot hash, see [`PoHService::submit_referenced`].
+    pub referenced: SyncSender<ReferencedSubmission>,
+    pub status: Arc<PoHStatus>,
+    pub self_test: Option<SelfTestReport>,
+    /// Policies that took effect on the hashing, delivery and ingest threads, in that order.
+    pub threads: Vec<AppliedPolicy>,
+}
+
+/// Aligns the wrapped value to its own cache line to avoid false sharing.
+#[repr(align(64))]
+#[derive(Debug, Default)]
+pub struct CachePadded<T>(pub T);
+
+/// Header at the start of every binary ledger stream.
+/// Only the chain parameters needed to replay and read the records are stored, not the whole [`PoHConfig`].
+/// Runtime settings such as the slip policy, threads and witness sockets are left out,
+/// verifiers bring the generator key and witness set they trust, see [`PoH::verify_witnessed_records`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerHeader {
+    pub version: u16,
+    /// Hash algorithm the chain was generated with, see `lib::utils::hash`.
+    pub algorithm: u8,
+    pub hashes_per_tick: u64,
+    pub ticks_per_slot: u64,
+    pub slots_per_epoch: u64,
+    /// Runs of empty ticks are collapsed, see [`PoHConfig::sparse`].
+    pub sparse: bool,
+    /// Events are mixed in by hash only, see [`PoHConfig::commit_events`].
+    pub commit_events: bool,
+}
+
+/// Streaming writer of the binary ledger format.
+pub struct RecordEncoder<W: Write> {
+    pub writer: W,
+    /// Reused so each record is written with a single call.
+    pub buffer: Vec<u8>,
+}
+
+/// Streaming reader of the binary ledger format.
+pub struct RecordDecoder<R: Read> {
+    pub reader: R,
+    pub header: LedgerHeader,
+}
+
+/// Record borrowed in place from an encoded ledger, fields are read without copying.
+#[derive(Debug, Clone, Copy)]
+pub struct RecordView<'a> {
+    pub tick_index: u64,
+    pub slot_index: u64,
+    pub epoch_index: u64,
+    pub hash: &'a [u8; 32],
+    pub timestamp_ms: u64,
+    pub num_hashes: u64,
+    pub commitment: Option<&'a [u8; 32]>,
+    pub signature: Option<&'a [u8; 64]>,
+    /// Encoded witness signatures, public key then signature for each.
+    pub witnesses: &'a [u8],
+    /// Canonical encoding of the attached event.
+    pub event: Option<&'a [u8]>,
+}
+
+/// Iterates the records of an encoded ledger as [`RecordView`]s.
+pub struct RecordViews<'a> {
+    /// Bytes not parsed yet.
+    pub rest: &'a [u8],
+}
+
+/// Binary ledger file mapped into memory, so verifying it never loads it onto the heap.
+pub struct MappedLedger {
+    pub map: memmap2::Mmap,
+    pub header: LedgerHeader,
+    /// Offset of the first record, right after the header.
+    pub start: usize,
+}
+
+/// When the on-disk ledger forces appended records to stable storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FsyncPolicy {
+    /// Leave it to the operating system, a crash can lose recent records.
+    Never,
+    /// After the last record of every slot.
+    Slot,
+    /// After every record.
+    Record,
+}
+
+/// Which old segments a ledger deletes, the segment being appended to is always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RetentionPolicy {
+    /// Never delete anything.
+    KeepAll,
+    /// Keep the last N epochs, the current one included.
+    Epochs(u64),
+    /// Keep the newest segments fitting in N bytes.
+    Bytes(u64),
+    /// Keep records stamped at or after this many milliseconds since the chain started.
+    After(u64),
+}
+
+/// Trusted starting point left where pruning cut the ledger, the remaining records verify from it instead of the seed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Last deleted segment.
+    pub segment: u64,
+    /// Position of the last deleted record, the first kept record extends its hash.
+    pub tick_index: u64,
+    pub slot_index: u64,
+    pub epoch_index: u64,
+    #[serde(with = "serialization")]
+    pub hash: [u8; 32],
+    pub timestamp_ms: u64,
+    /// Digest of the checkpoint this one replaces, zero for the first.
+    #[serde(with = "serialization")]
+    pub previous: [u8; 32],
+    /// Generator key and its signature over the digest, when the generator has an identity.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serialization::optional")]
+    pub public_key: Option<[u8; 32]>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serialization::optional")]
+    pub signature: Option<[u8; 64]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LedgerConfig {
+    /// Size after which the next slot starts a new segment file.
+    pub segment_bytes: u64,
+    pub fsync: FsyncPolicy,
+    /// Compress segments with zstd once they are full, the segment being appended to stays raw.
+    pub compress: bool,
+    pub compression_level: i32,
+    pub retention: RetentionPolicy,
+}
+
+/// Totals over the segments compressed by a ledger.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompressionStats {
+    pub segments: u64,
+    pub raw_bytes: u64,
+    pub compressed_bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// One zstd frame of a compressed segment, listed in the seek table at its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekEntry {
+    /// Slot whose records the frame holds.
+    pub slot: u64,
+    /// Byte offset of the frame in the compressed file.
+    pub offset: u64,
+    pub size: u32,
+}
+
+/// Where a slot starts in the ledger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotLocation {
+    pub segment: u64,
+    /// Byte offset of the first record of the slot within its segment.
+    pub offset: u64,
+}
+
+/// Append-only on-disk ledger made of rolling segment files, each starting with a [`LedgerHeader`].
+pub struct Ledger {
+    pub dir: PathBuf,
+    pub config: LedgerConfig,
+    /// Written into the header of every new segment.
+    pub poh_config: PoHConfig,
+    /// Segment being appended to.
+    pub segment: u64,
+    pub writer: BufWriter<File>,
+    /// Bytes in the current segment, header included.
+    pub offset: u64,
+    /// Slot index, mirrored in an append-only index file.
+    pub index: BTreeMap<u64, SlotLocation>,
+    pub index_file: File,
+    /// Event hash and tick of every event record, appended as they arrive.
+    pub events_file: File,
+    /// Last appended record, the point a restarted generator continues from.
+    pub last: Option<PoHRecord>,
+    /// Left by the last prune, `None` while the ledger still starts at the seed.
+    pub checkpoint: Option<Checkpoint>,
+    /// Reused to encode each record.
+    pub buffer: Vec<u8>,
+    pub compression: CompressionStats,
+}
+
+/// Streams every record of a ledger in order, raw and compressed segments alike.
+pub struct LedgerRecords {
+    /// Segments not opened yet.
+    pub segments: VecDeque<PathBuf>,
+    pub current: Option<RecordDecoder<Box<dyn Read>>>,
+}
+
+/// Read-only queries over a ledger directory, usable while a [`Ledger`] appends to it.
+pub struct LedgerReader {
+    pub dir: PathBuf,
+    /// Where each slot starts, loaded from the slot index when opened.
+    pub index: BTreeMap<u64, SlotLocation>,
+    /// Ticks carrying each event hash, loaded from the event index when opened.
+    pub events: HashMap<[u8; 32], Vec<u64>>,
+    pub checkpoint: Option<Checkpoint>,
+}
+
+/// Record found by a [`LedgerReader`] with what is needed to check it against the chain.
+#[derive(Debug, Clone)]
+pub struct LedgerEntry {
+    pub record: PoHRecord,
+    /// Tick and hash of the record before it, the checkpoint for the first record of a pruned ledger and `None` for that of a full one.
+    pub anchor: Option<(u64, [u8; 32])>,
+}
+
+/// Records of a [`LedgerReader`] range query, in chain order.
+pub struct LedgerQuery {
+    pub records: LedgerRecords,
+    /// Tick and hash of the last record read, the anchor of the next one.
+    pub previous: Option<(u64, [u8; 32])>,
+    /// Where a record falls relative to the queried range.
+    pub position: Box<dyn Fn(&PoHRecord) -> Ordering>,
+}
+
+/// Destination for delivered records, one run can feed several at once.
+pub trait RecordSink {
+    fn write_record(&mut self, record: &PoHRecord) -> anyhow::Result<()>;
+    /// Push buffered records out.
+    fn flush(&mut self) -> anyhow::Result<()>;
+    /// Called once after the last record.
+    fn finish(&mut self) -> anyhow::Result<()>;
+    /// Line for the end-of-run summary, if the sink has anything to report.
+    fn summary(&self) -> Option<String> {
+        return None;
+    }
+}
+
+/// Live progress and the latest record, drawn in place on a terminal.
+pub struct TerminalSink {
+    pub out: Box<dyn Write>,
+    /// Row of the progress line, the record is drawn below it.
+    pub top: u16,
+    pub target_ticks: u64,
+    pub status: Arc<PoHStatus>,
+    /// Ticks already in the chain when the run started.
+    pub first_tick: u64,
+    pub ticks: u64,
+    pub last_update: Instant,
+    pub last_tick_count: u64,
+}
+
+/// Collects every record and writes them as one JSON array at the end of the run.
+pub struct JsonSink {
+    pub filename: String,
+    pub records: Vec<PoHRecord>,
+}
+
+/// One CSV row per record below a header line.
+pub struct CsvSink<W: Write> {
+    pub writer: BufWriter<W>,
+}
+
+/// Records, events, slots and run metadata in a SQLite database for ad-hoc analysis.
+/// Records are inserted in batched transactions.
+pub struct SqliteLedger {
+    pub connection: rusqlite::Connection,
+    /// Run new records belong to and queries look at, the latest one when opened.
+    pub run_id: Option<i64>,
+    /// Records waiting for the next transaction.
+    pub pending: Vec<PoHRecord>,
+    pub batch_size: usize,
+}
+
+/// Run of chain between two records, enough to recompute the hash at its end without the records themselves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    /// Event data mixed in before hashing, the commitment or the encoding of an event recorded along the way.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serialization::optional_bytes")]
+    pub data: Option<Vec<u8>>,
+    pub num_hashes: u64,
+}
+
+/// Self-contained proof that an event was recorded at a tick, checked with [`proof::verify_proof`](crate::proof::verify_proof).
+/// The chain runs from the record before the event up to the signed hash closing its slot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InclusionProof {
+    /// Hash algorithm of the chain, see `lib::utils::hash`.
+    pub algorithm: u8,
+    /// Tick and hash of the record before the event.
+    pub prev_tick: u64,
+    #[serde(with = "serialization")]
+    pub prev_hash: [u8; 32],
+    /// Record the event was mixed into.
+    pub record: PoHRecord,
+    /// Chain from the event record to the slot end.
+    pub steps: Vec<ProofStep>,
+    /// Signed last record of the slot, `None` when the event record closes the slot itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot_end: Option<PoHRecord>,
+}
+
+/// Proof that one record came before another with at least some wall time between them, checked with [`proof::verify_order`](crate::proof::verify_order).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderingProof {
+    /// Hash algorithm of the chain, see `lib::utils::hash`.
+    pub algorithm: u8,
+    /// Tick and hash of the record before `first`, binding its event to the chain.
+    pub prev_tick: u64,
+    #[serde(with = "serialization")]
+    pub prev_hash: [u8; 32],
+    pub first: PoHRecord,
+    /// Chain from `first` to `second`.
+    pub steps: Vec<ProofStep>,
+    pub second: PoHRecord,
+    /// Declared upper bound on the generator hash rate, in hashes per second.
+    pub max_hash_rate: u64,
+    /// Claimed minimum time between the records at `max_hash_rate`.
+    pub min_elapsed_us: u64,
+}
+
+/// Standalone timestamp receipt for a digest, verifiable without the ledger and upgradable to newer signed slot hashes.
+///
+/// Stored as JSON or in a binary layout, integers little-endian:
+/// - `POHR` magic and u16 format version,
+/// - the ledger header with the algorithm and chain parameters, as at the start of a binary ledger,
+/// - the stamped digest and the generator public key, 32 bytes each,
+/// - the inclusion proof: u64 tick and 32-byte hash of the record before the stamp, the stamped record, its steps,
+///   then a u8 flag followed by the signed slot end record when set,
+/// - a u32 upgrade count, each upgrade its steps then its anchor record.
+///
+/// Records use the binary ledger encoding. Steps are a u32 count, then per step a u64 hash count and a u8 flag
+/// followed by the u32 length-prefixed data when set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Receipt {
+    /// Format version, bumped on any change to the layout.
+    pub version: u16,
+    /// Digest stamped as an [`Event::Document`].
+    #[serde(with = "serialization")]
+    pub digest: [u8; 32],
+    /// Algorithm, chain parameters and config of the generator.
+    pub header: LedgerHeader,
+    /// Generator key the slot hashes are signed with.
+    #[serde(with = "serialization")]
+    pub public_key: [u8; 32],
+    /// Inclusion of the digest up to the first signed slot hash after it.
+    pub proof: InclusionProof,
+    /// Chain segments appended later, each up to a newer signed slot hash.
+    #[serde(default)]
+    pub upgrades: Vec<ReceiptSegment>,
+}
+
+/// Chain from the last anchor of a [`Receipt`] to a newer signed slot hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptSegment {
+    pub steps: Vec<ProofStep>,
+    /// Signed last record of a slot, with any witness co-signatures.
+    pub anchor: PoHRecord,
+}
+
+/// Random value of a slot, derived from the hash closing it, see the `beacon` module.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaconValue {
+    pub slot_index: u64,
+    /// Last tick of the slot, whose hash the value is derived from.
+    pub tick_index: u64,
+    #[serde(with = "serialization")]
+    pub slot_hash: [u8; 32],
+    /// Generator signature over the slot hash, mixed in when the value is derived VRF-style.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serialization::optional")]
+    pub signature: Option<[u8; 64]>,
+    #[serde(with = "serialization")]
+    pub randomness: [u8; 32],
+}
+
+/// Event handed from the ingest thread to the hashing loop.
+#[derive(Debug)]
+pub struct Admission {
+    pub event: Event,
+    /// Slot closed by the hash an [`Event::Referenced`] refers to, resolved by the ingest thread.
+    pub referenced_slot: Option<u64>,
+    /// Where to report the tick, for events submitted with [`PoHService::submit_referenced`].
+    pub reply: Option<SyncSender<ReferenceReply>>,
+}
+
+/// Answer to an [`Event::Referenced`], the tick it was recorded at or why it was rejected.
+pub type ReferenceReply = Result<u64, EventRejection>;
+
+/// Event from [`PoHService::submit_referenced`], already wrapped in an [`Event::Referenced`], and where to answer it.
+pub type ReferencedSubmission = (Event, SyncSender<ReferenceReply>);
+
+/// Slot index and the hash closing it.
+pub type SlotHash = (u64, [u8; 32]);
+
+/// Why an [`Event::Referenced`] was not recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventRejection {
+    /// Not one of the recent slot hashes, it aged out of the ring or never closed a slot of this chain.
+    UnknownReference,
+    /// The referenced `slot` is `age` slots old, more than the event allows.
+    Expired { slot: u64, age: u64, max_age_slots: u64 },
+    /// The wrapped event references a slot hash itself, only one reference is allowed.
+    Nested,
+}
+
+/// Ring of the last slot hashes of the chain, oldest first, to check event references against.
+#[derive(Debug, Clone)]
+pub struct RecentHashes {
+    pub hashes: VecDeque<(u64, [u8; 32])>,
+    pub capacity: usize,
 }
@@ -0,0 +1,360 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread as std_thread;
+use std::time::{Duration, Instant};
+
+use crate::types::{Identity, PoH, PoHRecord, PoHStatus, Witness, WitnessConnection, WitnessPipeline, WitnessRequest, WitnessResponse, WitnessSignature, WitnessedSlot};
+
+use lib::DEFAULT_MS_PER_TICK;
+
+use anyhow::{Context, Result, anyhow, bail};
+use ring::signature::{ED25519, UnparsedPublicKey};
+
+impl WitnessSignature {
+    /// Check the signature against the slot `record` closes.
+    pub fn verify(&self, record: &PoHRecord) -> bool {
+        let key: UnparsedPublicKey<&[u8; 32]> = UnparsedPublicKey::new(&ED25519, &self.public_key);
+        return key.verify(&record.slot_message(), &self.signature).is_ok();
+    }
+}
+
+impl Witness {
+    /// Witness keeping its state in memory only.
+    pub fn new(identity: Identity) -> Self {
+        return Self {
+            identity,
+            generators: HashMap::new(),
+            path: None,
+        };
+    }
+
+    /// Witness whose state is saved to `path`, continuing from it if it exists.
+    pub fn open(identity: Identity, path: &Path) -> Result<Self> {
+        let mut witness: Self = Self::new(identity);
+        match fs::read(path) {
+            Ok(bytes) => {
+                let slots: Vec<WitnessedSlot> = serde_json::from_slice(&bytes).with_context(|| format!("Corrupted witness state {}", path.display()))?;
+                witness.generators = slots.into_iter().map(|slot| (slot.public_key, slot)).collect();
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        witness.path = Some(path.to_path_buf());
+        return Ok(witness);
+    }
+
+    /// Check `segment` of the generator with `public_key` and co-sign the slot it closes.
+    /// The first slot seen from a generator is trusted as its starting point, every later one must extend the last co-signed slot.
+    pub fn witness(&mut self, public_key: &[u8; 32], segment: &[PoHRecord]) -> Result<WitnessSignature> {
+        let last: Option<WitnessedSlot> = self.generators.get(public_key).cloned();
+        Self::check(public_key, segment, last.as_ref())?;
+        return self.sign(public_key, segment, last.as_ref());
+    }
+
+    /// Check that `segment` is signed by `public_key`, ends a slot and extends `last`, without touching any state.
+    pub fn check(public_key: &[u8; 32], segment: &[PoHRecord], last: Option<&WitnessedSlot>) -> Result<()> {
+        let (first, end): (&PoHRecord, &PoHRecord) = match (segment.first(), segment.last()) {
+            (Some(first), Some(end)) => (first, end),
+            _ => bail!("Empty segment."),
+        };
+        if !end.is_slot_end() {
+            bail!("Segment ends at tick {}, which does not close a slot.", end.tick_index);
+        }
+        let signed: bool = end
+            .signature
+            .is_some_and(|signature| UnparsedPublicKey::new(&ED25519, public_key).verify(&end.slot_message(), &signature).is_ok());
+        if !signed {
+            bail!("Slot {} is not signed by the generator.", end.slot_index);
+        }
+        // Cheap index checks first, a rewound or skipping generator is refused before any hashing.
+        if let Some(last) = last {
+            if end.slot_index <= last.slot_index {
+                bail!("Slot {} does not come after the last co-signed slot {}.", end.slot_index, last.slot_index);
+            }
+            if first.tick_index != last.tick_index.saturating_add(1) && first.slot_index != last.slot_index.saturating_add(1) {
+                bail!(
+                    "Segment starting at tick {} does not continue the last co-signed slot {}.",
+                    first.tick_index,
+                    last.slot_index
+                );
+            }
+            if !first.follows(&last.hash, last.tick_index) {
+                bail!("Segment ending at slot {} does not continue the last co-signed slot.", end.slot_index);
+            }
+        }
+        if !PoH::verify_records(segment) {
+            bail!("Segment ending at slot {} does not verify.", end.slot_index);
+        }
+        return Ok(());
+    }
+
+    /// Record the slot `segment` closes as the last one co-signed for `public_key` and sign it.
+    /// Refused when the state moved on from `last` since the segment was checked, e.g. through another connection.
+    pub fn sign(&mut self, public_key: &[u8; 32], segment: &[PoHRecord], last: Option<&WitnessedSlot>) -> Result<WitnessSignature> {
+        let end: &PoHRecord = segment.last().ok_or_else(|| anyhow!("Empty segment."))?;
+        if self.generators.get(public_key) != last {
+            bail!(
+                "Slot {} was checked against a stale state, another connection co-signed in the meantime.",
+                end.slot_index
+            );
+        }
+        self.generators.insert(
+            *public_key,
+            WitnessedSlot {
+                public_key: *public_key,
+                slot_index: end.slot_index,
+                tick_index: end.tick_index,
+                hash: end.hash,
+            },
+        );
+        // Durable before the signature leaves, a restarted witness never co-signs the same slot height twice.
+        self.save()?;
+
+        return Ok(WitnessSignature {
+            public_key: self.identity.public_key(),
+            signature: self.identity.sign_slot(end),
+        });
+    }
+
+    // Replace the state file, aside first so a crash leaves either the old or the new state.
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let slots: Vec<&WitnessedSlot> = self.generators.values().collect();
+        let temporary: PathBuf = path.with_extension("tmp");
+        let mut file: File = File::create(&temporary)?;
+        file.write_all(&serde_json::to_vec_pretty(&slots)?)?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)?;
+        return Ok(());
+    }
+}
+
+/// Answer witness requests on `listener` until it fails, each connection is handled on its own thread.
+/// Every connection shares the state of `witness`, so reconnecting never resets the continuity of a generator.
+pub fn serve(listener: UnixListener, witness: Witness) -> Result<()> {
+    let witness: Arc<Mutex<Witness>> = Arc::new(Mutex::new(witness));
+    for stream in listener.incoming() {
+        let stream: UnixStream = stream?;
+        let witness: Arc<Mutex<Witness>> = witness.clone();
+        std_thread::spawn(move || {
+            if let Err(e) = handle(stream, &witness) {
+                eprintln!("Witness connection closed: {}", e);
+            }
+        });
+    }
+    return Ok(());
+}
+
+fn handle(stream: UnixStream, witness: &Mutex<Witness>) -> Result<()> {
+    let mut writer: UnixStream = stream.try_clone()?;
+    // One JSON document per line in both directions.
+    for line in BufReader::new(stream).lines() {
+        let request: WitnessRequest = serde_json::from_str(&line?)?;
+        // Verify without holding the lock, then sign only if the generator state did not move meanwhile.
+        let last: Option<WitnessedSlot> = lock(witness)?.generators.get(&request.public_key).cloned();
+        let signed: Result<WitnessSignature> =
+            Witness::check(&request.public_key, &request.segment, last.as_ref()).and_then(|_| lock(witness)?.sign(&request.public_key, &request.segment, last.as_ref()));
+        let response: WitnessResponse = match signed {
+            Ok(signature) => WitnessResponse::Signed { signature },
+            Err(e) => WitnessResponse::Rejected { reason: e.to_string() },
+        };
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+    }
+    return Ok(());
+}
+
+fn lock(witness: &Mutex<Witness>) -> Result<MutexGuard<'_, Witness>> {
+    return witness.lock().map_err(|_| anyhow!("Witness state is poisoned."));
+}
+
+impl WitnessConnection {
+    /// Connect to the witness at `path`, its answers are read on a background thread so waiting never blocks the caller.
+    pub fn connect(path: &Path) -> Result<Self> {
+        let writer: UnixStream = UnixStream::connect(path).map_err(|e| anyhow!("Failed to connect to witness {}: {}", path.display(), e))?;
+        // A witness that stops reading fills the socket, give up on it rather than stall delivery.
+        writer.set_write_timeout(Some(Duration::from_millis(DEFAULT_MS_PER_TICK)))?;
+        let reader: BufReader<UnixStream> = BufReader::new(writer.try_clone()?);
+        let (responses_tx, responses): (Sender<WitnessResponse>, Receiver<WitnessResponse>) = channel();
+        std_thread::spawn(move || {
+            for line in reader.lines() {
+                let response: Option<WitnessResponse> = line.ok().and_then(|line| serde_json::from_str(&line).ok());
+                // A garbled answer ends the thread, which drops the witness.
+                if response.is_none_or(|response| responses_tx.send(response).is_err()) {
+                    return;
+                }
+            }
+        });
+        return Ok(Self { writer, responses, answered: 0 });
+    }
+
+    fn send(&mut self, request: &WitnessRequest) -> Result<()> {
+        writeln!(self.writer, "{}", serde_json::to_string(request)?)?;
+        return Ok(());
+    }
+}
+
+impl Drop for WitnessConnection {
+    fn drop(&mut self) {
+        // Unblocks the reader thread.
+        let _ = self.writer.shutdown(Shutdown::Both);
+    }
+}
+
+impl WitnessPipeline {
+    /// Connect to every witness, unreachable ones are skipped as long as `threshold` can still be met.
+    pub fn connect(witnesses: &[PathBuf], threshold: usize, timeout_ms: u64, public_key: [u8; 32], status: Arc<PoHStatus>) -> Result<Self> {
+        let connections: Vec<Option<WitnessConnection>> = witnesses
+            .iter()
+            .map(|path| WitnessConnection::connect(path).inspect_err(|e| eprintln!("Warning: {}", e)).ok())
+            .collect();
+        let connected: usize = connections.iter().flatten().count();
+        if connected < threshold {
+            bail!("Only {} of {} witnesses reachable, {} are required.", connected, witnesses.len(), threshold);
+        }
+
+        return Ok(Self {
+            connections,
+            threshold,
+            public_key,
+            timeout: Duration::from_millis(timeout_ms),
+            segment: Vec::new(),
+            pending: VecDeque::new(),
+            released: 0,
+            status,
+        });
+    }
+
+    /// Feed the next record, never waits for a witness.
+    ///
+    /// # Returns
+    /// Records whose slot has been through the witnesses, ready to deliver.
+    pub fn push(&mut self, record: PoHRecord) -> Vec<PoHRecord> {
+        let slot_end: bool = record.is_slot_end();
+        self.segment.push(record);
+        if slot_end {
+            let request: WitnessRequest = WitnessRequest {
+                public_key: self.public_key,
+                segment: std::mem::take(&mut self.segment),
+            };
+            for connection in self.connections.iter_mut() {
+                if connection.as_mut().is_some_and(|c| c.send(&request).is_err()) {
+                    *connection = None;
+                }
+            }
+            self.pending.push_back((request.segment, Instant::now()));
+        }
+        return self.collect(false);
+    }
+
+    /// Wait for every pending slot to be answered or time out, then release the trailing records of the unfinished slot.
+    pub fn finish(&mut self) -> Vec<PoHRecord> {
+        let mut ready: Vec<PoHRecord> = self.collect(true);
+        ready.append(&mut self.segment);
+        return ready;
+    }
+
+    // Attach the answers received so far and release the oldest slots that every witness answered or whose deadline passed.
+    // With `wait`, blocks until nothing is pending anymore.
+    fn collect(&mut self, wait: bool) -> Vec<PoHRecord> {
+        let mut ready: Vec<PoHRecord> = Vec::new();
+        loop {
+            self.receive();
+            let Some((_, sent)) = self.pending.front() else {
+                return ready;
+            };
+            let answered: bool = self.connections.iter().flatten().all(|c| c.answered > self.released);
+            let left: Duration = self.timeout.saturating_sub(sent.elapsed());
+            if !answered && !left.is_zero() {
+                if !wait {
+                    return ready;
+                }
+                std_thread::sleep(left.min(Duration::from_millis(DEFAULT_MS_PER_TICK)));
+                continue;
+            }
+
+            let Some((mut segment, _)) = self.pending.pop_front() else {
+                return ready;
+            };
+            // Witnesses still silent on a slot past its deadline are dropped, their later answers could no longer be matched.
+            for connection in self.connections.iter_mut() {
+                if connection.as_ref().is_some_and(|c| c.answered <= self.released) {
+                    eprintln!("Warning: dropping witness that did not answer slot {} in time.", self.released);
+                    *connection = None;
+                }
+            }
+            if segment.last().is_some_and(|end| end.witnesses.len() >= self.threshold) {
+                self.status.finalised_slots.fetch_add(1, Ordering::Relaxed);
+            }
+            self.released = self.released.saturating_add(1);
+            ready.append(&mut segment);
+        }
+    }
+
+    // Take every answer waiting on the connections and attach the valid signatures to their slots.
+    fn receive(&mut self) {
+        for connection in self.connections.iter_mut() {
+            let Some(active) = connection.as_mut() else {
+                continue;
+            };
+            loop {
+                let response: WitnessResponse = match active.responses.try_recv() {
+                    Ok(response) => response,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        eprintln!("Warning: dropping witness that closed the connection.");
+                        *connection = None;
+                        break;
+                    }
+                };
+                let sequence: u64 = active.answered;
+                active.answered = active.answered.saturating_add(1);
+                let Some(end) = usize::try_from(sequence.saturating_sub(self.released))
+                    .ok()
+                    .and_then(|index| self.pending.get_mut(index))
+                    .and_then(|(segment, _)| segment.last_mut())
+                else {
+                    continue;
+                };
+                match response {
+                    WitnessResponse::Signed { signature } if signature.verify(end) => end.witnesses.push(signature),
+                    WitnessResponse::Signed { .. } => eprintln!("Warning: witness returned an invalid signature for slot {}.", end.slot_index),
+                    WitnessResponse::Rejected { reason } => eprintln!("Warning: witness rejected slot {}: {}", end.slot_index, reason),
+                }
+            }
+        }
+    }
+}
+
+impl PoH {
+    /// Verify the chain and require every slot in it to be co-signed by at least `threshold` of `witnesses`.
+    /// Signatures from keys outside the set are ignored, invalid ones from keys in the set are not.
+    /// A `threshold` of 0 is refused, it would accept slots nobody co-signed.
+    pub fn verify_witnessed_records(records: &[PoHRecord], witnesses: &[[u8; 32]], threshold: usize) -> bool {
+        if threshold == 0 || !Self::verify_records(records) {
+            return false;
+        }
+
+        return records.iter().all(|record| {
+            if !record.is_slot_end() {
+                return record.witnesses.is_empty();
+            }
+            let mut signers: HashSet<[u8; 32]> = HashSet::new();
+            for signature in record.witnesses.iter().filter(|s| witnesses.contains(&s.public_key)) {
+                if !signature.verify(record) {
+                    return false;
+                }
+                signers.insert(signature.public_key);
+            }
+            signers.len() >= threshold
+        });
+    }
+}
@@ -1,13 +1,101 @@
 #[cfg(test)]
 mod operations {
+    use std::env;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
     use std::sync::mpsc::Receiver;
     use std::time::{Duration, Instant};
 
     use lib::utils::hash;
-    use lib::{DEFAULT_HASHES_PER_TICK, DEFAULT_MS_PER_TICK, DEFAULT_SLOTS_PER_EPOCH, DEFAULT_TICKS_PER_SLOT, DEFAULT_US_PER_TICK};
+    use lib::utils::json::JsonLinesWriter;
+    use lib::{DEFAULT_HASHES_PER_SECOND, DEFAULT_HASHES_PER_TICK, DEFAULT_MS_PER_TICK, DEFAULT_SLOTS_PER_EPOCH, DEFAULT_TICKS_PER_SLOT, DEFAULT_US_PER_TICK};
 
+    use poh::beacon::{randomness_at, verify_randomness};
+    use poh::preflight::measure_hash_rate;
+    use poh::proof::{prove, prove_order, verify_order, verify_proof};
     use poh::thread;
-    use poh::types::{PoH, PoHRecord};
+    use poh::types::{
+        BeaconValue, Checkpoint, CsvSink, Event, EventRejection, EventStore, ForkChoice, ForkKey, ForkTree, FsyncPolicy, Identity, InclusionProof, Ledger, LedgerConfig,
+        LedgerEntry, LedgerHeader, LedgerReader, MappedLedger, OrderingProof, PoH, PoHConfig, PoHEntry, PoHRecord, PoHService, PoHStatus, Receipt, RecentHashes,
+        RecordDecoder, RecordEncoder, RecordSink, RecordView, RetentionPolicy, RingBuffer, RingConsumer, RingProducer, SelfTestReport, SlipPolicy, SparseLedger,
+        SqliteLedger, Witness, WitnessSignature,
+    };
+    use poh::witness::serve;
+
+    // Service config for tests, the hash-rate check is skipped since unoptimised builds on shared runners fall short of it.
+    fn test_config() -> PoHConfig {
+        return PoHConfig {
+            enforce_self_test: false,
+            ..Default::default()
+        };
+    }
+
+    // Temp directory removed on drop, so a failing assertion doesn't leave it behind.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path: PathBuf = env::temp_dir().join(format!("poh-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            return Self(path);
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Unsynced ledger in a temp directory, fed from a chain whose slot ends are signed by `identity` when there is one.
+    struct LedgerFixture {
+        ledger: Ledger,
+        poh: PoH,
+        records: Vec<PoHRecord>,
+        identity: Option<Identity>,
+        dir: TempDir,
+    }
+
+    impl LedgerFixture {
+        fn new(name: &str, identity: Option<Identity>, ledger_config: LedgerConfig) -> Self {
+            let dir: TempDir = TempDir::new(name);
+            let ledger_config: LedgerConfig = LedgerConfig {
+                fsync: FsyncPolicy::Never,
+                ..ledger_config
+            };
+            let ledger: Ledger = Ledger::open(&dir.0, ledger_config, &PoHConfig::default()).expect("Failed to create ledger.");
+            return Self {
+                ledger,
+                poh: PoH::new(&[0u8; 64]),
+                records: Vec::new(),
+                identity,
+                dir,
+            };
+        }
+
+        // Append `ticks` records, `record` builds the i-th of them from the chain.
+        fn extend(&mut self, ticks: u64, mut record: impl FnMut(u64, &mut PoH) -> PoHRecord) {
+            for i in 0..ticks {
+                let next: PoHRecord = record(i, &mut self.poh);
+                let record: PoHRecord = self.sign(next);
+                self.ledger.append(&record).expect("Failed to append record.");
+                self.records.push(record);
+            }
+            self.ledger.flush().unwrap();
+        }
+
+        fn sign(&self, mut record: PoHRecord) -> PoHRecord {
+            if let Some(identity) = self.identity.as_ref().filter(|_| record.is_slot_end()) {
+                record.signature = Some(identity.sign_slot(&record));
+            }
+            return record;
+        }
+
+        fn reader(&self) -> LedgerReader {
+            return LedgerReader::open(&self.dir.0).expect("Failed to open ledger reader.");
+        }
+    }
 
     #[test]
     fn test_poh_record_construction() {
@@ -74,13 +162,15 @@ mod operations {
         let mut poh: PoH = PoH::new(&seed);
 
         let tick1: PoHRecord = poh.next_tick(); // Normal tick.
-        let event_data: &'static str = "Test event data";
-        let tick2: PoHRecord = poh.insert_event(event_data.as_bytes()); // Tick with event.
+        let event: Event = Event::Data {
+            bytes: b"Test event data".to_vec(),
+        };
+        let tick2: PoHRecord = poh.insert_event(event.clone()); // Tick with event.
         let tick3: PoHRecord = poh.next_tick(); // Normal tick.
 
         // Check that event was stored.
         assert!(tick2.event.is_some());
-        assert_eq!(tick2.event.clone().unwrap(), event_data.as_bytes());
+        assert_eq!(tick2.event.clone().unwrap(), event);
         // Check that non-event ticks don't have events.
         assert!(tick1.event.is_none());
         assert!(tick3.event.is_none());
@@ -149,6 +239,1194 @@ mod operations {
         }
     }
 
+    #[test]
+    fn test_thread_event_delivery() {
+        let seed: [u8; 64] = [0u8; 64];
+        let test_ticks: u64 = 25;
+
+        let rx: Receiver<PoHRecord> = thread::thread(&seed, test_ticks).expect("Failed to spawn PoH thread.");
+        let records: Vec<PoHRecord> = rx.iter().take(test_ticks as usize).collect();
+
+        assert_eq!(records.len(), test_ticks as usize, "Received incorrect number of records.");
+        // Payloads sent out-of-band must be re-attached to the right ticks.
+        for record in &records {
+            if record.tick_index % 10 == 0 {
+                let expected: Event = Event::Data {
+                    bytes: format!("Event at tick {}.", record.tick_index).into_bytes(),
+                };
+                assert_eq!(record.event.as_ref(), Some(&expected), "Event payload attached to the wrong tick.");
+            } else {
+                assert!(record.event.is_none(), "Unexpected event at tick {}.", record.tick_index);
+            }
+        }
+        assert!(PoH::verify_records(&records), "Reassembled records failed verification.");
+    }
+
+    #[test]
+    fn test_slip_status_accounting() {
+        let status: PoHStatus = PoHStatus::default();
+
+        assert!(!status.record_tick(0, 100), "On-time tick flagged as late.");
+        assert!(!status.record_tick(100, 100), "Tick at the threshold flagged as late.");
+        assert!(status.record_tick(300, 100), "Late tick not flagged.");
+        assert!(!status.record_tick(50, 100));
+
+        assert_eq!(status.ticks(), 4);
+        assert_eq!(status.late_ticks(), 1);
+        assert_eq!(status.last_lateness_us(), 50);
+        assert_eq!(status.max_lateness_us(), 300);
+        assert_eq!(status.schedule_error_us(), 450);
+    }
+
+    #[test]
+    fn test_slip_marker_policy() {
+        let seed: [u8; 64] = [0u8; 64];
+        let test_ticks: u64 = 24;
+        // Any lateness at all counts, so markers appear whenever this machine falls behind.
+        let config: PoHConfig = PoHConfig {
+            slip_policy: SlipPolicy::Marker,
+            slip_threshold_us: 0,
+            slip_tolerance_ticks: 1,
+            ..test_config()
+        };
+
+        let service: PoHService = thread::service(&seed, test_ticks, config).expect("Failed to spawn PoH thread.");
+        let records: Vec<PoHRecord> = service.receiver.iter().collect();
+        let markers: u64 = records.iter().filter(|r| matches!(r.event, Some(Event::ScheduleSlip { .. }))).count() as u64;
+
+        assert_eq!(records.len(), test_ticks as usize, "Marker policy must not drop ticks.");
+        assert_eq!(service.status.ticks(), test_ticks);
+        assert!(!service.status.is_aborted());
+        // A marker raised on the final tick has no tick left to land in.
+        assert!(markers <= service.status.slips() && markers.saturating_add(1) >= service.status.slips());
+        assert!(PoH::verify_records(&records), "Records with slip markers failed verification.");
+    }
+
+    #[test]
+    fn test_hash_rate_self_test() {
+        let report: SelfTestReport = measure_hash_rate(20);
+
+        assert!(report.hashes >= DEFAULT_HASHES_PER_TICK, "Self-test must hash at least one tick.");
+        assert!(report.elapsed_us >= 20_000, "Self-test ended before its window.");
+        assert_eq!(report.required_hashes_per_second, 2_000_000.0);
+        assert!((report.headroom - (report.hashes_per_second / report.required_hashes_per_second - 1.0)).abs() < 1e-9);
+        assert!(report.passes(report.headroom));
+        assert!(!report.passes(report.headroom + 0.01));
+    }
+
+    #[test]
+    fn test_self_test_refuses_slow_hardware() {
+        let seed: [u8; 64] = [0u8; 64];
+        // No machine has a million times the required hash rate.
+        let config: PoHConfig = PoHConfig {
+            self_test_ms: 10,
+            min_headroom: 1_000_000.0,
+            enforce_self_test: true,
+            ..test_config()
+        };
+        assert!(thread::service(&seed, 8, config.clone()).is_err(), "Slow hardware was not refused.");
+        // The override still reports the measurement but lets the loop run.
+        let allowed: PoHService = thread::service(
+            &seed,
+            8,
+            PoHConfig {
+                enforce_self_test: false,
+                ..config
+            },
+        )
+        .expect("Override did not allow the PoH thread to start.");
+
+        assert!(allowed.self_test.is_some_and(|r| !r.passes(1_000_000.0)));
+        assert_eq!(allowed.receiver.iter().count(), 8);
+    }
+
+    #[test]
+    fn test_event_submission_and_thread_policies() {
+        let seed: [u8; 64] = [0u8; 64];
+        let service: PoHService = thread::service(&seed, 40, test_config()).expect("Failed to spawn PoH thread.");
+
+        // Hashing, delivery and ingest threads each report what was applied.
+        let names: Vec<&str> = service.threads.iter().map(|p| p.thread_name.as_str()).collect();
+        assert_eq!(names, vec!["poh-thread-0", "poh-delivery-0", "poh-ingest-0"]);
+
+        let submitted: Vec<Event> = (0..3)
+            .map(|i| Event::Data {
+                bytes: format!("Submitted {}", i).into_bytes(),
+            })
+            .collect();
+        for event in &submitted {
+            service.events.send(event.clone()).expect("Failed to submit event.");
+        }
+
+        let records: Vec<PoHRecord> = service.receiver.iter().collect();
+        let recorded: Vec<Event> = records.iter().filter_map(|r| r.event.clone()).filter(|e| submitted.contains(e)).collect();
+        // Submitted events are recorded once each, in submission order, and never on a simulated event tick.
+        assert_eq!(recorded, submitted);
+        assert!(
+            records
+                .iter()
+                .all(|r| r.tick_index % 10 != 0 || !r.event.as_ref().is_some_and(|e| submitted.contains(e)))
+        );
+        assert!(PoH::verify_records(&records), "Records with submitted events failed verification.");
+        assert!(service.status.is_finished());
+    }
+
+    #[test]
+    fn test_fork_from_record() {
+        let seed: [u8; 64] = [0u8; 64];
+        let mut poh: PoH = PoH::new(&seed);
+        let records: Vec<PoHRecord> = (0..6).map(|_| poh.next_tick()).collect();
+
+        // Branching from a past record replays exactly the same continuation.
+        let mut branch: PoH = PoH::from_record(&records[2]);
+        let replayed: Vec<PoHRecord> = (0..3).map(|_| branch.next_tick()).collect();
+        for (original, replay) in records[3..].iter().zip(&replayed) {
+            assert_eq!(original.tick_index, replay.tick_index);
+            assert_eq!(original.hash, replay.hash);
+        }
+        assert!(replayed[0].timestamp_ms >= records[2].timestamp_ms, "Branch timestamps went backwards.");
+    }
+
+    #[test]
+    fn test_fork_tree_failover() {
+        let seed: [u8; 64] = [0u8; 64];
+        let ticks: usize = DEFAULT_TICKS_PER_SLOT as usize;
+        let mut leader: PoH = PoH::new(&seed);
+        let root: PoHRecord = leader.next_tick();
+        let mut tree: ForkTree = ForkTree::new(root.clone(), ForkChoice::Longest);
+
+        // The leader produces one slot, then a backup takes over from the same point with an event.
+        let slot: Vec<PoHRecord> = (1..ticks).map(|_| leader.next_tick()).collect();
+        let mut backup: PoH = tree.fork(&tree.root).expect("Root fork missing.");
+        let mut failover: Vec<PoHRecord> = vec![backup.insert_event(Event::Data {
+            bytes: b"leader failover".to_vec(),
+        })];
+        failover.extend((2..ticks).map(|_| backup.next_tick()));
+
+        let leader_key: ForkKey = tree.insert(&tree.root.clone(), &slot).expect("Leader segment rejected.");
+        let backup_key: ForkKey = tree.insert(&tree.root.clone(), &failover).expect("Backup segment rejected.");
+        assert_ne!(leader_key, backup_key);
+        assert_eq!(tree.leaves().len(), 2);
+        // A segment that does not continue its parent is refused.
+        assert!(tree.insert(&leader_key, &failover).is_err());
+
+        // Equal length, so the tie-break picks deterministically; the event fork wins by events.
+        let expected_tie: ForkKey = leader_key.min(backup_key);
+        assert_eq!(tree.heaviest(), expected_tie);
+        tree.choice = ForkChoice::MostEvents;
+        assert_eq!(tree.heaviest(), backup_key);
+        tree.choice = ForkChoice::Weighted;
+        tree.set_weight(&leader_key, 10).unwrap();
+        tree.set_weight(&backup_key, 3).unwrap();
+        assert_eq!(tree.heaviest(), leader_key);
+
+        // The leader keeps going and its fork becomes the longest.
+        let mut next: PoH = tree.fork(&leader_key).unwrap();
+        let extension: Vec<PoHRecord> = (0..ticks).map(|_| next.next_tick()).collect();
+        let extended_key: ForkKey = tree.insert(&leader_key, &extension).unwrap();
+        tree.choice = ForkChoice::Longest;
+        assert_eq!(tree.heaviest(), extended_key);
+
+        // Rooting the leader fork prunes the abandoned backup and the old root.
+        assert_eq!(tree.set_root(&leader_key).unwrap(), 2);
+        assert!(!tree.contains(&backup_key));
+        assert_eq!(tree.leaves(), vec![extended_key]);
+    }
+
+    #[test]
+    fn test_invalid_poh_config() {
+        let config: PoHConfig = PoHConfig {
+            slip_tolerance_ticks: 0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(PoHConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let (mut producer, mut consumer): (RingProducer<PoHEntry>, RingConsumer<PoHEntry>) = RingBuffer::channel(3);
+        // Capacity is rounded up to a power of two.
+        assert_eq!(producer.capacity(), 4);
+        assert!(consumer.is_empty());
+
+        let mut poh: PoH = PoH::new(&[0u8; 64]);
+        let entries: Vec<PoHEntry> = (0..4).map(|_| poh.next_entry()).collect();
+
+        for entry in &entries {
+            assert!(producer.try_push(*entry).is_ok(), "Push into a non-full ring failed.");
+        }
+        assert!(producer.try_push(entries[0]).is_err(), "Push into a full ring succeeded.");
+        assert_eq!(consumer.len(), 4);
+        assert_eq!(consumer.try_pop(), Some(entries[0]));
+        // A freed slot can be reused after wrapping around.
+        assert!(producer.try_push(entries[0]).is_ok());
+        drop(producer);
+        // Remaining values are still readable after the producer is gone, in order.
+        let drained: Vec<PoHEntry> = std::iter::from_fn(|| consumer.pop()).collect();
+        assert_eq!(drained, vec![entries[1], entries[2], entries[3], entries[0]]);
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn test_entry_matches_record() {
+        let seed: [u8; 64] = [0u8; 64];
+        let mut by_record: PoH = PoH::new(&seed);
+        let mut by_entry: PoH = PoH::new(&seed);
+
+        let event: Event = Event::Data { bytes: b"payload".to_vec() };
+        let record: PoHRecord = by_record.insert_event(event.clone());
+        let entry: PoHEntry = by_entry.insert_event_entry(&event);
+
+        assert!(entry.has_event);
+        assert_eq!(entry.hash, record.hash, "Entry and record paths diverged.");
+        assert_eq!(entry.into_record(Some(event)).event, record.event);
+    }
+
+    #[test]
+    fn test_event_encoding() {
+        let events: Vec<Event> = vec![
+            Event::Data { bytes: b"payload".to_vec() },
+            Event::Document { hash: [7u8; 32] },
+            Event::WallClock { unix_ms: 1_700_000_000_000 },
+            Event::SlotMarker { slot: 42 },
+            Event::CrossChain {
+                chain: "bitcoin".to_string(),
+                height: 840_000,
+                hash: [9u8; 32],
+            },
+            Event::ScheduleSlip { tick: 64, lateness_us: 12_500 },
+            Event::Referenced {
+                reference: [3u8; 32],
+                max_age_slots: 2,
+                event: Box::new(Event::Document { hash: [7u8; 32] }),
+            },
+        ];
+
+        for event in &events {
+            let encoded: Vec<u8> = event.encode();
+            assert_eq!(&Event::decode(&encoded).expect("Failed to decode event."), event);
+            // Trailing bytes and unknown tags are refused.
+            let mut padded: Vec<u8> = encoded.clone();
+            padded.push(0);
+            assert!(Event::decode(&padded).is_err());
+            assert!(Event::decode(&encoded[..encoded.len() - 1]).is_err());
+        }
+        assert!(Event::decode(&[0xFF]).is_err());
+        assert!(Event::decode(&[]).is_err());
+        // A reference wraps exactly one event that has none of its own.
+        let nested: Event = Event::Referenced {
+            reference: [4u8; 32],
+            max_age_slots: 1,
+            event: Box::new(events[6].clone()),
+        };
+        assert!(Event::decode(&nested.encode()).is_err());
+        // The tag is part of the hash, identical payloads under different kinds never collide.
+        assert_ne!(Event::SlotMarker { slot: 1 }.encode(), Event::WallClock { unix_ms: 1 }.encode());
+    }
+
+    #[test]
+    fn test_event_json_form() {
+        let event: Event = Event::Data { bytes: vec![0xDE, 0xAD] };
+        let json: String = serde_json::to_string(&event).expect("Failed to serialize event.");
+
+        assert_eq!(json, r#"{"type":"data","bytes":"dead"}"#);
+        assert_eq!(serde_json::from_str::<Event>(&json).expect("Failed to deserialize event."), event);
+
+        // Records round-trip through JSON and still verify, since only the canonical encoding is hashed.
+        let mut poh: PoH = PoH::new(&[0u8; 64]);
+        let records: Vec<PoHRecord> = vec![poh.next_tick(), poh.insert_event(Event::SlotMarker { slot: 0 }), poh.next_tick()];
+        let restored: Vec<PoHRecord> = serde_json::from_str(&serde_json::to_string(&records).unwrap()).expect("Failed to deserialize records.");
+        assert!(PoH::verify_records(&restored));
+
+        // Swapping the event kind breaks the chain.
+        let mut tampered: Vec<PoHRecord> = restored.clone();
+        tampered[1].event = Some(Event::WallClock { unix_ms: 0 });
+        assert!(!PoH::verify_records(&tampered));
+    }
+
+    #[test]
+    fn test_event_commitments() {
+        let seed: [u8; 64] = [0u8; 64];
+        let secret: Event = Event::Document { hash: [3u8; 32] };
+        let mut poh: PoH = PoH::new(&seed);
+        let mut store: EventStore = EventStore::new();
+
+        let mut records: Vec<PoHRecord> = vec![poh.next_tick(), poh.commit_event(&secret), poh.next_tick()];
+        assert_eq!(records[1].commitment, Some(secret.commitment()));
+        assert!(records[1].event.is_none() && records[1].has_event());
+        // The chain verifies from commitments alone.
+        assert!(PoH::verify_records(&records));
+        let published: Vec<PoHRecord> = serde_json::from_str(&serde_json::to_string(&records).unwrap()).expect("Failed to deserialize records.");
+        assert!(PoH::verify_records(&published));
+
+        // Payloads are matched against their commitments once attached.
+        store.insert(records[1].tick_index, secret.clone()).unwrap();
+        assert!(store.verify(&records));
+        store.attach(&mut records);
+        assert_eq!(records[1].event, Some(secret.clone()));
+        assert!(PoH::verify_records(&records));
+        records[1].event = Some(Event::Document { hash: [4u8; 32] });
+        assert!(!PoH::verify_records(&records), "Payload not matching its commitment was accepted.");
+
+        // A tampered store is caught, pruning leaves a chain that still verifies.
+        records[1].event = Some(secret);
+        store.detach(&mut records).unwrap();
+        assert!(records[1].event.is_none());
+        let commitment: [u8; 32] = records[1].commitment.unwrap();
+        store.events.get_mut(&commitment).unwrap().event = Event::SlotMarker { slot: 0 };
+        assert!(!store.verify(&records));
+        assert_eq!(store.prune_before(records[2].tick_index).unwrap(), 1);
+        assert!(store.is_empty());
+        assert!(store.verify(&records));
+    }
+
+    #[test]
+    fn test_thread_commit_events() {
+        let seed: [u8; 64] = [0u8; 64];
+        let path: PathBuf = env::temp_dir().join(format!("poh-event-store-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let config: PoHConfig = PoHConfig {
+            commit_events: true,
+            ..test_config()
+        };
+        assert!(config.validate().is_err(), "Committed payloads had nowhere to go.");
+
+        let config: PoHConfig = PoHConfig {
+            event_store: Some(path.clone()),
+            ..config
+        };
+        let service: PoHService = thread::service(&seed, 21, config).expect("Failed to spawn PoH thread.");
+        let mut records: Vec<PoHRecord> = service.receiver.iter().collect();
+
+        // Payloads never reach the delivered records, they are in the sidecar file.
+        assert!(records.iter().filter(|r| r.tick_index % 10 == 0).all(|r| r.commitment.is_some() && r.event.is_none()));
+        let store: EventStore = EventStore::open(&path).expect("Failed to open event store.");
+        assert_eq!(store.len(), 3);
+        assert!(store.verify(&records));
+        store.attach(&mut records);
+        assert_eq!(records[10].event.as_ref().map(Event::kind), Some("data"));
+
+        // A torn last line is dropped on reopen, pruning rewrites the file.
+        drop(store);
+        fs::write(&path, [fs::read(&path).unwrap(), b"{\"tick_".to_vec()].concat()).unwrap();
+        let mut store: EventStore = EventStore::open(&path).expect("Failed to recover event store.");
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.prune_before(records[20].tick_index).unwrap(), 2);
+        drop(store);
+        assert_eq!(EventStore::open(&path).unwrap().len(), 1);
+        // Damage before the last line is an error.
+        fs::write(&path, [b"garbage\n".to_vec(), fs::read(&path).unwrap()].concat()).unwrap();
+        assert!(EventStore::open(&path).is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_sparse_ledger() {
+        let seed: [u8; 64] = [0u8; 64];
+        let ticks: u64 = DEFAULT_TICKS_PER_SLOT * 2 + 5;
+        let mut poh: PoH = PoH::new(&seed);
+        let full: Vec<PoHRecord> = (0..ticks)
+            .map(|i| {
+                if i % 10 == 0 {
+                    poh.insert_event(Event::SlotMarker { slot: i })
+                } else {
+                    poh.next_tick()
+                }
+            })
+            .collect();
+        let sparse: Vec<PoHRecord> = SparseLedger::collapse(full.clone());
+
+        assert!(sparse.len() * 4 < full.len(), "Sparse ledger barely shrank: {} records.", sparse.len());
+        assert!(PoH::verify_records(&sparse), "Sparse ledger failed verification.");
+        assert!(PoH::verify_timestamps(&sparse, false) == PoH::verify_timestamps(&full, false));
+        // Every event and every slot's last tick is kept, and the collapsed hashes add up to the whole run.
+        let total: u64 = sparse.iter().skip(1).map(|r| r.num_hashes).sum();
+        assert_eq!(total, (ticks - 1) * DEFAULT_HASHES_PER_TICK);
+        assert_eq!(sparse.iter().filter(|r| r.event.is_some()).count(), full.iter().filter(|r| r.event.is_some()).count());
+        for slot in 0..2 {
+            let last: u64 = (slot + 1) * DEFAULT_TICKS_PER_SLOT - 1;
+            assert!(sparse.iter().any(|r| r.tick_index == last), "Missing slot boundary at tick {}.", last);
+        }
+        assert_eq!(sparse.last().unwrap().hash, full.last().unwrap().hash);
+
+        // A gap skipping a slot boundary or an event folded into a collapsed record is rejected.
+        let boundary: usize = sparse.iter().position(|r| r.tick_index == DEFAULT_TICKS_PER_SLOT - 1).unwrap();
+        let mut skipped: Vec<PoHRecord> = sparse.clone();
+        let removed: PoHRecord = skipped.remove(boundary);
+        skipped[boundary].num_hashes += removed.num_hashes;
+        assert!(!PoH::verify_records(&skipped));
+        let mut tampered: Vec<PoHRecord> = sparse.clone();
+        tampered[2].num_hashes += DEFAULT_HASHES_PER_TICK;
+        assert!(!PoH::verify_records(&tampered));
+    }
+
+    #[test]
+    fn test_thread_sparse_delivery() {
+        let seed: [u8; 64] = [0u8; 64];
+        let config: PoHConfig = PoHConfig { sparse: true, ..test_config() };
+
+        let service: PoHService = thread::service(&seed, 70, config).expect("Failed to spawn PoH thread.");
+        let records: Vec<PoHRecord> = service.receiver.iter().collect();
+
+        assert!(records.len() < 20, "Sparse delivery sent {} records.", records.len());
+        assert_eq!(records.last().map(|r| r.tick_index), Some(69));
+        assert!(PoH::verify_records(&records));
+    }
+
+    #[test]
+    fn test_signed_slot_hashes() {
+        let seed: [u8; 64] = [0u8; 64];
+        let identity: Identity = Identity::generate().expect("Failed to generate identity.");
+        let impostor: Identity = Identity::generate().expect("Failed to generate identity.");
+        let config: PoHConfig = PoHConfig {
+            identity: Some(identity.clone()),
+            ..test_config()
+        };
+
+        let service: PoHService = thread::service(&seed, DEFAULT_TICKS_PER_SLOT * 2, config).expect("Failed to spawn PoH thread.");
+        let records: Vec<PoHRecord> = service.receiver.iter().collect();
+
+        assert_eq!(records.iter().filter(|r| r.signature.is_some()).count(), 2, "Expected one signature per slot.");
+        assert!(PoH::verify_signed_records(&records, &identity.public_key()));
+        // Anyone with the seed can rebuild the chain, but not the signatures.
+        assert!(!PoH::verify_signed_records(&records, &impostor.public_key()));
+        let mut forged: Vec<PoHRecord> = records.clone();
+        let last: usize = forged.len() - 1;
+        forged[last].signature = Some(impostor.sign_slot(&forged[last]));
+        assert!(!PoH::verify_signed_records(&forged, &identity.public_key()));
+        forged[last].signature = None;
+        assert!(!PoH::verify_signed_records(&forged, &identity.public_key()), "Unsigned slot was accepted.");
+        // Signatures survive sparse collapsing and JSON.
+        let sparse: Vec<PoHRecord> = SparseLedger::collapse(records);
+        let restored: Vec<PoHRecord> = serde_json::from_str(&serde_json::to_string(&sparse).unwrap()).expect("Failed to deserialize records.");
+        assert!(PoH::verify_signed_records(&restored, &identity.public_key()));
+    }
+
+    #[test]
+    fn test_identity_keypair_file() {
+        let dir: PathBuf = env::temp_dir().join(format!("poh-identity-{}", std::process::id()));
+        let path: PathBuf = dir.join("identity.key");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create temp dir.");
+
+        let identity: Identity = Identity::generate().expect("Failed to generate identity.");
+        identity.save(&path).expect("Failed to save identity.");
+        let loaded: Identity = Identity::load(&path).expect("Failed to load identity.");
+
+        assert_eq!(loaded.public_key(), identity.public_key());
+        assert_eq!(identity.public_key_hex().len(), 64);
+        assert_eq!(bs58::decode(identity.public_key_base58()).into_vec().unwrap(), identity.public_key());
+        assert!(identity.save(&path).is_err(), "An existing keypair file was overwritten.");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(Identity::load(&path).is_err(), "World-readable keypair file was loaded.");
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        // The config loads the identity from its path.
+        let config: PoHConfig = PoHConfig {
+            identity_path: Some(path.clone()),
+            ..Default::default()
+        };
+        assert_eq!(config.resolve_identity().unwrap().map(|i| i.public_key()), Some(identity.public_key()));
+
+        // Truncated, garbage and empty files are rejected.
+        let bytes: Vec<u8> = fs::read(&path).unwrap();
+        for corrupted in [&bytes[..bytes.len() / 2], &b"not a keypair"[..], &[][..]] {
+            fs::write(&path, corrupted).unwrap();
+            assert!(Identity::load(&path).is_err(), "Corrupted keypair file was loaded.");
+        }
+        let mut flipped: Vec<u8> = bytes.clone();
+        let last: usize = flipped.len() - 1;
+        flipped[last] ^= 0xFF;
+        fs::write(&path, &flipped).unwrap();
+        assert!(Identity::load(&path).is_err(), "Keypair with a mismatched public key was loaded.");
+        assert!(Identity::load(&dir.join("missing.key")).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_witness_continuity() {
+        let seed: [u8; 64] = [0u8; 64];
+        let ticks: usize = DEFAULT_TICKS_PER_SLOT as usize;
+        let dir: PathBuf = env::temp_dir().join(format!("poh-witness-state-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create temp dir.");
+        let state: PathBuf = dir.join("witness.json");
+        let generator: Identity = Identity::generate().unwrap();
+        let key: [u8; 32] = generator.public_key();
+        let mut poh: PoH = PoH::new(&seed);
+        let mut slots: Vec<Vec<PoHRecord>> = (0..4).map(|_| (0..ticks).map(|_| poh.next_tick()).collect()).collect();
+        for slot in slots.iter_mut() {
+            let end: &mut PoHRecord = slot.last_mut().unwrap();
+            end.signature = Some(generator.sign_slot(end));
+        }
+        let witness_identity: Identity = Identity::generate().unwrap();
+        let mut witness: Witness = Witness::open(witness_identity.clone(), &state).unwrap();
+
+        let first = witness.witness(&key, &slots[0]).expect("First slot rejected.");
+        assert!(first.verify(slots[0].last().unwrap()));
+        // Skipping a slot, an unfinished slot, a tampered one or one signed by another generator is refused,
+        // and the witness can still continue afterwards.
+        assert!(witness.witness(&key, &slots[2]).is_err());
+        assert!(witness.witness(&key, &slots[1][..ticks - 1]).is_err());
+        let mut tampered: Vec<PoHRecord> = slots[1].clone();
+        tampered[3].hash[0] ^= 1;
+        assert!(witness.witness(&key, &tampered).is_err());
+        assert!(witness.witness(&Identity::generate().unwrap().public_key(), &slots[1]).is_err());
+        assert!(
+            witness.witness(&key, &SparseLedger::collapse(slots[1].clone())).is_ok(),
+            "Sparse continuation rejected."
+        );
+        assert!(witness.witness(&key, &slots[2]).is_ok());
+        assert!(witness.witness(&key, &slots[2]).is_err(), "Same slot co-signed twice.");
+
+        // A restarted witness continues where it stopped, so neither a rewind nor a fresh start is co-signed.
+        let mut restarted: Witness = Witness::open(witness_identity, &state).unwrap();
+        assert_eq!(restarted.generators.get(&key).map(|slot| slot.slot_index), Some(slots[2].last().unwrap().slot_index));
+        assert!(restarted.witness(&key, &slots[1]).is_err());
+        assert!(restarted.witness(&key, &slots[0]).is_err());
+        assert!(restarted.witness(&key, &slots[3]).is_ok());
+
+        fs::write(&state, b"not json").unwrap();
+        assert!(Witness::open(Identity::generate().unwrap(), &state).is_err(), "Corrupted witness state was loaded.");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_witness_threshold() {
+        let seed: [u8; 64] = [0u8; 64];
+        let dir: PathBuf = env::temp_dir().join(format!("poh-witness-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create temp dir.");
+
+        let mut sockets: Vec<PathBuf> = Vec::new();
+        let mut keys: Vec<[u8; 32]> = Vec::new();
+        for i in 0..3 {
+            let socket: PathBuf = dir.join(format!("witness-{}.sock", i));
+            let identity: Identity = Identity::generate().unwrap();
+            let listener: UnixListener = UnixListener::bind(&socket).expect("Failed to bind witness socket.");
+            keys.push(identity.public_key());
+            sockets.push(socket);
+            let witness: Witness = Witness::open(identity, &dir.join(format!("witness-{}.json", i))).unwrap();
+            std::thread::spawn(move || serve(listener, witness));
+        }
+        // One configured witness is down, the remaining three still meet the threshold.
+        sockets.push(dir.join("offline.sock"));
+        let config: PoHConfig = PoHConfig {
+            witnesses: sockets.clone(),
+            witness_threshold: 3,
+            // Unoptimised witnesses on a busy machine need far longer than the default to verify a slot.
+            witness_timeout_ms: 120_000,
+            sparse: true,
+            identity: Some(Identity::generate().unwrap()),
+            ..test_config()
+        };
+
+        let service: PoHService = thread::service(&seed, DEFAULT_TICKS_PER_SLOT * 2 + 5, config).expect("Failed to spawn PoH thread.");
+        let records: Vec<PoHRecord> = service.receiver.iter().collect();
+
+        assert_eq!(
+            records.last().map(|r| r.tick_index),
+            Some(DEFAULT_TICKS_PER_SLOT * 2 + 4),
+            "Trailing records were lost."
+        );
+        assert_eq!(service.status.finalised_slots(), 2);
+        assert!(PoH::verify_witnessed_records(&records, &keys, 3));
+        assert!(PoH::verify_witnessed_records(&records, &keys[..2], 2));
+        assert!(
+            !PoH::verify_witnessed_records(&records, &keys[..2], 3),
+            "Signatures outside the witness set were counted."
+        );
+        let mut forged: Vec<PoHRecord> = records.clone();
+        let end: usize = forged.iter().position(|r| r.is_slot_end()).unwrap();
+        forged[end].witnesses[0].signature[0] ^= 1;
+        assert!(!PoH::verify_witnessed_records(&forged, &keys, 2), "Invalid witness signature was accepted.");
+        assert!(!PoH::verify_witnessed_records(&records, &keys, 0), "A threshold of 0 was accepted.");
+
+        // A quorum that can't be reached refuses to start.
+        let config: PoHConfig = PoHConfig {
+            witnesses: vec![dir.join("offline.sock")],
+            witness_threshold: 1,
+            identity: Some(Identity::generate().unwrap()),
+            ..test_config()
+        };
+        assert!(thread::service(&seed, 1, config).is_err());
+        // Witnesses can't check the generator of an unsigned chain.
+        let config: PoHConfig = PoHConfig {
+            witnesses: sockets.clone(),
+            witness_threshold: 1,
+            ..test_config()
+        };
+        assert!(config.validate().is_err());
+        // A configured witness set with a threshold of 0 would finalise every slot unsigned.
+        let config: PoHConfig = PoHConfig {
+            witnesses: sockets.clone(),
+            identity: Some(Identity::generate().unwrap()),
+            ..test_config()
+        };
+        assert_eq!(config.witness_threshold, 0);
+        assert!(config.validate().is_err());
+        assert!(thread::service(&seed, 1, config).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_binary_record_format() {
+        let seed: [u8; 64] = [0u8; 64];
+        let identity: Identity = Identity::generate().unwrap();
+        let mut poh: PoH = PoH::new(&seed);
+        let mut records: Vec<PoHRecord> = (0..DEFAULT_TICKS_PER_SLOT)
+            .map(|i| match i % 10 {
+                0 => poh.insert_event(Event::CrossChain {
+                    chain: "bitcoin".to_string(),
+                    height: i,
+                    hash: [1u8; 32],
+                }),
+                5 => poh.commit_event(&Event::Data { bytes: vec![7u8; 100] }),
+                _ => poh.next_tick(),
+            })
+            .collect();
+        let end: usize = records.len() - 1;
+        let signature: [u8; 64] = identity.sign_slot(&records[end]);
+        records[end].signature = Some(signature);
+        records[end].witnesses.push(WitnessSignature {
+            public_key: identity.public_key(),
+            signature,
+        });
+        records = SparseLedger::collapse(records);
+
+        let config: PoHConfig = PoHConfig {
+            sparse: true,
+            slip_policy: SlipPolicy::Marker,
+            ..Default::default()
+        };
+        let mut encoder: RecordEncoder<Vec<u8>> = RecordEncoder::new(Vec::new(), &config).expect("Failed to write header.");
+        for record in &records {
+            encoder.write(record).expect("Failed to encode record.");
+        }
+        let bytes: Vec<u8> = encoder.into_inner();
+
+        let decoder: RecordDecoder<&[u8]> = RecordDecoder::new(&bytes[..]).expect("Failed to read header.");
+        let header: LedgerHeader = decoder.header.clone();
+        assert!(header.validate().is_ok());
+        assert!(header.sparse && !header.commit_events);
+        let decoded: Vec<PoHRecord> = decoder.collect::<anyhow::Result<_>>().expect("Failed to decode records.");
+        // Lossless, compared through the JSON form since records don't implement PartialEq.
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), serde_json::to_string(&records).unwrap());
+        assert!(PoH::verify_signed_records(&decoded, &identity.public_key()));
+        assert!(
+            bytes.len() * 2 < serde_json::to_string_pretty(&records).unwrap().len(),
+            "Binary format is not compact."
+        );
+
+        // A stream cut inside a record or with a foreign header is refused.
+        let mut decoder: RecordDecoder<&[u8]> = RecordDecoder::new(&bytes[..bytes.len() - 3]).unwrap();
+        assert!(decoder.any(|record| record.is_err()));
+        assert!(RecordDecoder::new(&b"JSON{}"[..]).is_err());
+
+        // A corrupted event length fails on the missing bytes instead of allocating them.
+        let mut encoder: RecordEncoder<Vec<u8>> = RecordEncoder::new(Vec::new(), &config).unwrap();
+        encoder.write(&PoH::new(&seed).next_tick()).unwrap();
+        let mut bytes: Vec<u8> = encoder.into_inner();
+        *bytes.last_mut().unwrap() = 1 << 3;
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        let mut decoder: RecordDecoder<&[u8]> = RecordDecoder::new(&bytes[..]).unwrap();
+        assert!(decoder.next().is_some_and(|record| record.is_err()));
+    }
+
+    #[test]
+    fn test_segmented_ledger_recovery() {
+        let dir: PathBuf = env::temp_dir().join(format!("poh-ledger-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        // Tiny segments roll after every slot.
+        let ledger_config: LedgerConfig = LedgerConfig {
+            segment_bytes: 1,
+            fsync: FsyncPolicy::Record,
+            ..Default::default()
+        };
+        let seed: [u8; 64] = [0u8; 64];
+        let mut poh: PoH = PoH::new(&seed);
+        let records: Vec<PoHRecord> = (0..DEFAULT_TICKS_PER_SLOT * 2 + 10).map(|_| poh.next_tick()).collect();
+
+        let mut ledger: Ledger = Ledger::open(&dir, ledger_config.clone(), &PoHConfig::default()).expect("Failed to create ledger.");
+        for record in &records {
+            ledger.append(record).expect("Failed to append record.");
+        }
+        assert!(ledger.append(&records[0]).is_err(), "Appended a record out of order.");
+        assert_eq!(ledger.segments().unwrap().len(), 3);
+        assert_eq!(ledger.index.len(), 3);
+        let slot: Vec<PoHRecord> = ledger.read_slot(1).unwrap();
+        assert_eq!(
+            serde_json::to_string(&slot).unwrap(),
+            serde_json::to_string(&records[DEFAULT_TICKS_PER_SLOT as usize..2 * DEFAULT_TICKS_PER_SLOT as usize]).unwrap()
+        );
+        drop(ledger);
+
+        // Simulate a crash inside a record and while rolling to a new segment.
+        let segments: Vec<PathBuf> = {
+            let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension().is_some_and(|e| e == "seg"))
+                .collect();
+            paths.sort();
+            paths
+        };
+        let mut torn: Vec<u8> = Vec::new();
+        poh.next_tick().encode_into(&mut torn).unwrap();
+        let mut tail: Vec<u8> = fs::read(&segments[2]).unwrap();
+        tail.extend_from_slice(&torn[..torn.len() / 2]);
+        fs::write(&segments[2], &tail).unwrap();
+        fs::write(dir.join("0000000003.seg"), b"PO").unwrap();
+
+        let mut ledger: Ledger = Ledger::open(&dir, ledger_config, &PoHConfig::default()).expect("Failed to recover ledger.");
+        assert_eq!(ledger.segments().unwrap().len(), 3);
+        assert_eq!(ledger.index.len(), 3);
+        let last: PoHRecord = ledger.last.clone().expect("Recovered ledger is empty.");
+        assert_eq!(last.hash, records.last().unwrap().hash);
+
+        // Generation continues from the last durable record and the chain still verifies.
+        let mut resumed: PoH = PoH::from_record(&last);
+        let next: PoHRecord = resumed.next_tick();
+        ledger.append(&next).expect("Failed to append after recovery.");
+        let slot: Vec<PoHRecord> = ledger.read_slot(2).unwrap();
+        assert_eq!(slot.len(), 11);
+        assert!(PoH::verify_records(&slot));
+        assert_eq!(ledger.read_slot(0).unwrap().len(), DEFAULT_TICKS_PER_SLOT as usize);
+        drop(ledger);
+
+        // Resuming with chain settings the header doesn't describe is refused, nothing is appended.
+        let length: u64 = fs::metadata(&segments[2]).unwrap().len();
+        for poh_config in [
+            PoHConfig {
+                sparse: true,
+                ..Default::default()
+            },
+            PoHConfig {
+                commit_events: true,
+                ..Default::default()
+            },
+        ] {
+            assert!(
+                Ledger::open(&dir, LedgerConfig::default(), &poh_config).is_err(),
+                "Resumed with different chain flags."
+            );
+        }
+        assert_eq!(fs::metadata(&segments[2]).unwrap().len(), length);
+
+        // Damage that isn't a short tail is refused instead of truncated away, flags of the first record after the 32-byte header.
+        let mut damaged: Vec<u8> = fs::read(&segments[2]).unwrap();
+        let length: usize = damaged.len();
+        damaged[32 + 72] = 0xFF;
+        fs::write(&segments[2], &damaged).unwrap();
+        assert!(
+            Ledger::open(&dir, LedgerConfig::default(), &PoHConfig::default()).is_err(),
+            "Corrupted segment was truncated."
+        );
+        assert_eq!(fs::metadata(&segments[2]).unwrap().len(), length as u64);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compressed_ledger_segments() {
+        let dir: PathBuf = env::temp_dir().join(format!("poh-compressed-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let ledger_config: LedgerConfig = LedgerConfig {
+            segment_bytes: 1,
+            fsync: FsyncPolicy::Never,
+            compress: true,
+            ..Default::default()
+        };
+        let seed: [u8; 64] = [0u8; 64];
+        let mut poh: PoH = PoH::new(&seed);
+        let records: Vec<PoHRecord> = (0..DEFAULT_TICKS_PER_SLOT * 3 + 5).map(|_| poh.next_tick()).collect();
+
+        let mut ledger: Ledger = Ledger::open(&dir, ledger_config.clone(), &PoHConfig::default()).expect("Failed to create ledger.");
+        for record in &records {
+            ledger.append(record).expect("Failed to append record.");
+        }
+        // Every closed segment is compressed, the open one stays raw.
+        let segments: Vec<PathBuf> = ledger.segments().unwrap();
+        assert_eq!(segments.len(), 4);
+        assert!(segments[..3].iter().all(|path| path.extension().is_some_and(|e| e == "zst")));
+        assert!(segments[3].extension().is_some_and(|e| e == "seg"));
+        assert_eq!(ledger.compression.segments, 3);
+        assert!(ledger.compression.ratio() > 1.0);
+        assert!(ledger.summary().is_some());
+
+        // A slot is read from its own frame through the seek table.
+        let slot: Vec<PoHRecord> = ledger.read_slot(1).unwrap();
+        assert_eq!(
+            serde_json::to_string(&slot).unwrap(),
+            serde_json::to_string(&records[DEFAULT_TICKS_PER_SLOT as usize..2 * DEFAULT_TICKS_PER_SLOT as usize]).unwrap()
+        );
+        assert!(Ledger::read_compressed_slot(&segments[0], 5).unwrap().is_empty());
+
+        // The whole ledger streams and verifies straight from the compressed segments.
+        let streamed: Vec<PoHRecord> = ledger.records().unwrap().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(streamed.len(), records.len());
+        assert_eq!(streamed.last().unwrap().hash, records.last().unwrap().hash);
+        assert!(ledger.verify(None, None).unwrap());
+
+        // A compressed segment is a plain zstd stream of the raw segment.
+        let raw: Vec<u8> = zstd::decode_all(fs::File::open(&segments[0]).unwrap()).unwrap();
+        let decoded: Vec<PoHRecord> = RecordDecoder::new(&raw[..]).unwrap().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(decoded.len(), DEFAULT_TICKS_PER_SLOT as usize);
+        drop(ledger);
+
+        // With the open segment gone, appending continues after the last compressed one.
+        fs::remove_file(&segments[3]).unwrap();
+        let mut ledger: Ledger = Ledger::open(&dir, ledger_config, &PoHConfig::default()).expect("Failed to reopen ledger.");
+        let last: PoHRecord = ledger.last.clone().expect("Reopened ledger is empty.");
+        assert_eq!(last.hash, records[3 * DEFAULT_TICKS_PER_SLOT as usize - 1].hash);
+        ledger.append(&PoH::from_record(&last).next_tick()).expect("Failed to append after reopening.");
+        assert_eq!(ledger.segments().unwrap().len(), 4);
+        assert!(ledger.verify(None, None).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ledger_reader_queries() {
+        let dir: PathBuf = env::temp_dir().join(format!("poh-reader-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let ledger_config: LedgerConfig = LedgerConfig {
+            segment_bytes: 1,
+            fsync: FsyncPolicy::Never,
+            compress: true,
+            ..Default::default()
+        };
+        let seed: [u8; 64] = [0u8; 64];
+        let mut poh: PoH = PoH::new(&seed);
+        let inserted: Event = Event::Document { hash: [3u8; 32] };
+        let committed: Event = Event::WallClock { unix_ms: 1_700_000_000_000 };
+        let records: Vec<PoHRecord> = (0..DEFAULT_TICKS_PER_SLOT * 4 + 8)
+            .map(|i| {
+                let mut record: PoHRecord = match i {
+                    70 => poh.insert_event(inserted.clone()),
+                    150 => poh.commit_event(&committed),
+                    _ => poh.next_tick(),
+                };
+                // Timestamps are not hashed, spread them out so time ranges are predictable.
+                record.timestamp_ms = i * DEFAULT_MS_PER_TICK;
+                record
+            })
+            .collect();
+
+        let mut ledger: Ledger = Ledger::open(&dir, ledger_config, &PoHConfig::default()).expect("Failed to create ledger.");
+        for record in &records {
+            ledger.append(record).expect("Failed to append record.");
+        }
+        ledger.flush().unwrap();
+        let reader: LedgerReader = LedgerReader::open(&dir).expect("Failed to open ledger reader.");
+
+        // Slot ranges cross compressed segments, every record comes with the hash it extends.
+        let slots: Vec<LedgerEntry> = reader.slots(1..3).unwrap().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(slots.len(), 2 * DEFAULT_TICKS_PER_SLOT as usize);
+        assert_eq!(slots[0].record.tick_index, DEFAULT_TICKS_PER_SLOT);
+        assert_eq!(slots[0].anchor, Some((DEFAULT_TICKS_PER_SLOT - 1, records[DEFAULT_TICKS_PER_SLOT as usize - 1].hash)));
+        assert!(slots.iter().all(LedgerEntry::verify));
+
+        let ticks: Vec<LedgerEntry> = reader.ticks(10..20).unwrap().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(
+            ticks.iter().map(|entry| entry.record.tick_index).collect::<Vec<u64>>(),
+            (10..20).collect::<Vec<u64>>()
+        );
+        assert!(ticks.iter().all(LedgerEntry::verify));
+        // The first record of the ledger has nothing before it.
+        let first: LedgerEntry = reader.ticks(0..1).unwrap().next().unwrap().unwrap();
+        assert!(first.anchor.is_none() && !first.verify());
+        assert_eq!(reader.epochs(0..1).unwrap().count(), records.len());
+        assert_eq!(reader.slots(9..12).unwrap().count(), 0);
+
+        let window: Vec<LedgerEntry> = reader
+            .time(100 * DEFAULT_MS_PER_TICK..200 * DEFAULT_MS_PER_TICK)
+            .unwrap()
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        assert_eq!(window.len(), 100);
+        assert_eq!(window[0].record.tick_index, 100);
+        assert!(window.iter().all(LedgerEntry::verify));
+
+        // Events are found by hash whether the payload was attached or only committed.
+        let found: LedgerEntry = reader.event(&inserted.commitment()).unwrap().expect("Inserted event not found.");
+        assert_eq!(found.record.tick_index, 70);
+        assert!(found.verify());
+        let found: LedgerEntry = reader.event(&committed.commitment()).unwrap().expect("Committed event not found.");
+        assert_eq!(found.record.tick_index, 150);
+        assert!(found.record.event.is_none() && found.verify());
+        assert!(reader.event(&[9u8; 32]).unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ledger_retention() {
+        let dir: PathBuf = env::temp_dir().join(format!("poh-retention-{}", std::process::id()));
+        let identity: Identity = Identity::generate().unwrap();
+        let poh_config: PoHConfig = PoHConfig {
+            identity: Some(identity.clone()),
+            ..Default::default()
+        };
+        let ticks_per_epoch: u64 = DEFAULT_TICKS_PER_SLOT * DEFAULT_SLOTS_PER_EPOCH;
+        // Two slots before an epoch boundary, two after it.
+        let generate = |first_tick: u64| -> Vec<PoHRecord> {
+            let mut start: PoHRecord = PoH::new(&[0u8; 64]).next_tick();
+            start.tick_index = first_tick - 1;
+            let mut poh: PoH = PoH::from_record(&start);
+            return (0..DEFAULT_TICKS_PER_SLOT * 4)
+                .map(|i| {
+                    let mut record: PoHRecord = match i {
+                        10 => poh.insert_event(Event::Document { hash: [4u8; 32] }),
+                        _ => poh.next_tick(),
+                    };
+                    record.timestamp_ms = i * DEFAULT_MS_PER_TICK;
+                    record
+                })
+                .collect();
+        };
+        let records: Vec<PoHRecord> = generate(ticks_per_epoch - 2 * DEFAULT_TICKS_PER_SLOT);
+        let boundary: usize = 2 * DEFAULT_TICKS_PER_SLOT as usize;
+
+        for retention in [
+            RetentionPolicy::Epochs(1),
+            RetentionPolicy::Bytes(10_000),
+            RetentionPolicy::After(boundary as u64 * DEFAULT_MS_PER_TICK + 1),
+        ] {
+            let _ = fs::remove_dir_all(&dir);
+            let ledger_config: LedgerConfig = LedgerConfig {
+                segment_bytes: 1,
+                fsync: FsyncPolicy::Never,
+                retention,
+                ..Default::default()
+            };
+            let mut ledger: Ledger = Ledger::open(&dir, ledger_config.clone(), &poh_config).expect("Failed to create ledger.");
+            for record in &records {
+                ledger.append(record).expect("Failed to append record.");
+            }
+
+            // The two slots before the boundary are gone, a signed checkpoint stands in for them.
+            let checkpoint: Checkpoint = ledger.checkpoint.clone().expect("Nothing was pruned.");
+            assert_eq!(checkpoint.tick_index, records[boundary - 1].tick_index, "{:?}", retention);
+            assert_eq!(checkpoint.hash, records[boundary - 1].hash);
+            assert!(checkpoint.verify(&identity.public_key()));
+            assert_eq!(ledger.segments().unwrap().len(), 3);
+            assert!(ledger.index.keys().all(|slot| *slot >= records[boundary].slot_index));
+            assert!(ledger.verify(Some(&identity.public_key()), None).unwrap());
+            assert!(ledger.verify(None, Some(&checkpoint.digest())).unwrap());
+            // The checkpoint can't vouch for itself.
+            assert!(ledger.verify(None, None).is_err());
+            assert!(!ledger.verify(Some(&Identity::generate().unwrap().public_key()), None).unwrap());
+            assert!(!ledger.verify(None, Some(&[0u8; 32])).unwrap());
+
+            // Readers anchor the first kept record on the checkpoint, events before it are no longer indexed.
+            ledger.flush().unwrap();
+            let reader: LedgerReader = LedgerReader::open(&dir).unwrap();
+            let first: LedgerEntry = reader.epochs(0..2).unwrap().next().unwrap().unwrap();
+            assert_eq!(first.record.tick_index, records[boundary].tick_index);
+            assert!(first.verify());
+            assert!(reader.event(&Event::Document { hash: [4u8; 32] }.commitment()).unwrap().is_none());
+            drop(ledger);
+
+            let mut ledger: Ledger = Ledger::open(&dir, ledger_config, &poh_config).expect("Failed to reopen ledger.");
+            assert_eq!(ledger.last.as_ref().unwrap().hash, records.last().unwrap().hash);
+            assert!(ledger.verify(Some(&identity.public_key()), None).unwrap());
+        }
+
+        // A checkpoint that does not match the kept records or its signature fails verification.
+        let mut forged: Checkpoint = Checkpoint::load(&dir).unwrap().unwrap();
+        forged.hash[0] ^= 1;
+        forged.save(&dir).unwrap();
+        let mut ledger: Ledger = Ledger::open(&dir, LedgerConfig::default(), &poh_config).unwrap();
+        assert!(!ledger.verify(Some(&identity.public_key()), None).unwrap());
+        let resigned: Checkpoint = Checkpoint::new(forged.segment, &forged.anchor(), None, Some(&identity));
+        resigned.save(&dir).unwrap();
+        let mut ledger: Ledger = Ledger::open(&dir, LedgerConfig::default(), &poh_config).unwrap();
+        assert!(!ledger.verify(Some(&identity.public_key()), None).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_mapped_ledger_verification() {
+        let seed: [u8; 64] = [0u8; 64];
+        let mut poh: PoH = PoH::new(&seed);
+        let event: Event = Event::CrossChain {
+            chain: "mapped".to_string(),
+            height: 7,
+            hash: [1u8; 32],
+        };
+        let records: Vec<PoHRecord> = (0..DEFAULT_TICKS_PER_SLOT * 2)
+            .map(|i| match i {
+                10 => poh.insert_event(event.clone()),
+                20 => poh.commit_event(&event),
+                _ => poh.next_tick(),
+            })
+            .collect();
+        let write = |path: &PathBuf, records: &[PoHRecord]| {
+            let mut encoder: RecordEncoder<fs::File> = RecordEncoder::new(fs::File::create(path).unwrap(), &PoHConfig::default()).unwrap();
+            for record in records {
+                encoder.write(record).unwrap();
+            }
+            encoder.flush().unwrap();
+        };
+        let path: PathBuf = env::temp_dir().join(format!("poh-mapped-{}.bin", std::process::id()));
+        write(&path, &records);
+
+        // Views read the mapped bytes in place and convert back losslessly.
+        let ledger: MappedLedger = MappedLedger::open(&path).expect("Failed to map ledger.");
+        let views: Vec<RecordView> = ledger.records().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(views.len(), records.len());
+        assert!(views[10].event.is_some() && views[20].commitment.is_some());
+        let decoded: Vec<PoHRecord> = views.iter().map(RecordView::to_record).collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), serde_json::to_string(&records).unwrap());
+
+        assert!(ledger.verify().unwrap());
+        for threads in [1, 3, 8] {
+            assert!(ledger.verify_parallel(threads).unwrap());
+            assert!(PoH::verify_records_parallel(&records, threads));
+        }
+
+        // A broken link is found whichever thread gets it.
+        let mut tampered: Vec<PoHRecord> = records.clone();
+        tampered[70].hash[0] ^= 1;
+        write(&path, &tampered);
+        let ledger: MappedLedger = MappedLedger::open(&path).unwrap();
+        assert!(!ledger.verify().unwrap());
+        assert!(!ledger.verify_parallel(4).unwrap());
+        assert!(!PoH::verify_records_parallel(&tampered, 4));
+
+        // A file cut inside a record is an error, not a silent pass.
+        write(&path, &records);
+        let bytes: Vec<u8> = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        assert!(MappedLedger::open(&path).unwrap().verify().is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_record_sinks() {
+        let seed: [u8; 64] = [0u8; 64];
+        let mut poh: PoH = PoH::new(&seed);
+        let records: Vec<PoHRecord> = (0..20)
+            .map(|i| match i {
+                3 => poh.insert_event(Event::CrossChain {
+                    chain: "a, \"b\"".to_string(),
+                    height: 1,
+                    hash: [0u8; 32],
+                }),
+                _ => poh.next_tick(),
+            })
+            .collect();
+
+        let mut csv: CsvSink<Vec<u8>> = CsvSink::new(Vec::new()).unwrap();
+        let mut json_lines: JsonLinesWriter<Vec<u8>> = JsonLinesWriter::new(Vec::new());
+        let mut encoder: RecordEncoder<Vec<u8>> = RecordEncoder::new(Vec::new(), &PoHConfig::default()).unwrap();
+        // The same records fan out to every sink through the trait.
+        {
+            let mut sinks: Vec<&mut dyn RecordSink> = vec![&mut csv, &mut json_lines, &mut encoder];
+            for record in &records {
+                for sink in sinks.iter_mut() {
+                    sink.write_record(record).expect("Failed to write record.");
+                }
+            }
+            for sink in sinks.iter_mut() {
+                sink.finish().expect("Failed to finish sink.");
+            }
+        }
+
+        let decoded: Vec<PoHRecord> = RecordDecoder::new(&encoder.into_inner()[..]).unwrap().collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), serde_json::to_string(&records).unwrap());
+        let streamed: String = String::from_utf8(json_lines.into_inner().unwrap()).unwrap();
+        assert_eq!(streamed.lines().count(), records.len());
+
+        let text: String = String::from_utf8(csv.writer.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), records.len() + 1);
+        assert!(lines[0].starts_with("tick_index,slot_index,epoch_index"));
+        assert!(lines[1].starts_with(&format!(
+            "0,0,0,{},{},{}",
+            records[0].timestamp_ms,
+            records[0].num_hashes,
+            hex::encode(records[0].hash)
+        )));
+        // The event is quoted with inner quotes doubled, so its commas don't split the row.
+        assert!(lines[4].contains(r#",0,"{""type"":""cross_chain"",""chain"":""a, \""b\"""","#), "{}", lines[4]);
+        assert!(lines[4].ends_with(r#"}""#));
+    }
+
+    #[test]
+    fn test_sqlite_ledger() {
+        let path: PathBuf = env::temp_dir().join(format!("poh-sqlite-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let seed: [u8; 64] = [0u8; 64];
+        let mut poh: PoH = PoH::new(&seed);
+        let committed: Event = Event::Data { bytes: vec![9u8; 16] };
+        let records: Vec<PoHRecord> = (0..DEFAULT_TICKS_PER_SLOT + 10)
+            .map(|i| match i {
+                5 => poh.insert_event(Event::WallClock { unix_ms: 1_700_000_000_000 }),
+                70 => poh.commit_event(&committed),
+                _ => poh.next_tick(),
+            })
+            .collect();
+
+        let mut database: SqliteLedger = SqliteLedger::open(&path).expect("Failed to create database.");
+        assert!(database.run_id.is_none());
+        let run_id: i64 = database.start_run(&PoHConfig::default()).unwrap();
+        database.batch_size = 16;
+        for record in &records {
+            database.write_record(record).expect("Failed to insert record.");
+        }
+        database.finish().unwrap();
+        drop(database);
+
+        // Reopening picks the latest run for queries.
+        let mut database: SqliteLedger = SqliteLedger::open(&path).unwrap();
+        assert_eq!(database.run_id, Some(run_id));
+        let slot: Vec<PoHRecord> = database.records(0..1).unwrap();
+        assert_eq!(
+            serde_json::to_string(&slot).unwrap(),
+            serde_json::to_string(&records[..DEFAULT_TICKS_PER_SLOT as usize]).unwrap()
+        );
+        assert_eq!(database.records(0..2).unwrap().len(), records.len());
+        assert!(database.records(5..9).unwrap().is_empty());
+
+        let prefix: String = hex::encode(committed.commitment())[..6].to_uppercase();
+        let found: Vec<PoHRecord> = database.events(&prefix).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].tick_index, 70);
+        assert_eq!(found[0].commitment, Some(committed.commitment()));
+        assert_eq!(database.events("").unwrap().len(), 2);
+
+        let (slots, last_tick): (u64, u64) = database
+            .connection
+            .query_row("SELECT COUNT(*), MAX(last_tick) FROM slots WHERE run_id = ?1", [run_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((slots, last_tick), (2, records.len() as u64 - 1));
+        let kind: String = database
+            .connection
+            .query_row("SELECT kind FROM events WHERE tick_index = 5", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kind, "wall_clock");
+
+        drop(database);
+        fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_timestamp_consistency() {
         let seed: [u8; 64] = [0u8; 64];
@@ -323,4 +1601,348 @@ mod operations {
 
         assert!((days_per_epoch - 2.0).abs() < 0.001, "An epoch should be approximately 2 days.");
     }
+
+    #[test]
+    fn test_event_inclusion_proofs() {
+        let identity: Identity = Identity::generate().unwrap();
+        let inserted: Event = Event::Document { hash: [5u8; 32] };
+        let committed: Event = Event::WallClock { unix_ms: 1_700_000_000_000 };
+        let neighbour: Event = Event::Document { hash: [6u8; 32] };
+        let closing: Event = Event::Document { hash: [7u8; 32] };
+        let pending: Event = Event::Document { hash: [8u8; 32] };
+        let slot_end: u64 = 2 * DEFAULT_TICKS_PER_SLOT - 1;
+
+        let mut fixture: LedgerFixture = LedgerFixture::new("proof", Some(identity.clone()), LedgerConfig::default());
+        fixture.extend(DEFAULT_TICKS_PER_SLOT * 3 + 8, |i, poh| match i {
+            70 => poh.insert_event(inserted.clone()),
+            80 => poh.commit_event(&committed),
+            90 => poh.insert_event(neighbour.clone()),
+            _ if i == slot_end => poh.insert_event(closing.clone()),
+            _ if i == DEFAULT_TICKS_PER_SLOT * 3 + 2 => poh.insert_event(pending.clone()),
+            _ => poh.next_tick(),
+        });
+        let records: &[PoHRecord] = &fixture.records;
+        let reader: LedgerReader = fixture.reader();
+        let public_key: [u8; 32] = identity.public_key();
+
+        // Events later in the slot are carried along as steps, whether inserted or committed.
+        let proof: InclusionProof = prove(&reader, &inserted.commitment()).expect("Failed to prove inserted event.");
+        assert_eq!(proof.record.tick_index, 70);
+        assert_eq!(proof.prev_hash, records[69].hash);
+        assert_eq!(proof.steps.iter().filter(|step| step.data.is_some()).count(), 2);
+        assert_eq!(proof.hashes(), (slot_end - 70) * DEFAULT_HASHES_PER_TICK);
+        verify_proof(&proof, &public_key).expect("Inserted event proof does not verify.");
+
+        let proof: InclusionProof = prove(&reader, &committed.commitment()).expect("Failed to prove committed event.");
+        assert!(proof.record.event.is_none());
+        verify_proof(&proof, &public_key).expect("Committed event proof does not verify.");
+
+        // A proof survives a round trip through its file.
+        let path: PathBuf = fixture.dir.0.join("proof.json");
+        proof.save(&path).unwrap();
+        verify_proof(&InclusionProof::load(&path).unwrap(), &public_key).expect("Loaded proof does not verify.");
+
+        // An event on the last tick of the slot is signed directly.
+        let proof: InclusionProof = prove(&reader, &closing.commitment()).expect("Failed to prove slot end event.");
+        assert!(proof.slot_end.is_none() && proof.steps.is_empty());
+        verify_proof(&proof, &public_key).expect("Slot end event proof does not verify.");
+
+        assert!(prove(&reader, &pending.commitment()).is_err());
+        assert!(prove(&reader, &[9u8; 32]).is_err());
+
+        // Any change to the proof or a different key breaks it.
+        let proof: InclusionProof = prove(&reader, &inserted.commitment()).unwrap();
+        assert!(verify_proof(&proof, &Identity::generate().unwrap().public_key()).is_err());
+        let mut tampered: InclusionProof = proof.clone();
+        tampered.record.event = Some(Event::Document { hash: [1u8; 32] });
+        assert!(verify_proof(&tampered, &public_key).is_err());
+        let mut tampered: InclusionProof = proof.clone();
+        tampered.record.tick_index += 1;
+        assert!(verify_proof(&tampered, &public_key).is_err());
+        let mut tampered: InclusionProof = proof.clone();
+        tampered.steps[0].num_hashes += DEFAULT_HASHES_PER_TICK;
+        assert!(verify_proof(&tampered, &public_key).is_err());
+        let mut tampered: InclusionProof = proof.clone();
+        tampered.steps.retain(|step| step.data.is_none());
+        assert!(verify_proof(&tampered, &public_key).is_err());
+        let mut tampered: InclusionProof = proof.clone();
+        tampered.slot_end = None;
+        assert!(verify_proof(&tampered, &public_key).is_err());
+
+        // Hash counts are checked against the tick indices before anything is hashed, a huge claim is refused at once.
+        let huge: u64 = u64::MAX / 2 / DEFAULT_HASHES_PER_TICK * DEFAULT_HASHES_PER_TICK;
+        let mut tampered: InclusionProof = proof.clone();
+        tampered.steps[0].num_hashes = huge;
+        assert!(verify_proof(&tampered, &public_key).is_err());
+        let mut forged: PoHRecord = records[1].clone();
+        forged.num_hashes = huge;
+        assert!(!PoH::verify_records(&[records[0].clone(), forged]));
+    }
+
+    #[test]
+    fn test_ordering_proofs() {
+        let ledger_config: LedgerConfig = LedgerConfig {
+            segment_bytes: 5_000,
+            ..Default::default()
+        };
+        let mut fixture: LedgerFixture = LedgerFixture::new("ordering", None, ledger_config);
+        fixture.extend(DEFAULT_TICKS_PER_SLOT * 2, |i, poh| match i {
+            20 => poh.insert_event(Event::Document { hash: [1u8; 32] }),
+            60 => poh.commit_event(&Event::Document { hash: [2u8; 32] }),
+            100 => poh.insert_event(Event::Document { hash: [3u8; 32] }),
+            _ => poh.next_tick(),
+        });
+        let records: &[PoHRecord] = &fixture.records;
+        let reader: LedgerReader = fixture.reader();
+
+        // At the nominal rate the hashes between two records take exactly their tick distance.
+        let proof: OrderingProof = prove_order(&reader, &records[20], &records[100], DEFAULT_HASHES_PER_SECOND).expect("Failed to prove order.");
+        assert_eq!(proof.hashes(), 80 * DEFAULT_HASHES_PER_TICK);
+        assert_eq!(proof.steps.iter().filter(|step| step.data.is_some()).count(), 1);
+        let elapsed: Duration = verify_order(&proof).expect("Ordering proof does not verify.");
+        assert_eq!(
+            elapsed,
+            Duration::from_nanos(80 * DEFAULT_HASHES_PER_TICK * 1_000_000_000 / DEFAULT_HASHES_PER_SECOND)
+        );
+        assert_eq!(u128::from(proof.min_elapsed_us), elapsed.as_micros());
+
+        let path: PathBuf = fixture.dir.0.join("order.json");
+        proof.save(&path).unwrap();
+        assert_eq!(verify_order(&OrderingProof::load(&path).unwrap()).unwrap(), elapsed);
+
+        // Faster hardware proves less time, a generous rate never makes the claim false.
+        let fast: OrderingProof = prove_order(&reader, &records[20], &records[100], 2 * DEFAULT_HASHES_PER_SECOND).unwrap();
+        assert_eq!(verify_order(&fast).unwrap(), elapsed / 2);
+
+        assert!(prove_order(&reader, &records[100], &records[20], DEFAULT_HASHES_PER_SECOND).is_err());
+        assert!(prove_order(&reader, &records[20], &records[20], DEFAULT_HASHES_PER_SECOND).is_err());
+        assert!(prove_order(&reader, &records[20], &records[100], 0).is_err());
+        let mut foreign: PoHRecord = records[100].clone();
+        foreign.hash = [0u8; 32];
+        assert!(prove_order(&reader, &records[20], &foreign, DEFAULT_HASHES_PER_SECOND).is_err());
+
+        // Overstated time, a lower declared rate, a changed event or a shortened chain are all rejected.
+        let mut tampered: OrderingProof = proof.clone();
+        tampered.min_elapsed_us += 1;
+        assert!(verify_order(&tampered).is_err());
+        let mut tampered: OrderingProof = proof.clone();
+        tampered.max_hash_rate *= 2;
+        assert!(verify_order(&tampered).is_err());
+        let mut tampered: OrderingProof = proof.clone();
+        tampered.first.event = Some(Event::Document { hash: [9u8; 32] });
+        assert!(verify_order(&tampered).is_err());
+        let mut tampered: OrderingProof = proof.clone();
+        tampered.steps[0].num_hashes -= DEFAULT_HASHES_PER_TICK;
+        assert!(verify_order(&tampered).is_err());
+        let mut tampered: OrderingProof = proof.clone();
+        std::mem::swap(&mut tampered.first, &mut tampered.second);
+        assert!(verify_order(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_receipts() {
+        let identity: Identity = Identity::generate().unwrap();
+        let public_key: [u8; 32] = identity.public_key();
+        let digest: [u8; 32] = hash::hash(b"contract.pdf");
+        let mut fixture: LedgerFixture = LedgerFixture::new("receipt", Some(identity), LedgerConfig::default());
+        fixture.extend(DEFAULT_TICKS_PER_SLOT * 2, |i, poh| match i {
+            40 => poh.insert_event(Event::Document { hash: digest }),
+            _ => poh.next_tick(),
+        });
+
+        let mut receipt: Receipt = Receipt::stamp(&fixture.reader(), digest, public_key).expect("Failed to stamp digest.");
+        assert_eq!(receipt.proof.record.tick_index, 40);
+        assert_eq!(receipt.anchor().tick_index, DEFAULT_TICKS_PER_SLOT - 1);
+        assert_eq!(receipt.verify(&public_key).expect("Receipt does not verify.").slot_index, 0);
+        assert!(Receipt::stamp(&fixture.reader(), [1u8; 32], public_key).is_err());
+
+        // Both formats round trip to the same receipt.
+        let json: PathBuf = fixture.dir.0.join("receipt.json");
+        let binary: PathBuf = fixture.dir.0.join("receipt.bin");
+        receipt.save(&json).unwrap();
+        receipt.save(&binary).unwrap();
+        assert_eq!(fs::read(&json).unwrap()[0], b'{');
+        assert_eq!(&fs::read(&binary).unwrap()[..4], b"POHR");
+        for path in [&json, &binary] {
+            let loaded: Receipt = Receipt::load(path).expect("Failed to load receipt.");
+            assert_eq!(loaded.encode().unwrap(), receipt.encode().unwrap());
+            loaded.verify(&public_key).expect("Loaded receipt does not verify.");
+        }
+
+        // Upgraded to the newest signed slot hash, and again once the chain grows.
+        assert!(receipt.upgrade(&fixture.reader()).unwrap());
+        assert_eq!(receipt.anchor().slot_index, 1);
+        assert!(!receipt.upgrade(&fixture.reader()).unwrap());
+        fixture.extend(DEFAULT_TICKS_PER_SLOT * 2 + 10, |i, poh| match i {
+            70 => poh.commit_event(&Event::Document { hash: [2u8; 32] }),
+            _ => poh.next_tick(),
+        });
+        assert!(receipt.upgrade(&fixture.reader()).unwrap());
+        assert_eq!(receipt.upgrades.len(), 2);
+        assert_eq!(receipt.anchor().slot_index, 3);
+        assert_eq!(receipt.verify(&public_key).unwrap().tick_index, 4 * DEFAULT_TICKS_PER_SLOT - 1);
+        receipt.save(&binary).unwrap();
+        assert_eq!(Receipt::load(&binary).unwrap().verify(&public_key).unwrap().slot_index, 3);
+        // Bytes after the last upgrade are refused, as from two receipts written into one file.
+        let mut concatenated: Vec<u8> = fs::read(&binary).unwrap();
+        concatenated.extend_from_slice(&receipt.encode().unwrap());
+        let padded: PathBuf = fixture.dir.0.join("padded.bin");
+        fs::write(&padded, &concatenated).unwrap();
+        assert!(format!("{:#}", Receipt::load(&padded).unwrap_err()).contains("trailing bytes"));
+
+        // Another key, digest, unsigned anchor, altered chain or unknown version are all rejected.
+        assert!(receipt.verify(&Identity::generate().unwrap().public_key()).is_err());
+        let mut tampered: Receipt = receipt.clone();
+        tampered.digest = [3u8; 32];
+        assert!(tampered.verify(&public_key).is_err());
+        let mut tampered: Receipt = receipt.clone();
+        tampered.upgrades[1].anchor.signature = None;
+        assert!(tampered.verify(&public_key).is_err());
+        let mut tampered: Receipt = receipt.clone();
+        tampered.upgrades[1].steps[0].num_hashes += DEFAULT_HASHES_PER_TICK;
+        assert!(tampered.verify(&public_key).is_err());
+        let mut tampered: Receipt = receipt.clone();
+        tampered.upgrades.remove(0);
+        assert!(tampered.verify(&public_key).is_err());
+        let mut tampered: Receipt = receipt.clone();
+        tampered.proof.algorithm ^= 1;
+        assert!(tampered.verify(&public_key).is_err());
+        let mut tampered: Receipt = receipt.clone();
+        tampered.version += 1;
+        assert!(tampered.verify(&public_key).is_err());
+        tampered.save(&json).unwrap();
+        assert!(Receipt::load(&json).is_err());
+    }
+
+    #[test]
+    fn test_randomness_beacon() {
+        let identity: Identity = Identity::generate().unwrap();
+        let public_key: [u8; 32] = identity.public_key();
+        let mut fixture: LedgerFixture = LedgerFixture::new("beacon", Some(identity), LedgerConfig::default());
+        fixture.extend(DEFAULT_TICKS_PER_SLOT * 3 + 5, |i, poh| match i {
+            150 => poh.insert_event(Event::Document { hash: [1u8; 32] }),
+            _ => poh.next_tick(),
+        });
+        let records: &[PoHRecord] = &fixture.records;
+        let reader: LedgerReader = fixture.reader();
+
+        // Values are reproducible from the ledger and from replaying the chain, and differ between slots and modes.
+        let unsigned: BeaconValue = randomness_at(&reader, 1, None).expect("Failed to derive slot randomness.");
+        let signed: BeaconValue = randomness_at(&reader, 1, Some(&public_key)).expect("Failed to derive signed slot randomness.");
+        assert_eq!(unsigned, randomness_at(&reader, 1, None).unwrap());
+        assert_eq!(unsigned.slot_hash, records[2 * DEFAULT_TICKS_PER_SLOT as usize - 1].hash);
+        assert_ne!(unsigned.randomness, signed.randomness);
+        assert_ne!(unsigned.randomness, randomness_at(&reader, 0, None).unwrap().randomness);
+        let mut replay: PoH = PoH::from_record(&records[DEFAULT_TICKS_PER_SLOT as usize - 1]);
+        let slot_end: PoHRecord = (0..DEFAULT_TICKS_PER_SLOT).map(|_| fixture.sign(replay.next_tick())).last().unwrap();
+        assert_eq!(BeaconValue::from_slot_end(&slot_end, Some(&public_key)).unwrap(), signed);
+        verify_randomness(&unsigned, None).expect("Unsigned beacon value does not verify.");
+        verify_randomness(&signed, Some(&public_key)).expect("Signed beacon value does not verify.");
+
+        // Nothing about a slot's value is known before its own hashes exist: an open slot has none,
+        // and a different event in the slot changes its value but not that of the slot before.
+        assert!(randomness_at(&reader, 3, None).is_err());
+        assert!(randomness_at(&reader, 4, None).is_err());
+        let mut fork: PoH = PoH::from_record(&records[2 * DEFAULT_TICKS_PER_SLOT as usize - 1]);
+        let forked: PoHRecord = (2 * DEFAULT_TICKS_PER_SLOT..3 * DEFAULT_TICKS_PER_SLOT)
+            .map(|i| match i {
+                150 => fork.insert_event(Event::Document { hash: [2u8; 32] }),
+                _ => fork.next_tick(),
+            })
+            .last()
+            .unwrap();
+        let original: BeaconValue = randomness_at(&reader, 2, None).unwrap();
+        assert_ne!(BeaconValue::from_slot_end(&forked, None).unwrap().randomness, original.randomness);
+        assert_eq!(randomness_at(&reader, 1, None).unwrap(), unsigned);
+
+        // Altered values, a missing or foreign key and records that do not close a slot are rejected.
+        let mut tampered: BeaconValue = unsigned.clone();
+        tampered.randomness[0] ^= 1;
+        assert!(verify_randomness(&tampered, None).is_err());
+        let mut tampered: BeaconValue = signed.clone();
+        tampered.slot_hash[0] ^= 1;
+        assert!(verify_randomness(&tampered, Some(&public_key)).is_err());
+        assert!(verify_randomness(&signed, None).is_err());
+        assert!(verify_randomness(&unsigned, Some(&public_key)).is_err());
+        assert!(verify_randomness(&signed, Some(&Identity::generate().unwrap().public_key())).is_err());
+        assert!(BeaconValue::from_slot_end(&records[10], None).is_err());
+    }
+
+    #[test]
+    fn test_recent_hash_event_expiry() {
+        // The ring keeps the newest slot hashes and ages events by the slot they would be recorded in.
+        let mut recent: RecentHashes = RecentHashes::new(3);
+        for slot in 0..5u64 {
+            recent.push(slot, [slot as u8; 32]);
+        }
+        assert_eq!(recent.hashes.len(), 3);
+        assert_eq!(recent.slot_of(&[1u8; 32]), None);
+        assert_eq!(recent.slot_of(&[4u8; 32]), Some(4));
+        assert_eq!(recent.check(&[3u8; 32], 2, 5), Ok(3));
+        assert_eq!(
+            recent.check(&[2u8; 32], 2, 5),
+            Err(EventRejection::Expired {
+                slot: 2,
+                age: 3,
+                max_age_slots: 2
+            })
+        );
+        assert_eq!(recent.check(&[1u8; 32], 10, 5), Err(EventRejection::UnknownReference));
+
+        let service: PoHService = thread::service(&[0u8; 64], DEFAULT_TICKS_PER_SLOT * 5, test_config()).expect("Failed to spawn PoH thread.");
+        let fresh: Event = Event::Document { hash: [1u8; 32] };
+        let mut replies: Vec<Receiver<Result<u64, EventRejection>>> = Vec::new();
+        let mut records: Vec<PoHRecord> = Vec::new();
+        for record in service.receiver.iter() {
+            // Submitted as soon as the slot hash is seen, and again once it is three slots old.
+            if record.tick_index == DEFAULT_TICKS_PER_SLOT - 1 {
+                replies.push(service.submit_referenced(fresh.clone(), &record.hash, 2).unwrap());
+                replies.push(service.submit_referenced(fresh.clone(), &[7u8; 32], 1).unwrap());
+            }
+            if record.tick_index == 3 * DEFAULT_TICKS_PER_SLOT {
+                let reference: [u8; 32] = records[DEFAULT_TICKS_PER_SLOT as usize - 1].hash;
+                replies.push(service.submit_referenced(fresh.clone(), &reference, 1).unwrap());
+                // A replayed copy of the recorded event is checked on the plain channel too.
+                let replayed: Event = Event::Referenced {
+                    reference,
+                    max_age_slots: 2,
+                    event: Box::new(fresh.clone()),
+                };
+                service.events.send(replayed).unwrap();
+            }
+            records.push(record);
+        }
+
+        // The submitter learns the tick a fresh event landed on, or why it was turned away.
+        let answers: Vec<Result<u64, EventRejection>> = replies.iter().map(|reply| reply.recv_timeout(Duration::from_secs(5)).unwrap()).collect();
+        let tick: u64 = answers[0].clone().expect("Fresh event was rejected.");
+        assert!((1..=2).contains(&(tick / DEFAULT_TICKS_PER_SLOT)));
+        // The reference is recorded with the event, so it is hashed into the chain.
+        let recorded: Event = Event::Referenced {
+            reference: records[DEFAULT_TICKS_PER_SLOT as usize - 1].hash,
+            max_age_slots: 2,
+            event: Box::new(fresh.clone()),
+        };
+        assert_eq!(records[tick as usize].event.as_ref(), Some(&recorded));
+        assert_eq!(answers[1], Err(EventRejection::UnknownReference));
+        assert!(matches!(answers[2], Err(EventRejection::Expired { slot: 0, age, max_age_slots: 1 }) if age >= 3));
+        assert_eq!(records.iter().filter(|record| record.event.as_ref() == Some(&recorded)).count(), 1);
+        assert!(
+            records
+                .iter()
+                .all(|record| record.event.as_ref().is_none_or(|event| event.reference().is_none() || event == &recorded))
+        );
+        assert!(answers[2].as_ref().unwrap_err().to_string().contains("expired"));
+
+        // Verifiers check the reference against the chain itself.
+        assert!(PoH::verify_records(&records));
+        assert!(PoH::verify_referenced_records(&records));
+        let mut late: Vec<PoHRecord> = records.clone();
+        late[4 * DEFAULT_TICKS_PER_SLOT as usize + 1].event = Some(recorded.clone());
+        assert!(!PoH::verify_referenced_records(&late), "Expired reference was accepted.");
+        assert!(
+            !PoH::verify_referenced_records(&records[DEFAULT_TICKS_PER_SLOT as usize..]),
+            "Unresolved reference was accepted."
+        );
+    }
 }
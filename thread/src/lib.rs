@@ -4,7 +4,7 @@ pub mod native {
     mod handle;
     pub mod manager;
     mod platform;
-    mod policy;
+    pub mod policy;
     pub mod pool;
     pub mod types;
 }
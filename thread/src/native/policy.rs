@@ -1,24 +1,54 @@
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::thread as std_thread;
 
 use crate::native::{
     platform::{set_affinity, set_priority},
-    types::CoreAllocation,
+    types::{AppliedPolicy, CoreAllocation},
 };
 
 use anyhow::Result;
 
+thread_local! {
+    static APPLIED_POLICY: RefCell<AppliedPolicy> = RefCell::new(AppliedPolicy::default());
+}
+
+impl Display for AppliedPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let cores: String = if self.cores.is_empty() {
+            "OS default cores".to_string()
+        } else {
+            format!("cores {:?}", self.cores)
+        };
+        let priority: String = if self.priority == 0 {
+            "default priority".to_string()
+        } else {
+            format!("priority {}", self.priority)
+        };
+        return write!(f, "{}: {}, {}", self.thread_name, cores, priority);
+    }
+}
+
 /// Apply thread policy based on core allocation and priority.
+/// Whatever succeeds is recorded and can be read back with [`applied_policy`].
 pub fn apply_policy(alloc: &CoreAllocation, priority: u8, cores: &[usize]) -> Result<()> {
+    let thread_name: String = std_thread::current().name().unwrap_or("unknown").to_string();
+    APPLIED_POLICY.with(|applied| {
+        *applied.borrow_mut() = AppliedPolicy {
+            thread_name: thread_name.clone(),
+            ..Default::default()
+        }
+    });
     // Set thread priority.
     set_priority(priority)?;
+    APPLIED_POLICY.with(|applied| applied.borrow_mut().priority = priority);
     // Apply core affinity based on allocation strategy.
-    match alloc {
+    let pinned: Vec<usize> = match alloc {
         CoreAllocation::PinnedCores { .. } => {
             if !cores.is_empty() {
                 // For pinned cores, we pick one core for this thread.
                 // Use a stable approach to select a core without using thread::current().id().as_u64().
                 // Generate a pseudo-random core selection based on thread name hash.
-                let thread_name: String = std_thread::current().name().unwrap_or("unknown").to_string();
                 let name_hash: u64 = thread_name.bytes().fold(0u64, |acc, b| acc.wrapping_add(b as u64));
                 #[allow(clippy::arithmetic_side_effects)]
                 let core_idx: usize = if cores.is_empty() {
@@ -28,6 +58,9 @@ pub fn apply_policy(alloc: &CoreAllocation, priority: u8, cores: &[usize]) -> Re
                     (name_hash % len) as usize
                 };
                 set_affinity(&[cores[core_idx]])?;
+                vec![cores[core_idx]]
+            } else {
+                vec![]
             }
         }
         CoreAllocation::DedicatedCoreSet { .. } => {
@@ -35,10 +68,25 @@ pub fn apply_policy(alloc: &CoreAllocation, priority: u8, cores: &[usize]) -> Re
             if !cores.is_empty() {
                 set_affinity(cores)?;
             }
+            cores.to_vec()
         }
         CoreAllocation::OsDefault => {
             // Let OS handle thread scheduling.
+            vec![]
         }
-    }
+    };
+    APPLIED_POLICY.with(|applied| applied.borrow_mut().cores = pinned);
     return Ok(());
 }
+
+/// Get the policy that took effect on the calling thread.
+/// Threads not spawned through a [`crate::native::types::Manager`] report OS defaults.
+pub fn applied_policy() -> AppliedPolicy {
+    return APPLIED_POLICY.with(|applied| {
+        let mut policy: AppliedPolicy = applied.borrow().clone();
+        if policy.thread_name.is_empty() {
+            policy.thread_name = std_thread::current().name().unwrap_or("unknown").to_string();
+        }
+        policy
+    });
+}
@@ -29,6 +29,16 @@ pub enum CoreAllocation {
     DedicatedCoreSet { min: usize, max: usize },
 }
 
+/// Affinity and priority that actually took effect on a thread.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct AppliedPolicy {
+    pub thread_name: String,
+    /// Cores the thread is pinned to, empty when left to the OS.
+    pub cores: Vec<usize>,
+    /// Priority that was set, 0 when left unchanged.
+    pub priority: u8,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
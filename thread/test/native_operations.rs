@@ -10,8 +10,8 @@ mod tests {
     };
 
     use anyhow::Result;
-    use thread::native::types::{Config, CoreAllocation, Manager};
-    use thread::native::{manager::default_manager, pool::default_pool};
+    use thread::native::types::{AppliedPolicy, Config, CoreAllocation, Manager};
+    use thread::native::{manager::default_manager, policy::applied_policy, pool::default_pool};
 
     #[test]
     fn test_thread_manager_basic() -> Result<()> {
@@ -313,6 +313,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_applied_policy_reporting() -> Result<()> {
+        // Default threads are left to the OS.
+        let manager = default_manager("policy-default")?;
+        let applied = manager.spawn_named("policy-os".to_string(), applied_policy)?.join().unwrap();
+        assert_eq!(applied.thread_name, "policy-os");
+        assert!(applied.cores.is_empty());
+        assert_eq!(applied.priority, 0);
+
+        // A dedicated core set reports the cores it was pinned to.
+        let config = Config {
+            core_allocation: CoreAllocation::DedicatedCoreSet { min: 0, max: 0 },
+            ..Default::default()
+        };
+        let manager = Manager::new("policy-pinned".to_string(), config)?;
+        let applied: AppliedPolicy = manager.spawn(applied_policy)?.join().unwrap();
+        // Affinity may be refused without privileges, in which case nothing is reported as pinned.
+        assert!(applied.cores.is_empty() || applied.cores == vec![0]);
+        assert_eq!(applied.thread_name, "policy-pinned-0");
+
+        Ok(())
+    }
+
     #[test]
     fn test_thread_manager_panic_handling() -> Result<()> {
         let manager = default_manager("panic-test")?;